@@ -8,15 +8,16 @@
 use as_ffi_bindings::{BufferPtr, Read as ASRead, StringPtr, Write as ASWrite};
 use function_name::named;
 use std::ops::Add;
+use std::time::Instant;
 use wasmer::{AsStoreMut, AsStoreRef, FunctionEnvMut, Memory};
 
-use super::env::{get_remaining_points, sub_remaining_gas_abi, ASEnv};
-use crate::settings;
+use super::env::{get_remaining_points, sub_remaining_gas, sub_remaining_gas_abi, ASEnv};
 #[cfg(feature = "execution-trace")]
 use crate::{
     into_trace_value,
     types::{AbiTrace, AbiTraceType},
 };
+use crate::{settings, types::CounterOverflow, AbortInfo};
 
 use super::common::{call_module, create_sc, function_exists, local_call};
 use super::error::{abi_bail, ABIResult};
@@ -43,12 +44,22 @@ pub(crate) fn get_env(ctx: &FunctionEnvMut<ASEnv>) -> ABIResult<ASEnv> {
     }
 }
 
+/// Fails if the execution is pure, for the ABIs observing or moving coins, see
+/// `RunOptions::pure`
+fn check_not_pure(env: &ASEnv, abi_name: &str) -> ABIResult<()> {
+    if env.options.pure {
+        abi_bail!(format!("{} is forbidden in pure mode", abi_name));
+    }
+    Ok(())
+}
+
 /// Get the coins that have been made available for a specific purpose for the
 /// current call.
 #[named]
 pub(crate) fn assembly_script_get_call_coins(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i64> {
     let env = get_env(&ctx)?;
     sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    check_not_pure(&env, function_name!())?;
     let res = env.get_interface().get_call_coins()? as i64;
     #[cfg(feature = "execution-trace")]
     ctx.data_mut().trace.push(AbiTrace {
@@ -60,6 +71,68 @@ pub(crate) fn assembly_script_get_call_coins(mut ctx: FunctionEnvMut<ASEnv>) ->
     Ok(res)
 }
 
+/// Get the coins deposited by the operation that originated the current
+/// execution.
+///
+/// This differs from the call coins, which are the coins forwarded to the
+/// current call by its caller, and from the balance, which is the amount owned
+/// by the current address.
+#[named]
+pub(crate) fn assembly_script_get_op_deposit(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i64> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    check_not_pure(&env, function_name!())?;
+    let res = env.get_interface().get_op_deposit()? as i64;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
+/// Get the version of the runtime executing the contract, see
+/// `RUNTIME_VERSION`
+#[named]
+pub(crate) fn assembly_script_get_runtime_version(
+    mut ctx: FunctionEnvMut<ASEnv>,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let res = settings::RUNTIME_VERSION as i32;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
+/// Get the priority tier of the operation that originated the current
+/// execution.
+///
+/// Tiers are assigned by the node fee market: 0 is the standard tier and
+/// higher values denote higher priority operations. The tier is fixed for the
+/// whole operation and does not change along the call stack.
+#[named]
+pub(crate) fn assembly_script_get_priority_tier(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let res = env.get_interface().get_priority_tier()?;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res as i32)
+}
+
 /// Transfer an amount from the address on the current call stack to a target
 /// address.
 #[named]
@@ -70,6 +143,7 @@ pub(crate) fn assembly_script_transfer_coins(
 ) -> ABIResult<()> {
     let env = get_env(&ctx)?;
     sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    check_not_pure(&env, function_name!())?;
     if raw_amount.is_negative() {
         abi_bail!("Negative raw amount.");
     }
@@ -80,8 +154,15 @@ pub(crate) fn assembly_script_transfer_coins(
     //     let fname = format!("massa.{}:0", function_name!());
     //     param_size_update(&env, &mut ctx, &fname, to_address.len(), true);
     // }
-    env.get_interface()
-        .transfer_coins(&to_address, raw_amount as u64)?;
+    if env.options.bootstrap {
+        env.get_interface()
+            .transfer_coins_bootstrap(None, &to_address, raw_amount as u64)?;
+    } else {
+        env.get_interface()
+            .transfer_coins(&to_address, raw_amount as u64)?;
+    }
+    ctx.data_mut()
+        .record_payment(&to_address, raw_amount as u64);
     #[cfg(feature = "execution-trace")]
     {
         let call_stack = env.get_interface().get_call_stack();
@@ -116,6 +197,7 @@ pub(crate) fn assembly_script_transfer_coins_for(
 ) -> ABIResult<()> {
     let env = get_env(&ctx)?;
     sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    check_not_pure(&env, function_name!())?;
     if raw_amount.is_negative() {
         abi_bail!("Negative raw amount.");
     }
@@ -129,8 +211,16 @@ pub(crate) fn assembly_script_transfer_coins_for(
     //     let fname = format!("massa.{}:1", function_name!());
     //     param_size_update(&env, &mut ctx, &fname, to_address.len(), true);
     // }
-    env.get_interface()
-        .transfer_coins_for(&from_address, &to_address, raw_amount as u64)?;
+    if env.options.bootstrap {
+        env.get_interface().transfer_coins_bootstrap(
+            Some(&from_address),
+            &to_address,
+            raw_amount as u64,
+        )?;
+    } else {
+        env.get_interface()
+            .transfer_coins_for(&from_address, &to_address, raw_amount as u64)?;
+    }
     #[cfg(feature = "execution-trace")]
     ctx.data_mut().trace.push(AbiTrace {
         name: function_name!().to_string(),
@@ -149,6 +239,7 @@ pub(crate) fn assembly_script_transfer_coins_for(
 pub(crate) fn assembly_script_get_balance(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i64> {
     let env = get_env(&ctx)?;
     sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    check_not_pure(&env, function_name!())?;
     let res = env.get_interface().get_balance()? as i64;
     #[cfg(feature = "execution-trace")]
     ctx.data_mut().trace.push(AbiTrace {
@@ -167,6 +258,7 @@ pub(crate) fn assembly_script_get_balance_for(
 ) -> ABIResult<i64> {
     let env = get_env(&ctx)?;
     sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    check_not_pure(&env, function_name!())?;
     let memory = get_memory!(env);
     let address = read_string(memory, &ctx, address)?;
     // Do not remove this. It could be used for gas_calibration in future.
@@ -185,6 +277,81 @@ pub(crate) fn assembly_script_get_balance_for(
     Ok(res)
 }
 
+/// Get the coins the current address can still transfer during this call.
+///
+/// Unlike the balance at execution start, this reflects the transfers already
+/// made during the current execution.
+#[named]
+pub(crate) fn assembly_script_get_remaining_transferable(
+    mut ctx: FunctionEnvMut<ASEnv>,
+) -> ABIResult<i64> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    check_not_pure(&env, function_name!())?;
+    let res = env.get_interface().get_remaining_transferable()? as i64;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
+/// Get the signed difference between the current balance and the balance of
+/// the current address when the execution started, i.e. after the coins of
+/// the call were received.
+///
+/// Deltas that do not fit in an i64 are saturated to `i64::MIN` /
+/// `i64::MAX`.
+#[named]
+pub(crate) fn assembly_script_get_balance_delta(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i64> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    check_not_pure(&env, function_name!())?;
+    let Some(initial_balance) = env.initial_balance else {
+        abi_bail!("balance at the start of the execution is unavailable");
+    };
+    let balance = env.get_interface().get_balance()?;
+    let res = (balance as i128 - initial_balance as i128).clamp(i64::MIN as i128, i64::MAX as i128)
+        as i64;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
+/// Get the total amount of coins the current execution sent so far to
+/// `address`, with `transfer_coins` or as coins of a `call`.
+///
+/// Only the transfers made by the current execution are counted, not the ones
+/// of its sub-calls nor of previous executions: the total starts from 0 on
+/// every run.
+#[named]
+pub(crate) fn assembly_script_get_paid_to(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    address: i32,
+) -> ABIResult<i64> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let address = read_string(memory, &ctx, address)?;
+    let res = ctx.data().paid_to.get(&address).copied().unwrap_or(0) as i64;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(address)],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
 /// Raw call that have the right type signature to be able to be call a module
 /// directly form AssemblyScript:
 #[named]
@@ -213,6 +380,8 @@ pub(crate) fn assembly_script_call(
     // }
 
     let response = call_module(&mut ctx, &address, &function, &param, call_coins)?;
+    // call_module rejects negative amounts
+    ctx.data_mut().record_payment(&address, call_coins as u64);
     #[cfg(feature = "execution-trace")]
     ctx.data_mut().trace.push(AbiTrace {
         name: function_name!().to_string(),
@@ -277,6 +446,147 @@ pub(crate) fn assembly_script_print(mut ctx: FunctionEnvMut<ASEnv>, arg: i32) ->
     Ok(())
 }
 
+/// Returns Ok(1) if the remaining gas, after paying for this call, is at
+/// least `amount`, otherwise Ok(0)
+#[named]
+pub(crate) fn assembly_script_has_gas_at_least(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    amount: i64,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let res = get_remaining_points(&env, &mut ctx)? as i128 >= amount as i128;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(amount)],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res as i32)
+}
+
+/// Estimate the gas a call to the ABI named `abi` (the import name, e.g.
+/// `assembly_script_set_data`) would cost with `size` bytes of input, under
+/// the current gas costs.
+///
+/// The estimate is the base cost of the ABI plus `size` times its per-byte
+/// cost (the `<abi>_byte` entry of the cost table) if it has one. Returns -1
+/// if the ABI has no entry in the cost table.
+#[named]
+pub(crate) fn assembly_script_estimate_abi_cost(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    abi: i32,
+    size: i64,
+) -> ABIResult<i64> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let abi = read_string(memory, &ctx, abi)?;
+    let size: u64 = match size.try_into() {
+        Ok(size) => size,
+        Err(_) => abi_bail!("negative size in estimate_abi_cost"),
+    };
+    let abi_costs = env.get_gas_costs().abi_costs;
+    let res = match abi_costs.get(&abi) {
+        Some(base) => {
+            let per_byte = abi_costs.get(&format!("{}_byte", abi)).copied();
+            let cost = base.saturating_add(per_byte.unwrap_or(0).saturating_mul(size));
+            cost.min(i64::MAX as u64) as i64
+        }
+        None => -1,
+    };
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(abi), into_trace_value!(size)],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
+/// Get the number of operators executed so far by the current call, derived
+/// from the gas it consumed: the consumed metering points divided by the
+/// operator cost.
+///
+/// As the gas charged by the ABIs and the operators with a specific cost
+/// (`call_indirect`) are counted in operator cost units, this is a
+/// compute-progress signal rather than an exact count.
+#[named]
+pub(crate) fn assembly_script_get_op_count(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i64> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let Some(start_points) = env.start_points else {
+        abi_bail!("Operator count is unavailable");
+    };
+    let remaining_points = get_remaining_points(&env, &mut ctx)?;
+    let operator_cost = env.get_gas_costs().operator_cost.max(1);
+    let res = (start_points.saturating_sub(remaining_points) / operator_cost) as i64;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
+/// Get the milliseconds left before the execution time limit, or -1 if there
+/// is no limit. The limit starts with the top-level execution and is shared
+/// with the sub-calls, see `RunOptions::max_execution_time`.
+///
+/// The value is NOT deterministic: it may only be used to checkpoint or stop
+/// early, never to influence a consensus-critical output.
+#[named]
+pub(crate) fn assembly_script_get_remaining_time_ms(
+    mut ctx: FunctionEnvMut<ASEnv>,
+) -> ABIResult<i64> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let res = match env.execution_deadline {
+        Some(deadline) => deadline
+            .saturating_duration_since(Instant::now())
+            .as_millis()
+            .min(i64::MAX as u128) as i64,
+        None => -1,
+    };
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
+/// Get the logical clock of the execution: the gas consumed since the
+/// top-level execution started.
+///
+/// The clock is deterministic and shared by the sub-calls, so it is monotonic
+/// along the whole execution, and strictly increasing between two calls to
+/// this ABI as each of them costs gas. It is reset for every top-level
+/// execution.
+#[named]
+pub(crate) fn assembly_script_get_logical_clock(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i64> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let Some(clock_origin) = env.clock_origin else {
+        abi_bail!("Logical clock is unavailable");
+    };
+    let res = clock_origin.saturating_sub(get_remaining_points(&env, &mut ctx)?) as i64;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
 /// Get the operation datastore keys (aka entries)
 #[named]
 pub(crate) fn assembly_script_get_op_keys(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i32> {
@@ -287,6 +597,8 @@ pub(crate) fn assembly_script_get_op_keys(mut ctx: FunctionEnvMut<ASEnv>) -> ABI
         Ok(keys) => {
             let fmt_keys =
                 ser_bytearray_vec(&keys, keys.len(), settings::max_op_datastore_entry_count())?;
+            ctx.data_mut().consume_op_data_budget(fmt_keys.len())?;
+            ctx.data_mut().consume_read_budget(fmt_keys.len())?;
             let ptr = pointer_from_bytearray(&env, &mut ctx, &fmt_keys)?.offset();
 
             #[cfg(feature = "execution-trace")]
@@ -321,6 +633,8 @@ pub(crate) fn assembly_script_get_op_keys_prefix(
         Ok(keys) => {
             let fmt_keys =
                 ser_bytearray_vec(&keys, keys.len(), settings::max_op_datastore_entry_count())?;
+            ctx.data_mut().consume_op_data_budget(fmt_keys.len())?;
+            ctx.data_mut().consume_read_budget(fmt_keys.len())?;
             let ptr = pointer_from_bytearray(&env, &mut ctx, &fmt_keys)?.offset();
 
             #[cfg(feature = "execution-trace")]
@@ -389,6 +703,8 @@ pub(crate) fn assembly_script_get_op_data(
     //     param_size_update(&env, &mut ctx, &fname, key_bytes.len(), true);
     // }
     let data = env.get_interface().get_op_data(&key_bytes)?;
+    ctx.data_mut().consume_op_data_budget(data.len())?;
+    ctx.data_mut().consume_read_budget(data.len())?;
     let ptr = pointer_from_bytearray(&env, &mut ctx, &data)?.offset() as i32;
 
     #[cfg(feature = "execution-trace")]
@@ -401,6 +717,88 @@ pub(crate) fn assembly_script_get_op_data(
     Ok(ptr)
 }
 
+/// Get the operation datastore values of several keys, in the order of the
+/// keys.
+///
+/// The keys are serialized as done by `ser_bytearray_vec`, the values as done
+/// by `ser_optional_values`. An additional cost is charged for each key and
+/// for each returned byte.
+#[named]
+pub(crate) fn assembly_script_get_op_data_multi(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    keys: i32,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let keys = deser_bytearray_vec(&read_buffer(memory, &ctx, keys)?)?;
+    if keys.len() > settings::max_op_datastore_entry_count() {
+        abi_bail!("Too many keys");
+    }
+    let abi_costs = env.get_gas_costs().abi_costs;
+    let key_cost = *abi_costs
+        .get("assembly_script_get_op_data_multi_key")
+        .ok_or_else(|| {
+            wasmer::RuntimeError::new("Failed to get gas for assembly_script_get_op_data_multi_key")
+        })?;
+    let byte_cost = *abi_costs
+        .get("assembly_script_get_op_data_multi_byte")
+        .ok_or_else(|| {
+            wasmer::RuntimeError::new(
+                "Failed to get gas for assembly_script_get_op_data_multi_byte",
+            )
+        })?;
+    sub_remaining_gas(&env, &mut ctx, key_cost.saturating_mul(keys.len() as u64))?;
+    let values = env.get_interface().get_op_data_multi(&keys)?;
+    if values.len() != keys.len() {
+        abi_bail!("Interface returned a wrong number of values");
+    }
+    let data = ser_optional_values(&values)?;
+    ctx.data_mut().consume_op_data_budget(data.len())?;
+    sub_remaining_gas(&env, &mut ctx, byte_cost.saturating_mul(data.len() as u64))?;
+    ctx.data_mut().consume_read_budget(data.len())?;
+    let ptr = pointer_from_bytearray(&env, &mut ctx, &data)?.offset() as i32;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(keys)],
+        return_value: data.into(),
+        sub_calls: None,
+    });
+    Ok(ptr)
+}
+
+/// Get the data attached to the deferred message that triggered the current
+/// execution, or an empty buffer if it was not triggered by a message.
+///
+/// Unlike the call parameter, which is chosen by the caller of each function,
+/// the message data is set when the message is sent and is the same for the
+/// whole execution.
+#[named]
+pub(crate) fn assembly_script_get_message_data(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let data = env.get_interface().get_message_data()?;
+    let byte_cost = *env
+        .get_gas_costs()
+        .abi_costs
+        .get("assembly_script_get_message_data_byte")
+        .ok_or_else(|| {
+            wasmer::RuntimeError::new("Failed to get gas for assembly_script_get_message_data_byte")
+        })?;
+    sub_remaining_gas(&env, &mut ctx, byte_cost.saturating_mul(data.len() as u64))?;
+    ctx.data_mut().consume_read_budget(data.len())?;
+    let ptr = pointer_from_bytearray(&env, &mut ctx, &data)?.offset() as i32;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: data.into(),
+        sub_calls: None,
+    });
+    Ok(ptr)
+}
+
 /// Read a bytecode string, representing the webassembly module binary encoded
 /// with in base64.
 #[named]
@@ -454,6 +852,87 @@ pub(crate) fn assembly_script_hash(mut ctx: FunctionEnvMut<ASEnv>, value: i32) -
     Ok(ptr as i32)
 }
 
+/// performs a hash on a bytearray with the selected algorithm and returns the
+/// hash
+///
+/// Algorithm selector:
+/// * 0: blake3
+/// * 1: sha256
+/// * 2: sha3-256
+/// * 3: keccak256
+#[named]
+pub(crate) fn assembly_script_hash_ex(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    algo: i32,
+    value: i32,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    let algo_name = match algo {
+        0 => "blake3",
+        1 => "sha256",
+        2 => "sha3_256",
+        3 => "keccak256",
+        _ => abi_bail!(format!("Unknown hash algorithm selector: {}", algo)),
+    };
+    sub_remaining_gas_abi(
+        &env,
+        &mut ctx,
+        &format!("{}_{}", function_name!(), algo_name),
+    )?;
+    let memory = get_memory!(env);
+    let bytes = read_buffer(memory, &ctx, value)?;
+    let interface = env.get_interface();
+    let hash = match algo {
+        0 => interface.hash_blake3(&bytes)?,
+        1 => interface.hash_sha256(&bytes)?,
+        2 => interface.hash_sha3_256(&bytes)?,
+        _ => interface.hash_keccak256(&bytes)?,
+    }
+    .to_vec();
+    let ptr = pointer_from_bytearray(&env, &mut ctx, &hash)?.offset();
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(algo), into_trace_value!(bytes)],
+        return_value: hash.into(),
+        sub_calls: None,
+    });
+    Ok(ptr as i32)
+}
+
+/// performs a blake3 hash on a bytearray and returns the hash
+///
+/// Unlike `assembly_script_hash_ex`, the gas charged grows with the size of
+/// the hashed data.
+#[named]
+pub(crate) fn assembly_script_hash_blake3(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    value: i32,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let bytes = read_buffer(memory, &ctx, value)?;
+    let byte_cost = *env
+        .get_gas_costs()
+        .abi_costs
+        .get("assembly_script_hash_blake3_byte")
+        .ok_or_else(|| {
+            wasmer::RuntimeError::new("Failed to get gas for assembly_script_hash_blake3_byte")
+        })?;
+    sub_remaining_gas(&env, &mut ctx, byte_cost.saturating_mul(bytes.len() as u64))?;
+    let hash = env.get_interface().hash_blake3(&bytes)?.to_vec();
+    let ptr = pointer_from_bytearray(&env, &mut ctx, &hash)?.offset();
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(bytes)],
+        return_value: hash.into(),
+        sub_calls: None,
+    });
+    Ok(ptr as i32)
+}
+
 /// performs a hash on a bytearray and returns the hash
 #[named]
 pub(crate) fn assembly_script_keccak256_hash(
@@ -478,61 +957,207 @@ pub(crate) fn assembly_script_keccak256_hash(
     Ok(ptr as i32)
 }
 
-/// Get keys (aka entries) in the datastore
+/// Get keys (aka entries) in the datastore
+#[named]
+pub(crate) fn assembly_script_get_keys(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    prefix: i32,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let prefix = read_buffer(memory, &ctx, prefix)?;
+    let prefix_opt = if !prefix.is_empty() {
+        Some(prefix.as_ref())
+    } else {
+        None
+    };
+    let keys = env.get_interface().get_keys(prefix_opt)?;
+    let fmt_keys = ser_bytearray_vec(&keys, keys.len(), settings::max_datastore_entry_count())?;
+    ctx.data_mut().consume_read_budget(fmt_keys.len())?;
+    let ptr = pointer_from_bytearray(&env, &mut ctx, &fmt_keys)?.offset();
+
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(prefix)],
+        return_value: fmt_keys.into(),
+        sub_calls: None,
+    });
+    Ok(ptr as i32)
+}
+
+/// Get keys (aka entries) in the datastore
+#[named]
+pub(crate) fn assembly_script_get_keys_for(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    address: i32,
+    prefix: i32,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let address = read_string(memory, &ctx, address)?;
+    let prefix = read_buffer(memory, &ctx, prefix)?;
+    let prefix_opt = if !prefix.is_empty() {
+        Some(prefix.as_ref())
+    } else {
+        None
+    };
+    let keys = env.get_interface().get_keys_for(&address, prefix_opt)?;
+    let fmt_keys = ser_bytearray_vec(&keys, keys.len(), settings::max_datastore_entry_count())?;
+    ctx.data_mut().consume_read_budget(fmt_keys.len())?;
+    let ptr = pointer_from_bytearray(&env, &mut ctx, &fmt_keys)?.offset();
+
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(address), into_trace_value!(prefix)],
+        return_value: AbiTraceType::ByteArrays(keys.iter().cloned().collect()),
+        sub_calls: None,
+    });
+    Ok(ptr as i32)
+}
+
+/// Get at most `max_count` keys of the datastore sharing the given prefix
+///
+/// On top of its flat cost, this ABI is charged for each returned key. Keys
+/// are returned in ascending order, serialized like `assembly_script_get_keys`.
+#[named]
+pub(crate) fn assembly_script_get_keys_prefix(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    prefix: i32,
+    max_count: i32,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let Ok(max_count) = usize::try_from(max_count) else {
+        abi_bail!("negative max_count");
+    };
+    let max_count = max_count.min(settings::max_datastore_entry_count());
+    let memory = get_memory!(env);
+    let prefix = read_buffer(memory, &ctx, prefix)?;
+    let keys = env.get_interface().get_keys_prefix(&prefix, max_count)?;
+    if keys.len() > max_count {
+        abi_bail!("Interface returned too many keys");
+    }
+    let key_cost = *env
+        .get_gas_costs()
+        .abi_costs
+        .get("assembly_script_get_keys_prefix_key")
+        .ok_or_else(|| {
+            wasmer::RuntimeError::new("Failed to get gas for assembly_script_get_keys_prefix_key")
+        })?;
+    sub_remaining_gas(&env, &mut ctx, key_cost.saturating_mul(keys.len() as u64))?;
+    let fmt_keys = ser_bytearray_vec(&keys, keys.len(), max_count)?;
+    ctx.data_mut().consume_read_budget(fmt_keys.len())?;
+    let ptr = pointer_from_bytearray(&env, &mut ctx, &fmt_keys)?.offset();
+
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![
+            into_trace_value!(prefix),
+            (stringify!(max_count), max_count as u64).into(),
+        ],
+        return_value: fmt_keys.into(),
+        sub_calls: None,
+    });
+    Ok(ptr as i32)
+}
+
+/// Get the datastore keys under `prefix` that differ between two addresses:
+/// keys present in only one of them, or present in both with different
+/// values.
+///
+/// The keys are sorted and serialized as done by `ser_bytearray_vec`.
+/// An additional cost is charged for each compared entry.
 #[named]
-pub(crate) fn assembly_script_get_keys(
+pub(crate) fn assembly_script_datastore_diff(
     mut ctx: FunctionEnvMut<ASEnv>,
+    address_a: i32,
+    address_b: i32,
     prefix: i32,
 ) -> ABIResult<i32> {
     let env = get_env(&ctx)?;
     sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
     let memory = get_memory!(env);
+    let address_a = read_string(memory, &ctx, address_a)?;
+    let address_b = read_string(memory, &ctx, address_b)?;
     let prefix = read_buffer(memory, &ctx, prefix)?;
     let prefix_opt = if !prefix.is_empty() {
         Some(prefix.as_ref())
     } else {
         None
     };
-    let keys = env.get_interface().get_keys(prefix_opt)?;
+    let (keys, compared_entries) = env
+        .get_interface()
+        .datastore_diff(&address_a, &address_b, prefix_opt)?;
+    let entry_cost = *env
+        .get_gas_costs()
+        .abi_costs
+        .get("assembly_script_datastore_diff_entry")
+        .ok_or_else(|| {
+            wasmer::RuntimeError::new("Failed to get gas for assembly_script_datastore_diff_entry")
+        })?;
+    sub_remaining_gas(&env, &mut ctx, entry_cost.saturating_mul(compared_entries))?;
     let fmt_keys = ser_bytearray_vec(&keys, keys.len(), settings::max_datastore_entry_count())?;
+    ctx.data_mut().consume_read_budget(fmt_keys.len())?;
     let ptr = pointer_from_bytearray(&env, &mut ctx, &fmt_keys)?.offset();
-
     #[cfg(feature = "execution-trace")]
     ctx.data_mut().trace.push(AbiTrace {
         name: function_name!().to_string(),
-        params: vec![into_trace_value!(prefix)],
-        return_value: fmt_keys.into(),
+        params: vec![
+            into_trace_value!(address_a),
+            into_trace_value!(address_b),
+            into_trace_value!(prefix),
+        ],
+        return_value: AbiTraceType::ByteArrays(keys.iter().cloned().collect()),
         sub_calls: None,
     });
     Ok(ptr as i32)
 }
 
-/// Get keys (aka entries) in the datastore
+/// Get the addresses allowed to write to the datastore of the given address,
+/// see `Interface::get_writers`.
+///
+/// The addresses are sorted and serialized as done by `ser_bytearray_vec`.
+/// An additional cost is charged for each returned address.
 #[named]
-pub(crate) fn assembly_script_get_keys_for(
+pub(crate) fn assembly_script_get_writers(
     mut ctx: FunctionEnvMut<ASEnv>,
     address: i32,
-    prefix: i32,
 ) -> ABIResult<i32> {
     let env = get_env(&ctx)?;
     sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
     let memory = get_memory!(env);
     let address = read_string(memory, &ctx, address)?;
-    let prefix = read_buffer(memory, &ctx, prefix)?;
-    let prefix_opt = if !prefix.is_empty() {
-        Some(prefix.as_ref())
-    } else {
-        None
-    };
-    let keys = env.get_interface().get_keys_for(&address, prefix_opt)?;
-    let fmt_keys = ser_bytearray_vec(&keys, keys.len(), settings::max_datastore_entry_count())?;
-    let ptr = pointer_from_bytearray(&env, &mut ctx, &fmt_keys)?.offset();
-
+    let writers = env.get_interface().get_writers(&address)?;
+    let entry_cost = *env
+        .get_gas_costs()
+        .abi_costs
+        .get("assembly_script_get_writers_entry")
+        .ok_or_else(|| {
+            wasmer::RuntimeError::new("Failed to get gas for assembly_script_get_writers_entry")
+        })?;
+    sub_remaining_gas(
+        &env,
+        &mut ctx,
+        entry_cost.saturating_mul(writers.len() as u64),
+    )?;
+    let writers: Vec<Vec<u8>> = writers.into_iter().map(String::into_bytes).collect();
+    let fmt_writers = ser_bytearray_vec(
+        &writers,
+        writers.len(),
+        settings::max_datastore_entry_count(),
+    )?;
+    ctx.data_mut().consume_read_budget(fmt_writers.len())?;
+    let ptr = pointer_from_bytearray(&env, &mut ctx, &fmt_writers)?.offset();
     #[cfg(feature = "execution-trace")]
     ctx.data_mut().trace.push(AbiTrace {
         name: function_name!().to_string(),
-        params: vec![into_trace_value!(address), into_trace_value!(prefix)],
-        return_value: AbiTraceType::ByteArrays(keys.iter().cloned().collect()),
+        params: vec![into_trace_value!(address)],
+        return_value: AbiTraceType::ByteArrays(writers),
         sub_calls: None,
     });
     Ok(ptr as i32)
@@ -549,7 +1174,7 @@ pub(crate) fn assembly_script_set_data(
     let env = get_env(&ctx)?;
     sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
     let memory = get_memory!(env);
-    let key = read_buffer(memory, &ctx, key)?;
+    let key = map_key(&env, None, read_buffer(memory, &ctx, key)?)?;
     let value = read_buffer(memory, &ctx, value)?;
 
     // Do not remove this. It could be used for gas_calibration in future.
@@ -581,6 +1206,136 @@ pub(crate) fn assembly_script_set_data(
     Ok(())
 }
 
+/// sets a key-indexed data entry in the datastore only if the key is absent,
+/// returns Ok(1) if the value was written, otherwise Ok(0)
+#[named]
+pub(crate) fn assembly_script_set_data_if_absent(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    key: i32,
+    value: i32,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let key = map_key(&env, None, read_buffer(memory, &ctx, key)?)?;
+    let value = read_buffer(memory, &ctx, value)?;
+    let res = env.get_interface().set_data_if_absent(&key, &value)?;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(key), into_trace_value!(value)],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res as i32)
+}
+
+/// Swaps the values of two keys of the datastore of the current address. If
+/// only one of them exists, its value is moved to the other one.
+#[named]
+pub(crate) fn assembly_script_swap_data(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    key_a: i32,
+    key_b: i32,
+) -> ABIResult<()> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let key_a = map_key(&env, None, read_buffer(memory, &ctx, key_a)?)?;
+    let key_b = map_key(&env, None, read_buffer(memory, &ctx, key_b)?)?;
+    env.get_interface().swap_data(&key_a, &key_b)?;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(key_a), into_trace_value!(key_b)],
+        return_value: AbiTraceType::None,
+        sub_calls: None,
+    });
+    Ok(())
+}
+
+/// Add a signed `delta` to a counter stored in the datastore of the current
+/// address and return the new value.
+///
+/// The counter is stored as an unsigned 64 bits integer encoded in little
+/// endian on exactly 8 bytes, a missing key being read as 0. Its bounds are
+/// thus [0, 2^64 - 1], and the returned i64 must be reinterpreted as an u64.
+///
+/// `mode` selects the behavior when the result is out of these bounds:
+/// * 0: checked, the call fails and the counter is left unchanged
+/// * 1: saturating, the result is clamped to the bounds
+/// * 2: wrapping, the result wraps around modulo 2^64
+#[named]
+pub(crate) fn assembly_script_add_to_data(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    key: i32,
+    delta: i64,
+    mode: i32,
+) -> ABIResult<i64> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let key = map_key(&env, None, read_buffer(memory, &ctx, key)?)?;
+    let overflow = match mode {
+        0 => CounterOverflow::Checked,
+        1 => CounterOverflow::Saturating,
+        2 => CounterOverflow::Wrapping,
+        _ => abi_bail!("Unknown overflow mode"),
+    };
+    let res = env.get_interface().add_to_data(&key, delta, overflow)?;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![
+            into_trace_value!(key),
+            into_trace_value!(delta),
+            into_trace_value!(mode),
+        ],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res as i64)
+}
+
+/// Rate limiting: count an operation against the counter stored at `key` in
+/// the datastore of the current address, allowing at most `max_per_period`
+/// operations per window of `period` periods. Returns 1 if the operation is
+/// allowed (and counted), 0 otherwise.
+///
+/// See `Interface::rate_limit` for the windows and the storage format.
+#[named]
+pub(crate) fn assembly_script_rate_limit(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    key: i32,
+    max_per_period: i64,
+    period: i64,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let key = map_key(&env, None, read_buffer(memory, &ctx, key)?)?;
+    let (Ok(max_per_period), Ok(period @ 1..)) =
+        (u64::try_from(max_per_period), u64::try_from(period))
+    else {
+        abi_bail!("rate_limit expects a non-negative maximum and a positive period");
+    };
+    let res = env
+        .get_interface()
+        .rate_limit(&key, max_per_period, period)?;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![
+            into_trace_value!(key),
+            into_trace_value!(max_per_period),
+            into_trace_value!(period),
+        ],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res as i32)
+}
+
 /// appends data to a key-indexed data entry in the datastore, fails if the
 /// entry does not exist
 #[named]
@@ -592,7 +1347,7 @@ pub(crate) fn assembly_script_append_data(
     let env = get_env(&ctx)?;
     sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
     let memory = get_memory!(env);
-    let key = read_buffer(memory, &ctx, key)?;
+    let key = map_key(&env, None, read_buffer(memory, &ctx, key)?)?;
     let value = read_buffer(memory, &ctx, value)?;
     // Do not remove this. It could be used for gas_calibration in future.
     // if cfg!(feature = "gas_calibration") {
@@ -612,19 +1367,79 @@ pub(crate) fn assembly_script_append_data(
     Ok(())
 }
 
+/// Take a savepoint of the state written so far, returning its id, or -1 if
+/// the interface does not support savepoints.
+///
+/// Savepoints are scoped to the current execution: a sub-call cannot roll
+/// back to a savepoint of its caller, and the savepoints of a sub-call are
+/// discarded when it returns.
+#[named]
+pub(crate) fn assembly_script_savepoint(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i64> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let res = match env.get_interface().savepoint()? {
+        Some(id) => {
+            ctx.data_mut().savepoints.push(id);
+            id as i64
+        }
+        None => -1,
+    };
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
+/// Undo every state write made since the given savepoint, including the
+/// writes of the sub-calls made in between, and release the savepoints taken
+/// after it. The savepoint itself stays valid.
+///
+/// Rolling back to -1 (savepoints unsupported) is a no-op.
+#[named]
+pub(crate) fn assembly_script_rollback_to(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    id: i64,
+) -> ABIResult<()> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    if id != -1 {
+        let Ok(id) = u64::try_from(id) else {
+            abi_bail!("Invalid savepoint id");
+        };
+        let Some(pos) = env.savepoints.iter().position(|savepoint| *savepoint == id) else {
+            abi_bail!("Unknown savepoint in the current execution");
+        };
+        env.get_interface().rollback_to(id)?;
+        ctx.data_mut().savepoints.truncate(pos + 1);
+    }
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(id)],
+        return_value: AbiTraceType::None,
+        sub_calls: None,
+    });
+    Ok(())
+}
+
 /// gets a key-indexed data entry in the datastore, failing if non-existent
 #[named]
 pub(crate) fn assembly_script_get_data(mut ctx: FunctionEnvMut<ASEnv>, key: i32) -> ABIResult<i32> {
     let env = get_env(&ctx)?;
     sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
     let memory = get_memory!(env);
-    let key = read_buffer(memory, &ctx, key)?;
+    let key = map_key(&env, None, read_buffer(memory, &ctx, key)?)?;
     // Do not remove this. It could be used for gas_calibration in future.
     // if cfg!(feature = "gas_calibration") {
     //     let fname = format!("massa.{}:0", function_name!());
     //     param_size_update(&env, &mut ctx, &fname, key.len(), true);
     // }
     let data = env.get_interface().raw_get_data(&key)?;
+    ctx.data_mut().consume_read_budget(data.len())?;
     let ptr = pointer_from_bytearray(&env, &mut ctx, &data)?.offset() as i32;
     #[cfg(feature = "execution-trace")]
     ctx.data_mut().trace.push(AbiTrace {
@@ -636,13 +1451,113 @@ pub(crate) fn assembly_script_get_data(mut ctx: FunctionEnvMut<ASEnv>, key: i32)
     Ok(ptr)
 }
 
+/// Get the datastore values of several keys of the current address, in the
+/// order of the keys.
+///
+/// The keys are serialized as done by `ser_bytearray_vec`, the values as done
+/// by `ser_optional_values`. An additional cost is charged for each key and
+/// for each returned byte.
+#[named]
+pub(crate) fn assembly_script_get_data_multi(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    keys: i32,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let keys = deser_bytearray_vec(&read_buffer(memory, &ctx, keys)?)?;
+    if keys.len() > settings::max_datastore_entry_count() {
+        abi_bail!("Too many keys");
+    }
+    let abi_costs = env.get_gas_costs().abi_costs;
+    let key_cost = *abi_costs
+        .get("assembly_script_get_data_multi_key")
+        .ok_or_else(|| {
+            wasmer::RuntimeError::new("Failed to get gas for assembly_script_get_data_multi_key")
+        })?;
+    let byte_cost = *abi_costs
+        .get("assembly_script_get_data_multi_byte")
+        .ok_or_else(|| {
+            wasmer::RuntimeError::new("Failed to get gas for assembly_script_get_data_multi_byte")
+        })?;
+    sub_remaining_gas(&env, &mut ctx, key_cost.saturating_mul(keys.len() as u64))?;
+    let keys = keys
+        .into_iter()
+        .map(|key| map_key(&env, None, key))
+        .collect::<ABIResult<Vec<_>>>()?;
+    let values = env.get_interface().get_data_multi(&keys)?;
+    if values.len() != keys.len() {
+        abi_bail!("Interface returned a wrong number of values");
+    }
+    let data = ser_optional_values(&values)?;
+    sub_remaining_gas(&env, &mut ctx, byte_cost.saturating_mul(data.len() as u64))?;
+    ctx.data_mut().consume_read_budget(data.len())?;
+    let ptr = pointer_from_bytearray(&env, &mut ctx, &data)?.offset() as i32;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(keys)],
+        return_value: data.into(),
+        sub_calls: None,
+    });
+    Ok(ptr)
+}
+
+/// Get the value a datastore key of the current address had at the given past
+/// finalized slot.
+///
+/// Returns an empty buffer if the key was missing at that slot, otherwise the
+/// byte 1 followed by the value. Fails if the slot is not strictly before the
+/// current one; the interface also rejects slots that are not finalized.
+#[named]
+pub(crate) fn assembly_script_get_data_at_slot(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    key: i32,
+    period: i64,
+    thread: i32,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let Ok(period) = u64::try_from(period) else {
+        abi_bail!("Negative period");
+    };
+    let Ok(thread) = u8::try_from(thread) else {
+        abi_bail!("Invalid thread");
+    };
+    let memory = get_memory!(env);
+    let key = map_key(&env, None, read_buffer(memory, &ctx, key)?)?;
+    let interface = env.get_interface();
+    let current_slot = interface.get_current_slot()?;
+    if (period, thread as u32) >= (current_slot.period, current_slot.thread) {
+        abi_bail!("Slot is not in the past");
+    }
+    let data = match interface.get_data_at_slot(&key, (period, thread))? {
+        Some(value) => [&[1u8][..], &value].concat(),
+        None => Vec::new(),
+    };
+    ctx.data_mut().consume_read_budget(data.len())?;
+    let ptr = pointer_from_bytearray(&env, &mut ctx, &data)?.offset() as i32;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![
+            into_trace_value!(key),
+            into_trace_value!(period),
+            into_trace_value!(thread),
+        ],
+        return_value: data.clone().into(),
+        sub_calls: None,
+    });
+    Ok(ptr)
+}
+
 /// checks if a key-indexed data entry exists in the datastore
 #[named]
 pub(crate) fn assembly_script_has_data(mut ctx: FunctionEnvMut<ASEnv>, key: i32) -> ABIResult<i32> {
     let env = get_env(&ctx)?;
     sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
     let memory = get_memory!(env);
-    let key = read_buffer(memory, &ctx, key)?;
+    let key = map_key(&env, None, read_buffer(memory, &ctx, key)?)?;
     // Do not remove this. It could be used for gas_calibration in future.
     // if cfg!(feature = "gas_calibration") {
     //     let fname = format!("massa.{}:0", function_name!());
@@ -669,7 +1584,7 @@ pub(crate) fn assembly_script_delete_data(
     let env = get_env(&ctx)?;
     sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
     let memory = get_memory!(env);
-    let key = read_buffer(memory, &ctx, key)?;
+    let key = map_key(&env, None, read_buffer(memory, &ctx, key)?)?;
     // Do not remove this. It could be used for gas_calibration in future.
     // if cfg!(feature = "gas_calibration") {
     //     let fname = format!("massa.{}:0", function_name!());
@@ -701,6 +1616,7 @@ pub(crate) fn assembly_script_set_data_for(
     let key = read_buffer(memory, &ctx, key)?;
     let value = read_buffer(memory, &ctx, value)?;
     let address = read_string(memory, &ctx, address)?;
+    let key = map_key(&env, Some(&address), key)?;
     // Do not remove this. It could be used for gas_calibration in future.
     // if cfg!(feature = "gas_calibration") {
     //     let fname = format!("massa.{}:0", function_name!());
@@ -741,6 +1657,7 @@ pub(crate) fn assembly_script_append_data_for(
     let key = read_buffer(memory, &ctx, key)?;
     let value = read_buffer(memory, &ctx, value)?;
     let address = read_string(memory, &ctx, address)?;
+    let key = map_key(&env, Some(&address), key)?;
     // Do not remove this. It could be used for gas_calibration in future.
     // if cfg!(feature = "gas_calibration") {
     //     let fname = format!("massa.{}:0", function_name!());
@@ -779,6 +1696,7 @@ pub(crate) fn assembly_script_get_data_for(
     let memory = get_memory!(env);
     let address = read_string(memory, &ctx, address)?;
     let key = read_buffer(memory, &ctx, key)?;
+    let key = map_key(&env, Some(&address), key)?;
     // Do not remove this. It could be used for gas_calibration in future.
     // if cfg!(feature = "gas_calibration") {
     //     let fname = format!("massa.{}:0", function_name!());
@@ -788,6 +1706,7 @@ pub(crate) fn assembly_script_get_data_for(
     // }
 
     let data = env.get_interface().raw_get_data_for(&address, &key)?;
+    ctx.data_mut().consume_read_budget(data.len())?;
     let ptr = pointer_from_bytearray(&env, &mut ctx, &data)?.offset() as i32;
     #[cfg(feature = "execution-trace")]
     ctx.data_mut().trace.push(AbiTrace {
@@ -812,6 +1731,7 @@ pub(crate) fn assembly_script_delete_data_for(
     let memory = get_memory!(env);
     let address = read_string(memory, &ctx, address)?;
     let key = read_buffer(memory, &ctx, key)?;
+    let key = map_key(&env, Some(&address), key)?;
     // Do not remove this. It could be used for gas_calibration in future.
     // if cfg!(feature = "gas_calibration") {
     //     let fname = format!("massa.{}:0", function_name!());
@@ -841,6 +1761,7 @@ pub(crate) fn assembly_script_has_data_for(
     let memory = get_memory!(env);
     let address = read_string(memory, &ctx, address)?;
     let key = read_buffer(memory, &ctx, key)?;
+    let key = map_key(&env, Some(&address), key)?;
     // Do not remove this. It could be used for gas_calibration in future.
     // if cfg!(feature = "gas_calibration") {
     //     let fname = format!("massa.{}:0", function_name!());
@@ -911,7 +1832,9 @@ pub(crate) fn assembly_script_generate_event(
     //     let fname = format!("massa.{}:0", function_name!());
     //     param_size_update(&env, &mut ctx, &fname, event.len(), true);
     // }
+    ctx.data_mut().consume_event_budget(event.len())?;
     env.get_interface().generate_event(event.clone())?;
+    ctx.data_mut().events.push(event.clone());
     #[cfg(feature = "execution-trace")]
     ctx.data_mut().trace.push(AbiTrace {
         name: function_name!().to_string(),
@@ -922,6 +1845,35 @@ pub(crate) fn assembly_script_generate_event(
     Ok(())
 }
 
+/// Generate an event tagged as an error with the given `code`, see
+/// `Interface::generate_error_event`.
+///
+/// The execution goes on: an error event is a recoverable warning meant to be
+/// filtered by indexers, unlike `abort` which ends the execution and reverts
+/// its effects. Counts towards the events size limit like a regular event.
+#[named]
+pub(crate) fn assembly_script_generate_error_event(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    code: i32,
+    message: i32,
+) -> ABIResult<()> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let message = read_string(memory, &ctx, message)?;
+    ctx.data_mut().consume_event_budget(message.len())?;
+    env.get_interface()
+        .generate_error_event(code as u32, message.clone())?;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(code), into_trace_value!(message)],
+        return_value: AbiTraceType::None,
+        sub_calls: None,
+    });
+    Ok(())
+}
+
 /// verify a signature of data given a public key. Returns Ok(1) if correctly
 /// verified, otherwise Ok(0)
 #[named]
@@ -948,14 +1900,130 @@ pub(crate) fn assembly_script_signature_verify(
     // }
     let res = env
         .get_interface()
-        .signature_verify(data.as_bytes(), &signature, &public_key)?;
+        .signature_verify(data.as_bytes(), &signature, &public_key)?;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![
+            into_trace_value!(data.as_bytes().to_vec()),
+            into_trace_value!(signature),
+            into_trace_value!(public_key),
+        ],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res as i32)
+}
+
+/// Verify a threshold (m-of-n) signature of data.
+///
+/// `signatures` and `public_keys` are buffers of the same length encoded as
+/// done by `ser_bytearray_vec`, each entry being the UTF-8 bytes of a
+/// base58check encoded signature or public key. The pair at index `i` is
+/// (`signatures[i]`, `public_keys[i]`).
+///
+/// Every pair is charged as an `assembly_script_signature_verify` call before
+/// the verification. This is an upper bound: the interface may stop once the
+/// threshold is reached, but the cost does not depend on how many pairs it
+/// actually verifies.
+///
+/// Returns Ok(1) if at least `threshold` pairs are valid, otherwise Ok(0).
+#[named]
+pub(crate) fn assembly_script_verify_threshold(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    data: i32,
+    signatures: i32,
+    public_keys: i32,
+    threshold: i32,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    if threshold.is_negative() {
+        abi_bail!("Negative threshold.");
+    }
+    let memory = get_memory!(env);
+    let data = read_buffer(memory, &ctx, data)?;
+    let signatures = deser_string_vec(&read_buffer(memory, &ctx, signatures)?)?;
+    let public_keys = deser_string_vec(&read_buffer(memory, &ctx, public_keys)?)?;
+    if signatures.len() != public_keys.len() {
+        abi_bail!("Signature and public key counts differ.");
+    }
+    // Every pair is charged upfront, whether or not the interface verifies it
+    let signature_cost = *env
+        .get_gas_costs()
+        .abi_costs
+        .get("assembly_script_signature_verify")
+        .ok_or_else(|| {
+            wasmer::RuntimeError::new("Failed to get gas for assembly_script_signature_verify ABI")
+        })?;
+    sub_remaining_gas(
+        &env,
+        &mut ctx,
+        signature_cost.saturating_mul(signatures.len() as u64),
+    )?;
+    let res =
+        env.get_interface()
+            .verify_threshold(&data, &signatures, &public_keys, threshold as u32)?;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![
+            into_trace_value!(data),
+            into_trace_value!(signatures),
+            into_trace_value!(public_keys),
+            into_trace_value!(threshold),
+        ],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res as i32)
+}
+
+/// Verify that the datastore entry (`address`, `key`, `value`) is included in
+/// the latest finalized state root, given a Merkle `proof`.
+///
+/// The proof is passed as is to the interface and must be serialized in the
+/// format of the node state trie. An additional cost is charged for each byte
+/// of the proof.
+///
+/// Returns Ok(1) if the inclusion is proven, otherwise Ok(0).
+#[named]
+pub(crate) fn assembly_script_verify_state_inclusion(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    address: i32,
+    key: i32,
+    value: i32,
+    proof: i32,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let address = read_string(memory, &ctx, address)?;
+    let key = read_buffer(memory, &ctx, key)?;
+    let key = map_key(&env, Some(&address), key)?;
+    let value = read_buffer(memory, &ctx, value)?;
+    let proof = read_buffer(memory, &ctx, proof)?;
+    let byte_cost = *env
+        .get_gas_costs()
+        .abi_costs
+        .get("assembly_script_verify_state_inclusion_byte")
+        .ok_or_else(|| {
+            wasmer::RuntimeError::new(
+                "Failed to get gas for assembly_script_verify_state_inclusion_byte",
+            )
+        })?;
+    sub_remaining_gas(&env, &mut ctx, byte_cost.saturating_mul(proof.len() as u64))?;
+    let res = env
+        .get_interface()
+        .verify_state_inclusion(&address, &key, &value, &proof)?;
     #[cfg(feature = "execution-trace")]
     ctx.data_mut().trace.push(AbiTrace {
         name: function_name!().to_string(),
         params: vec![
-            into_trace_value!(data.as_bytes().to_vec()),
-            into_trace_value!(signature),
-            into_trace_value!(public_key),
+            into_trace_value!(address),
+            into_trace_value!(key),
+            into_trace_value!(value),
+            into_trace_value!(proof),
         ],
         return_value: res.into(),
         sub_calls: None,
@@ -1093,6 +2161,58 @@ pub(crate) fn assembly_script_address_from_public_key(
     Ok(ptr)
 }
 
+/// Check whether the given address is derived from the given public key,
+/// using the same derivation as `assembly_script_address_from_public_key`.
+///
+/// Returns Ok(1) if they match, otherwise Ok(0).
+#[named]
+pub(crate) fn assembly_script_address_matches_pubkey(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    address: i32,
+    public_key: i32,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let address = read_string(memory, &ctx, address)?;
+    let public_key = read_string(memory, &ctx, public_key)?;
+    let res = env.get_interface().address_from_public_key(&public_key)? == address;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(address), into_trace_value!(public_key)],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res as i32)
+}
+
+/// Resolve a name of the node name registry to the address it is registered
+/// to, or an empty string if the name is not registered.
+///
+/// The resolution reflects the committed registry state, so it is
+/// deterministic within an execution.
+#[named]
+pub(crate) fn assembly_script_resolve_name(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    name: i32,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let name = read_string(memory, &ctx, name)?;
+    let address = env.get_interface().resolve_name(&name)?.unwrap_or_default();
+    let ptr = pointer_from_string(&env, &mut ctx, &address)?.offset() as i32;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(name)],
+        return_value: address.into(),
+        sub_calls: None,
+    });
+    Ok(ptr)
+}
+
 /// Validates an address is correct
 #[named]
 pub(crate) fn assembly_script_validate_address(
@@ -1130,6 +2250,29 @@ pub(crate) fn assembly_script_unsafe_random(mut ctx: FunctionEnvMut<ASEnv>) -> A
     Ok(res)
 }
 
+/// generates a pseudo-random number from a seed and the current slot, safe
+/// for consensus as all the nodes get the same value, see
+/// `Interface::random_from_seed`
+#[named]
+pub(crate) fn assembly_script_random_from_seed(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    seed: i32,
+) -> ABIResult<i64> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let seed = read_buffer(memory, &ctx, seed)?;
+    let res = env.get_interface().random_from_seed(&seed)?;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(seed)],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
 /// gets the current unix timestamp in milliseconds
 #[named]
 pub(crate) fn assembly_script_get_time(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i64> {
@@ -1248,7 +2391,174 @@ pub(crate) fn assembly_script_send_message(
     Ok(())
 }
 
-/// converts a public key to an address
+/// Get the slots at which the current contract has pending scheduled
+/// executions (e.g. messages it sent to itself).
+///
+/// The slots are sorted and serialized as: L (32 bits LE) followed by L
+/// entries of period (64 bits LE) and thread (8 bits).
+/// An additional cost is charged for each returned slot.
+#[named]
+pub(crate) fn assembly_script_get_my_scheduled_slots(
+    mut ctx: FunctionEnvMut<ASEnv>,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let interface = env.get_interface();
+    let Some(address) = interface.get_call_stack()?.pop() else {
+        abi_bail!("empty call stack");
+    };
+    let mut slots = interface.get_scheduled_slots(&address)?;
+    let entry_cost = *env
+        .get_gas_costs()
+        .abi_costs
+        .get("assembly_script_get_my_scheduled_slots_entry")
+        .ok_or_else(|| {
+            wasmer::RuntimeError::new(
+                "Failed to get gas for assembly_script_get_my_scheduled_slots_entry",
+            )
+        })?;
+    sub_remaining_gas(
+        &env,
+        &mut ctx,
+        entry_cost.saturating_mul(slots.len() as u64),
+    )?;
+    slots.sort_unstable();
+    let Ok(entry_count) = u32::try_from(slots.len()) else {
+        abi_bail!("Too many scheduled slots");
+    };
+    let mut buffer = Vec::with_capacity(4 + slots.len() * 9);
+    buffer.extend_from_slice(&entry_count.to_le_bytes());
+    for (period, thread) in slots.iter() {
+        buffer.extend_from_slice(&period.to_le_bytes());
+        buffer.push(*thread);
+    }
+    let ptr = pointer_from_bytearray(&env, &mut ctx, &buffer)?.offset();
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: buffer.into(),
+        sub_calls: None,
+    });
+    Ok(ptr as i32)
+}
+
+/// Get the sum of the coins attached to the pending scheduled messages sent by
+/// the current contract, i.e. coins committed but not yet spent.
+#[named]
+pub(crate) fn assembly_script_get_my_deferred_coins(
+    mut ctx: FunctionEnvMut<ASEnv>,
+) -> ABIResult<i64> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    check_not_pure(&env, function_name!())?;
+    let interface = env.get_interface();
+    let Some(address) = interface.get_call_stack()?.pop() else {
+        abi_bail!("empty call stack");
+    };
+    let res = interface.get_deferred_coins(&address)? as i64;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
+/// Get the minimum balance an account must keep to not be reaped, see
+/// `Interface::get_min_balance`
+#[named]
+pub(crate) fn assembly_script_get_min_balance(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i64> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let res = env.get_interface().get_min_balance()? as i64;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
+/// Get an estimate of the coins refunded for the unused gas: the gas
+/// remaining after paying for this call times `Interface::get_gas_price`.
+///
+/// This is an instantaneous estimate, it decreases as the execution goes on.
+#[named]
+pub(crate) fn assembly_script_get_estimated_refund(
+    mut ctx: FunctionEnvMut<ASEnv>,
+) -> ABIResult<i64> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let remaining_gas = get_remaining_points(&env, &mut ctx)?;
+    let gas_price = env.get_interface().get_gas_price()?;
+    let res = remaining_gas.saturating_mul(gas_price).min(i64::MAX as u64) as i64;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
+/// Get the interface version declared by the contract at `address`, see
+/// `Interface::get_interface_version`. Returns -1 if it declares none.
+#[named]
+pub(crate) fn assembly_script_get_interface_version(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    address: i32,
+) -> ABIResult<i64> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let memory = get_memory!(env);
+    let address = read_string(memory, &ctx, address)?;
+    let res = match env.get_interface().get_interface_version(&address)? {
+        Some(version) => version as i64,
+        None => -1,
+    };
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(address)],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
+/// Get the number of bytes the current contract occupies in the datastore.
+///
+/// See `Interface::get_storage_bytes` for what is accounted.
+#[named]
+pub(crate) fn assembly_script_get_my_storage_bytes(
+    mut ctx: FunctionEnvMut<ASEnv>,
+) -> ABIResult<i64> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let interface = env.get_interface();
+    let Some(address) = interface.get_call_stack()?.pop() else {
+        abi_bail!("empty call stack");
+    };
+    let res = interface.get_storage_bytes(&address)? as i64;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
+/// gets the id of the operation that originated the current execution, as a
+/// string, empty if the execution was not triggered by an operation (e.g.
+/// an asynchronous message)
 #[named]
 pub(crate) fn assembly_script_get_origin_operation_id(
     mut ctx: FunctionEnvMut<ASEnv>,
@@ -1270,12 +2580,52 @@ pub(crate) fn assembly_script_get_origin_operation_id(
     Ok(ptr)
 }
 
+/// gets the address of the producer of the block being executed, see
+/// `Interface::get_block_producer`
+#[named]
+pub(crate) fn assembly_script_get_block_producer(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let producer = env.get_interface().get_block_producer()?;
+    let ptr = pointer_from_string(&env, &mut ctx, &producer)?.offset() as i32;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: producer.into(),
+        sub_calls: None,
+    });
+    Ok(ptr)
+}
+
+/// gets the current execution slot, packed as its period (u64 LE) followed by
+/// its thread (u8)
+#[named]
+pub(crate) fn assembly_script_get_current_slot(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let current_slot = env.get_interface().get_current_slot()?;
+    let Ok(thread) = u8::try_from(current_slot.thread) else {
+        abi_bail!("Invalid thread");
+    };
+    let data = [&current_slot.period.to_le_bytes()[..], &[thread]].concat();
+    let ptr = pointer_from_bytearray(&env, &mut ctx, &data)?.offset() as i32;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: data.into(),
+        sub_calls: None,
+    });
+    Ok(ptr)
+}
+
 /// gets the period of the current execution slot
 #[named]
 pub(crate) fn assembly_script_get_current_period(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i64> {
     let env = get_env(&ctx)?;
     sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
-    let current_period = env.get_interface().get_current_period()?;
+    let current_period = env.get_interface().get_current_slot()?.period;
     #[cfg(feature = "execution-trace")]
     ctx.data_mut().trace.push(AbiTrace {
         name: function_name!().to_string(),
@@ -1291,7 +2641,7 @@ pub(crate) fn assembly_script_get_current_period(mut ctx: FunctionEnvMut<ASEnv>)
 pub(crate) fn assembly_script_get_current_thread(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i32> {
     let env = get_env(&ctx)?;
     sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
-    let current_thread = env.get_interface().get_current_thread()?;
+    let current_thread = env.get_interface().get_current_slot()?.thread;
     #[cfg(feature = "execution-trace")]
     ctx.data_mut().trace.push(AbiTrace {
         name: function_name!().to_string(),
@@ -1302,6 +2652,34 @@ pub(crate) fn assembly_script_get_current_thread(mut ctx: FunctionEnvMut<ASEnv>)
     Ok(current_thread as i32)
 }
 
+/// compares the given slot to the current execution slot, returns -1 if it is
+/// in the past, 0 if it is the current slot and 1 if it is in the future
+#[named]
+pub(crate) fn assembly_script_compare_slot(
+    mut ctx: FunctionEnvMut<ASEnv>,
+    period: i64,
+    thread: i32,
+) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let Ok(period) = u64::try_from(period) else {
+        abi_bail!("Negative period");
+    };
+    let Ok(thread) = u8::try_from(thread) else {
+        abi_bail!("Invalid thread");
+    };
+    let current_slot = env.get_interface().get_current_slot()?;
+    let res = (period, thread as u32).cmp(&(current_slot.period, current_slot.thread)) as i32;
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![into_trace_value!(period), into_trace_value!(thread)],
+        return_value: res.into(),
+        sub_calls: None,
+    });
+    Ok(res)
+}
+
 /// sets the executable bytecode of an arbitrary address
 #[named]
 pub(crate) fn assembly_script_set_bytecode_for(
@@ -1321,6 +2699,10 @@ pub(crate) fn assembly_script_set_bytecode_for(
     //     let fname = format!("massa.{}:1", function_name!());
     //     param_size_update(&env, &mut ctx, &fname, bytecode_raw.len(), true);
     // }
+    let storage_cost = env
+        .get_gas_costs()
+        .bytecode_storage_cost(bytecode_raw.len());
+    sub_remaining_gas(&env, &mut ctx, storage_cost)?;
     env.get_interface()
         .raw_set_bytecode_for(&address, &bytecode_raw)?;
     #[cfg(feature = "execution-trace")]
@@ -1348,6 +2730,10 @@ pub(crate) fn assembly_script_set_bytecode(
     //     let fname = format!("massa.{}:0", function_name!());
     //     param_size_update(&env, &mut ctx, &fname, bytecode_raw.len(), true);
     // }
+    let storage_cost = env
+        .get_gas_costs()
+        .bytecode_storage_cost(bytecode_raw.len());
+    sub_remaining_gas(&env, &mut ctx, storage_cost)?;
     env.get_interface().raw_set_bytecode(&bytecode_raw)?;
     #[cfg(feature = "execution-trace")]
     ctx.data_mut().trace.push(AbiTrace {
@@ -1365,6 +2751,7 @@ pub(crate) fn assembly_script_get_bytecode(mut ctx: FunctionEnvMut<ASEnv>) -> AB
     let env = get_env(&ctx)?;
     sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
     let data = env.get_interface().raw_get_bytecode()?;
+    ctx.data_mut().consume_read_budget(data.len())?;
     let ptr = pointer_from_bytearray(&env, &mut ctx, &data)?.offset() as i32;
     #[cfg(feature = "execution-trace")]
     ctx.data_mut().trace.push(AbiTrace {
@@ -1387,6 +2774,7 @@ pub(crate) fn assembly_script_get_bytecode_for(
     let memory = get_memory!(env);
     let address = read_string(memory, &ctx, address)?;
     let data = env.get_interface().raw_get_bytecode_for(&address)?;
+    ctx.data_mut().consume_read_budget(data.len())?;
     let ptr = pointer_from_bytearray(&env, &mut ctx, &data)?.offset() as i32;
     #[cfg(feature = "execution-trace")]
     ctx.data_mut().trace.push(AbiTrace {
@@ -1399,6 +2787,8 @@ pub(crate) fn assembly_script_get_bytecode_for(
 }
 
 /// execute `function` of the given bytecode in the current context
+///
+/// See `local_call` for the storage and context rules.
 #[named]
 pub(crate) fn assembly_script_local_execution(
     mut ctx: FunctionEnvMut<ASEnv>,
@@ -1436,7 +2826,8 @@ pub(crate) fn assembly_script_local_execution(
 }
 
 /// execute `function` of the bytecode located at `address` in the current
-/// context
+/// context: the datastore written by the bytecode is the one of the caller,
+/// not the one of `address`. See `local_call`.
 #[named]
 pub(crate) fn assembly_script_local_call(
     mut ctx: FunctionEnvMut<ASEnv>,
@@ -1515,6 +2906,28 @@ pub fn assembly_script_function_exists(
     Ok(function_exists as i32)
 }
 
+/// Get the share of the gas fees that is burned, in permille.
+///
+/// The returned value is in [0, 1000]: `burned = fee * ratio / 1000`, the
+/// remaining `fee - burned` being redistributed to the block producers.
+#[named]
+pub(crate) fn assembly_script_get_fee_split(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<i32> {
+    let env = get_env(&ctx)?;
+    sub_remaining_gas_abi(&env, &mut ctx, function_name!())?;
+    let res = env.get_interface().get_fee_split()?;
+    if res > 1000 {
+        abi_bail!("Invalid fee split");
+    }
+    #[cfg(feature = "execution-trace")]
+    ctx.data_mut().trace.push(AbiTrace {
+        name: function_name!().to_string(),
+        params: vec![],
+        return_value: (res as u32).into(),
+        sub_calls: None,
+    });
+    Ok(res as i32)
+}
+
 /// Return current chain id
 #[named]
 pub(crate) fn assembly_script_chain_id(mut ctx: FunctionEnvMut<ASEnv>) -> ABIResult<u64> {
@@ -1533,7 +2946,8 @@ pub(crate) fn assembly_script_chain_id(mut ctx: FunctionEnvMut<ASEnv>) -> ABIRes
 
 /// Assembly script builtin `abort` function.
 ///
-/// It prints the origin filename, an error messag, the line and column.
+/// It ends the execution with the message, the origin filename, the line and
+/// the column, also attached to the returned `VMError::ExecutionError`.
 #[allow(unused_macros)]
 #[allow(unused_mut)]
 #[named]
@@ -1573,13 +2987,15 @@ pub fn assembly_script_abort(
         return_value: AbiTraceType::None,
         sub_calls: None,
     });
-    abi_bail!(format!(
-        "error: {} at {}:{} col: {}",
-        message_.unwrap(),
-        filename_.unwrap(),
+    let abort_info = AbortInfo {
+        message: message_.unwrap(),
+        filename: filename_.unwrap(),
         line,
-        col
-    ));
+        column: col,
+    };
+    let error = abort_info.to_string();
+    ctx.data_mut().abort_info = Some(abort_info);
+    abi_bail!(error);
 }
 
 /// Assembly script builtin `seed` function
@@ -1821,6 +3237,12 @@ fn alloc_string_array(ctx: &mut FunctionEnvMut<ASEnv>, vec: &[String]) -> ABIRes
     Ok(StringPtr::alloc(&addresses, env.get_ffi_env(), ctx)?.offset() as i32)
 }
 
+/// Apply `Interface::map_key` to a key of the datastore of the given address,
+/// the current one if `None`
+fn map_key(env: &ASEnv, address: Option<&str>, key: Vec<u8>) -> ABIResult<Vec<u8>> {
+    Ok(env.get_interface().map_key(address, &key)?)
+}
+
 /// Flatten a Vec<Vec<u8>> (or anything that can be turned into an iterator) to
 /// a Vec<u8> with the format: L (32 bits LE) V1_L (8 bits) V1 (8bits * V1_L),
 /// V2_L ... VN (8 bits * VN_L)
@@ -1854,6 +3276,70 @@ where
     Ok(buffer)
 }
 
+/// Serialize a list of optional values with the format: L (32 bits LE), then
+/// for each value a presence flag (8 bits, 0 or 1) followed, if present, by
+/// V_L (32 bits LE) and V (8 bits * V_L)
+fn ser_optional_values(values: &[Option<Vec<u8>>]) -> ABIResult<Vec<u8>> {
+    let Ok(count) = u32::try_from(values.len()) else {
+        abi_bail!("Too many values");
+    };
+    let mut buffer = count.to_le_bytes().to_vec();
+    for value in values {
+        match value {
+            Some(value) => {
+                let Ok(len) = u32::try_from(value.len()) else {
+                    abi_bail!("Value is too long");
+                };
+                buffer.push(1);
+                buffer.extend_from_slice(&len.to_le_bytes());
+                buffer.extend_from_slice(value);
+            }
+            None => buffer.push(0),
+        }
+    }
+    Ok(buffer)
+}
+
+/// Read back a Vec<Vec<u8>> serialized with `ser_bytearray_vec`
+fn deser_bytearray_vec(buffer: &[u8]) -> ABIResult<Vec<Vec<u8>>> {
+    if buffer.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    if buffer.len() < 4 {
+        abi_bail!("Invalid bytearray list: missing entry count");
+    }
+    let (entry_count, mut cursor) = buffer.split_at(4);
+    // Safe to unwrap as we checked the buffer length
+    let entry_count = u32::from_le_bytes(entry_count.try_into().unwrap()) as usize;
+
+    let mut data = Vec::with_capacity(entry_count.min(cursor.len()));
+    for _ in 0..entry_count {
+        let Some((&len, rest)) = cursor.split_first() else {
+            abi_bail!("Invalid bytearray list: missing entry length");
+        };
+        if rest.len() < len as usize {
+            abi_bail!("Invalid bytearray list: entry is too short");
+        }
+        let (entry, rest) = rest.split_at(len as usize);
+        data.push(entry.to_vec());
+        cursor = rest;
+    }
+    if !cursor.is_empty() {
+        abi_bail!("Invalid bytearray list: trailing bytes");
+    }
+
+    Ok(data)
+}
+
+/// Read back a list of UTF-8 strings serialized with `ser_bytearray_vec`
+fn deser_string_vec(buffer: &[u8]) -> ABIResult<Vec<String>> {
+    deser_bytearray_vec(buffer)?
+        .into_iter()
+        .map(|entry| String::from_utf8(entry).map_err(|err| super::ABIError::Error(err.into())))
+        .collect()
+}
+
 /// performs a sha256 hash on byte array and returns the hash as byte array
 #[named]
 pub(crate) fn assembly_script_hash_sha256(
@@ -1871,7 +3357,7 @@ pub(crate) fn assembly_script_hash_sha256(
 
 #[cfg(test)]
 mod tests {
-    use crate::as_execution::abi::ser_bytearray_vec;
+    use crate::as_execution::abi::{deser_bytearray_vec, ser_bytearray_vec, ser_optional_values};
 
     #[test]
     fn test_ser() {
@@ -1879,6 +3365,25 @@ mod tests {
 
         let vb_ser = ser_bytearray_vec(&vb, vb.len(), 10).unwrap();
         assert_eq!(vb_ser, [2, 0, 0, 0, 3, 1, 2, 3, 1, 255]);
+        assert_eq!(deser_bytearray_vec(&vb_ser).unwrap(), vb);
+    }
+
+    #[test]
+    fn test_ser_optional_values() {
+        assert_eq!(
+            ser_optional_values(&[Some(vec![7, 8]), None, Some(vec![])]).unwrap(),
+            [3, 0, 0, 0, 1, 2, 0, 0, 0, 7, 8, 0, 1, 0, 0, 0, 0]
+        );
+        assert_eq!(ser_optional_values(&[]).unwrap(), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_deser_invalid() {
+        assert!(deser_bytearray_vec(&[1, 0, 0]).is_err());
+        assert!(deser_bytearray_vec(&[1, 0, 0, 0]).is_err());
+        assert!(deser_bytearray_vec(&[1, 0, 0, 0, 2, 1]).is_err());
+        assert!(deser_bytearray_vec(&[1, 0, 0, 0, 1, 1, 0]).is_err());
+        assert!(deser_bytearray_vec(&[]).unwrap().is_empty());
     }
 
     #[test]
@@ -8,6 +8,7 @@ use wasmer::FunctionEnvMut;
 use super::abi::get_env;
 use super::env::{get_remaining_points, set_remaining_points, ASEnv, Metered};
 use super::error::{abi_bail, ABIResult};
+use crate::execution::CallContext;
 use crate::Response;
 
 /// Calls an exported function in a WASM module at a given address
@@ -23,6 +24,8 @@ pub(crate) fn call_module(
         Err(_) => abi_bail!("negative amount of coins in Call"),
     };
     let env = get_env(ctx)?;
+    // checked before `init_call` so that the interface call stack is untouched
+    let call_context = subcall_context(&env)?;
     let bytecode = env.get_interface().init_call(address, raw_coins)?;
     let interface = env.get_interface();
     let remaining_gas = get_remaining_gas(&env, ctx)?;
@@ -38,22 +41,32 @@ pub(crate) fn call_module(
             )))
         })?;
 
-    let resp = crate::execution::run_function(
+    let resp = crate::execution::run_subcall(
         &*interface,
         module,
         function,
         param,
         remaining_gas,
         env.get_gas_costs(),
+        env.options.clone(),
+        call_context,
     )?;
     if cfg!(not(feature = "gas_calibration")) {
         set_remaining_points(&env, ctx, resp.remaining_gas)?;
     }
+    check_subcall_return_size(&env, &resp)?;
     env.get_interface().finish_call()?;
     Ok(resp)
 }
 
 /// Alternative to `call_module` to execute bytecode in a local context
+///
+/// Unlike `call_module`, no call is pushed on the interface call stack
+/// (`init_call`/`finish_call` are not called) and no coins are transferred:
+/// the executed bytecode runs as the current address. Every ABI it uses
+/// therefore reads and writes the datastore, balance and events of the caller,
+/// never those of the address the bytecode was loaded from. This is the
+/// delegatecall pattern used by library contracts.
 pub(crate) fn local_call(
     ctx: &mut FunctionEnvMut<ASEnv>,
     bytecode: &[u8],
@@ -62,7 +75,7 @@ pub(crate) fn local_call(
     tmp: bool,
 ) -> ABIResult<Response> {
     let env = get_env(ctx)?;
-    let gas_costs = env.get_gas_costs();
+    let call_context = subcall_context(&env)?;
     let interface = env.get_interface();
     let remaining_gas = get_remaining_gas(&env, ctx)?;
 
@@ -72,20 +85,44 @@ pub(crate) fn local_call(
         interface.get_module(bytecode, remaining_gas)?
     };
 
-    let resp = crate::execution::run_function(
+    let resp = crate::execution::run_subcall(
         &*interface,
         module,
         function,
         param,
         remaining_gas,
-        gas_costs,
+        env.get_gas_costs(),
+        env.options.clone(),
+        call_context,
     )?;
     if cfg!(not(feature = "gas_calibration")) {
         set_remaining_points(&env, ctx, resp.remaining_gas)?;
     }
+    check_subcall_return_size(&env, &resp)?;
     Ok(resp)
 }
 
+/// Context of a sub-call, sharing the logical clock and the execution time
+/// limit of the caller
+///
+/// Abort if the sub-call would exceed `GasCosts::max_call_depth`.
+fn subcall_context(env: &ASEnv) -> ABIResult<CallContext> {
+    let call_context = env.subcall_context();
+    if call_context.call_depth > env.get_gas_costs().max_call_depth {
+        abi_bail!("maximum call depth exceeded");
+    }
+    Ok(call_context)
+}
+
+/// Abort if a sub-call returned more than `GasCosts::max_subcall_return_size`
+/// bytes, before its return value gets allocated in the caller memory
+fn check_subcall_return_size(env: &ASEnv, resp: &Response) -> ABIResult<()> {
+    if resp.ret.len() as u64 > env.get_gas_costs().max_subcall_return_size {
+        abi_bail!("sub-call return too large");
+    }
+    Ok(())
+}
+
 /// Create a smart contract with the given `bytecode`
 pub(crate) fn create_sc(ctx: &mut FunctionEnvMut<ASEnv>, bytecode: &[u8]) -> ABIResult<String> {
     let env = ctx.data();
@@ -1,18 +1,33 @@
 use super::abi::*;
 use super::env::{get_remaining_points, set_remaining_points, ASEnv, Metered};
+use super::error::LaunchError;
 use crate::types::Response;
-use crate::{GasCosts, Interface};
+use crate::{GasCosts, Interface, RunOptions};
 use anyhow::{bail, Result};
 use as_ffi_bindings::{BufferPtr, Read as ASRead, Write as ASWrite};
+use std::sync::Arc;
+use std::time::Instant;
 use wasmer::{
-    imports, Function, FunctionEnv, Imports, Instance, InstantiationError, Module, Store, Value,
+    imports, Extern, Function, FunctionEnv, FunctionEnvMut, Imports, Instance, InstantiationError,
+    Module, Store, Value,
 };
 use wasmer_middlewares::metering::{self, MeteringPoints};
 use wasmer_types::TrapCode;
 
+/// Experimental host functions added to the imports of an AssemblyScript
+/// module, as (namespace, name, function) triples.
+///
+/// Intended for ABI research only: extra imports are NOT consensus-safe, they
+/// are neither metered nor known by the other nodes. They can only add new
+/// imports, shadowing a core import is an error. Sub-calls made by the
+/// executed module do not get them.
+pub type ExtraImports =
+    Arc<dyn Fn(&mut Store, &FunctionEnv<ASEnv>) -> Vec<(String, String, Function)> + Send + Sync>;
+
 pub(crate) struct ASContext {
     pub env: ASEnv,
     pub module: Module,
+    pub extra_imports: Option<ExtraImports>,
 }
 
 /// Execution context of an AS module.
@@ -26,10 +41,12 @@ impl ASContext {
         interface: &dyn Interface,
         binary_module: Module,
         gas_costs: GasCosts,
+        options: RunOptions,
     ) -> Self {
         Self {
-            env: ASEnv::new(interface, gas_costs),
+            env: ASEnv::with_options(interface, gas_costs, options),
             module: binary_module,
+            extra_imports: None,
         }
     }
 
@@ -38,7 +55,9 @@ impl ASContext {
         &mut self,
         store: &mut Store,
     ) -> Result<(Instance, FunctionEnv<ASEnv>, u64)> {
-        let (imports, mut fenv) = self.resolver(store);
+        let (mut imports, mut fenv) = self.resolver(store);
+        self.add_extra_imports(store, &fenv, &mut imports)?;
+        self.profile_imports(store, &mut imports);
         match Instance::new(store, &self.module, &imports) {
             Ok(instance) => {
                 self.init_with_instance(store, &instance, &mut fenv)?;
@@ -80,25 +99,107 @@ impl ASContext {
         function: &str,
         param: &[u8],
     ) -> Result<Response> {
+        self.sub_launch_cost(store)?;
+        // Now can exec
+        let wasm_func = instance.exports.get_function(function)?;
+        let param_ptr = match wasm_func.param_arity(store) {
+            0 if self.env.get_gas_costs().reject_unexpected_param
+                && !param.is_empty()
+                && function != crate::settings::MAIN =>
+            {
+                bail!("Function {} takes no parameter but one was given", function)
+            }
+            0 => None,
+            1 => {
+                self.check_param_size(param)?;
+                Some(
+                    BufferPtr::alloc(&param.to_vec(), self.env.get_ffi_env(), store)?.offset()
+                        as i32,
+                )
+            }
+            _ => bail!("Unexpected number of parameters in the function called"),
+        };
+        self.call_function(store, instance, function, param_ptr)
+    }
+
+    /// Same as `execution` but with a parameter already allocated in the
+    /// instance memory, see `alloc_pinned_param`
+    pub(crate) fn execution_with_param_ptr(
+        &self,
+        store: &mut Store,
+        instance: &Instance,
+        function: &str,
+        param_ptr: i32,
+    ) -> Result<Response> {
+        self.sub_launch_cost(store)?;
+        self.call_function(store, instance, function, Some(param_ptr))
+    }
+
+    /// Allocate a parameter in the instance memory and pin it (if the module
+    /// exports `__pin`) so that it survives the garbage collections happening
+    /// during successive calls.
+    pub(crate) fn alloc_pinned_param(
+        &self,
+        store: &mut Store,
+        instance: &Instance,
+        param: &[u8],
+    ) -> Result<i32> {
+        self.check_param_size(param)?;
+        let param_ptr =
+            BufferPtr::alloc(&param.to_vec(), self.env.get_ffi_env(), store)?.offset() as i32;
+        if let Ok(fn_pin) = instance
+            .exports
+            .get_typed_function::<i32, i32>(&store, "__pin")
+        {
+            fn_pin.call(store, param_ptr)?;
+        }
+        Ok(param_ptr)
+    }
+
+    /// Reject a parameter larger than `GasCosts::max_param_size` before
+    /// trying to allocate it
+    fn check_param_size(&self, param: &[u8]) -> Result<()> {
+        let max_param_size = self.env.get_gas_costs().max_param_size;
+        if param.len() as u64 > max_param_size {
+            bail!(
+                "Parameter of {} bytes exceeds the maximum size of {} bytes",
+                param.len(),
+                max_param_size
+            );
+        }
+        Ok(())
+    }
+
+    /// Sub initial metering cost
+    fn sub_launch_cost(&self, store: &mut Store) -> Result<()> {
         if cfg!(not(feature = "gas_calibration")) {
-            // Sub initial metering cost
             let metering_initial_cost = self.env.get_gas_costs().launch_cost;
             let remaining_gas = get_remaining_points(&self.env, store)?;
             if metering_initial_cost > remaining_gas {
-                bail!("Not enough gas to launch the virtual machine")
+                bail!(LaunchError::InsufficientGas {
+                    required: metering_initial_cost,
+                    available: remaining_gas,
+                })
             }
             set_remaining_points(&self.env, store, remaining_gas - metering_initial_cost)?;
         }
-        // Now can exec
+        Ok(())
+    }
+
+    /// Call an exported function and read its return value
+    fn call_function(
+        &self,
+        store: &mut Store,
+        instance: &Instance,
+        function: &str,
+        param_ptr: Option<i32>,
+    ) -> Result<Response> {
         let wasm_func = instance.exports.get_function(function)?;
         let argc = wasm_func.param_arity(store);
-        let res = if argc == 0 {
-            wasm_func.call(store, &[])
-        } else if argc == 1 {
-            let param_ptr = *BufferPtr::alloc(&param.to_vec(), self.env.get_ffi_env(), store)?;
-            wasm_func.call(store, &[Value::I32(param_ptr.offset() as i32)])
-        } else {
-            bail!("Unexpected number of parameters in the function called")
+        let res = match (argc, param_ptr) {
+            (0, _) => wasm_func.call(store, &[]),
+            (1, Some(param_ptr)) => wasm_func.call(store, &[Value::I32(param_ptr)]),
+            _ => bail!("Unexpected number of parameters in the function called"),
         };
 
         match res {
@@ -114,6 +215,11 @@ impl ASContext {
                         ret: Vec::new(), // main return empty vec
                         remaining_gas: remaining_gas?,
                         init_gas_cost: 0,
+                        gas_used: 0,
+                        abi_gas: None,
+                        abi_timings: Default::default(),
+                        used_abis: Default::default(),
+                        events: Default::default(),
                         #[cfg(feature = "execution-trace")]
                         trace: Default::default(),
                     });
@@ -138,6 +244,11 @@ impl ASContext {
                     ret,
                     remaining_gas: remaining_gas?,
                     init_gas_cost: 0,
+                    gas_used: 0,
+                    abi_gas: None,
+                    abi_timings: Default::default(),
+                    used_abis: Default::default(),
+                    events: Default::default(),
                     #[cfg(feature = "execution-trace")]
                     trace: Default::default(),
                 })
@@ -210,6 +321,62 @@ impl ASContext {
         Ok(())
     }
 
+    /// Merge the extra imports into the core ones, failing on collisions
+    fn add_extra_imports(
+        &self,
+        store: &mut Store,
+        fenv: &FunctionEnv<ASEnv>,
+        imports: &mut Imports,
+    ) -> Result<()> {
+        let Some(extra_imports) = &self.extra_imports else {
+            return Ok(());
+        };
+        for (namespace, name, function) in extra_imports(store, fenv) {
+            if imports.exists(&namespace, &name) {
+                bail!("Extra import {}.{} shadows a core import", namespace, name);
+            }
+            imports.define(&namespace, &name, function);
+        }
+        Ok(())
+    }
+
+    /// Wrap every import in a function measuring the wall-clock time spent in
+    /// it, if ABI profiling is enabled, and recording that it was called, if
+    /// ABI usage recording is enabled. Nested calls are included in the time
+    /// of their caller.
+    fn profile_imports(&self, store: &mut Store, imports: &mut Imports) {
+        if self.env.abi_timings.is_none() && self.env.used_abis.is_none() {
+            return;
+        }
+        let penv = FunctionEnv::new(store, ());
+        for ((namespace, name), import) in &*imports {
+            let Extern::Function(function) = import else {
+                continue;
+            };
+            let ty = function.ty(store);
+            let abi_timings = self.env.abi_timings.clone();
+            let used_abis = self.env.used_abis.clone();
+            let abi_name = name.clone();
+            let profiled = Function::new_with_env(
+                store,
+                &penv,
+                ty,
+                move |mut penv: FunctionEnvMut<()>, args: &[Value]| {
+                    if let Some(used_abis) = &used_abis {
+                        used_abis.lock().insert(abi_name.clone());
+                    }
+                    let start = Instant::now();
+                    let res = function.call(&mut penv, args);
+                    if let Some(abi_timings) = &abi_timings {
+                        *abi_timings.lock().entry(abi_name.clone()).or_default() += start.elapsed();
+                    }
+                    res.map(Vec::from)
+                },
+            );
+            imports.define(&namespace, &name, profiled);
+        }
+    }
+
     pub(crate) fn resolver(&self, store: &mut Store) -> (Imports, FunctionEnv<ASEnv>) {
         let fenv = FunctionEnv::new(store, self.env.clone());
 
@@ -231,10 +398,23 @@ impl ASContext {
                 "assembly_script_print" => Function::new_typed_with_env(store, &fenv, assembly_script_print),
                 "assembly_script_call" => Function::new_typed_with_env(store, &fenv, assembly_script_call),
                 "assembly_script_get_remaining_gas" => Function::new_typed_with_env(store, &fenv, assembly_script_get_remaining_gas),
+                "assembly_script_has_gas_at_least" => Function::new_typed_with_env(store, &fenv, assembly_script_has_gas_at_least),
+                "assembly_script_estimate_abi_cost" => Function::new_typed_with_env(store, &fenv, assembly_script_estimate_abi_cost),
+                "assembly_script_get_op_count" => Function::new_typed_with_env(store, &fenv, assembly_script_get_op_count),
+                "assembly_script_get_remaining_time_ms" => Function::new_typed_with_env(store, &fenv, assembly_script_get_remaining_time_ms),
+                "assembly_script_get_logical_clock" => Function::new_typed_with_env(store, &fenv, assembly_script_get_logical_clock),
                 "assembly_script_create_sc" => Function::new_typed_with_env(store, &fenv, assembly_script_create_sc),
                 "assembly_script_set_data" => Function::new_typed_with_env(store, &fenv, assembly_script_set_data),
                 "assembly_script_set_data_for" => Function::new_typed_with_env(store, &fenv, assembly_script_set_data_for),
+                "assembly_script_set_data_if_absent" => Function::new_typed_with_env(store, &fenv, assembly_script_set_data_if_absent),
+                "assembly_script_swap_data" => Function::new_typed_with_env(store, &fenv, assembly_script_swap_data),
+                "assembly_script_add_to_data" => Function::new_typed_with_env(store, &fenv, assembly_script_add_to_data),
+                "assembly_script_rate_limit" => Function::new_typed_with_env(store, &fenv, assembly_script_rate_limit),
+                "assembly_script_savepoint" => Function::new_typed_with_env(store, &fenv, assembly_script_savepoint),
+                "assembly_script_rollback_to" => Function::new_typed_with_env(store, &fenv, assembly_script_rollback_to),
                 "assembly_script_get_data" => Function::new_typed_with_env(store, &fenv, assembly_script_get_data),
+                "assembly_script_get_data_multi" => Function::new_typed_with_env(store, &fenv, assembly_script_get_data_multi),
+                "assembly_script_get_data_at_slot" => Function::new_typed_with_env(store, &fenv, assembly_script_get_data_at_slot),
                 "assembly_script_get_data_for" => Function::new_typed_with_env(store, &fenv, assembly_script_get_data_for),
                 "assembly_script_delete_data" => Function::new_typed_with_env(store, &fenv, assembly_script_delete_data),
                 "assembly_script_delete_data_for" => Function::new_typed_with_env(store, &fenv, assembly_script_delete_data_for),
@@ -245,35 +425,63 @@ impl ASContext {
                 "assembly_script_get_owned_addresses" => Function::new_typed_with_env(store, &fenv, assembly_script_get_owned_addresses),
                 "assembly_script_get_call_stack" => Function::new_typed_with_env(store, &fenv, assembly_script_get_call_stack),
                 "assembly_script_generate_event" => Function::new_typed_with_env(store, &fenv, assembly_script_generate_event),
+                "assembly_script_generate_error_event" => Function::new_typed_with_env(store, &fenv, assembly_script_generate_error_event),
                 "assembly_script_transfer_coins" => Function::new_typed_with_env(store, &fenv, assembly_script_transfer_coins),
+                "assembly_script_get_paid_to" => Function::new_typed_with_env(store, &fenv, assembly_script_get_paid_to),
                 "assembly_script_transfer_coins_for" => Function::new_typed_with_env(store, &fenv, assembly_script_transfer_coins_for),
                 "assembly_script_get_balance" => Function::new_typed_with_env(store, &fenv, assembly_script_get_balance),
+                "assembly_script_get_min_balance" => Function::new_typed_with_env(store, &fenv, assembly_script_get_min_balance),
+                "assembly_script_get_estimated_refund" => Function::new_typed_with_env(store, &fenv, assembly_script_get_estimated_refund),
+                "assembly_script_get_interface_version" => Function::new_typed_with_env(store, &fenv, assembly_script_get_interface_version),
                 "assembly_script_get_balance_for" => Function::new_typed_with_env(store, &fenv, assembly_script_get_balance_for),
+                "assembly_script_get_balance_delta" => Function::new_typed_with_env(store, &fenv, assembly_script_get_balance_delta),
+                "assembly_script_get_remaining_transferable" => Function::new_typed_with_env(store, &fenv, assembly_script_get_remaining_transferable),
                 "assembly_script_hash" => Function::new_typed_with_env(store, &fenv, assembly_script_hash),
+                "assembly_script_hash_ex" => Function::new_typed_with_env(store, &fenv, assembly_script_hash_ex),
+                "assembly_script_hash_blake3" => Function::new_typed_with_env(store, &fenv, assembly_script_hash_blake3),
                 "assembly_script_hash_sha256" =>  Function::new_typed_with_env(store, &fenv, assembly_script_hash_sha256),
                 "assembly_script_keccak256_hash" =>  Function::new_typed_with_env(store, &fenv, assembly_script_keccak256_hash),
                 "assembly_script_signature_verify" => Function::new_typed_with_env(store, &fenv, assembly_script_signature_verify),
+                "assembly_script_verify_threshold" => Function::new_typed_with_env(store, &fenv, assembly_script_verify_threshold),
+                "assembly_script_verify_state_inclusion" => Function::new_typed_with_env(store, &fenv, assembly_script_verify_state_inclusion),
                 "assembly_script_evm_signature_verify" => Function::new_typed_with_env(store, &fenv, assembly_script_evm_signature_verify),
                 "assembly_script_evm_get_address_from_pubkey" => Function::new_typed_with_env(store, &fenv, assembly_script_evm_get_address_from_pubkey),
                 "assembly_script_evm_get_pubkey_from_signature" => Function::new_typed_with_env(store, &fenv, assembly_script_evm_get_pubkey_from_signature),
                 "assembly_script_is_address_eoa" => Function::new_typed_with_env(store, &fenv, assembly_script_is_address_eoa),
                 "assembly_script_address_from_public_key" => Function::new_typed_with_env(store, &fenv, assembly_script_address_from_public_key),
+                "assembly_script_address_matches_pubkey" => Function::new_typed_with_env(store, &fenv, assembly_script_address_matches_pubkey),
+                "assembly_script_resolve_name" => Function::new_typed_with_env(store, &fenv, assembly_script_resolve_name),
                 "assembly_script_validate_address" => Function::new_typed_with_env(store, &fenv, assembly_script_validate_address),
                 "assembly_script_unsafe_random" => Function::new_typed_with_env(store, &fenv, assembly_script_unsafe_random),
+                "assembly_script_random_from_seed" => Function::new_typed_with_env(store, &fenv, assembly_script_random_from_seed),
                 "assembly_script_get_call_coins" => Function::new_typed_with_env(store, &fenv, assembly_script_get_call_coins),
+                "assembly_script_get_op_deposit" => Function::new_typed_with_env(store, &fenv, assembly_script_get_op_deposit),
+                "assembly_script_get_priority_tier" => Function::new_typed_with_env(store, &fenv, assembly_script_get_priority_tier),
                 "assembly_script_get_time" => Function::new_typed_with_env(store, &fenv, assembly_script_get_time),
+                "assembly_script_get_runtime_version" => Function::new_typed_with_env(store, &fenv, assembly_script_get_runtime_version),
                 "assembly_script_send_message" => Function::new_typed_with_env(store, &fenv, assembly_script_send_message),
+                "assembly_script_get_my_scheduled_slots" => Function::new_typed_with_env(store, &fenv, assembly_script_get_my_scheduled_slots),
+                "assembly_script_get_my_deferred_coins" => Function::new_typed_with_env(store, &fenv, assembly_script_get_my_deferred_coins),
+                "assembly_script_get_my_storage_bytes" => Function::new_typed_with_env(store, &fenv, assembly_script_get_my_storage_bytes),
                 "assembly_script_get_origin_operation_id" => Function::new_typed_with_env(store, &fenv, assembly_script_get_origin_operation_id),
+                "assembly_script_get_block_producer" => Function::new_typed_with_env(store, &fenv, assembly_script_get_block_producer),
                 "assembly_script_get_current_period" => Function::new_typed_with_env(store, &fenv, assembly_script_get_current_period),
                 "assembly_script_get_current_thread" => Function::new_typed_with_env(store, &fenv, assembly_script_get_current_thread),
+                "assembly_script_get_current_slot" => Function::new_typed_with_env(store, &fenv, assembly_script_get_current_slot),
+                "assembly_script_compare_slot" => Function::new_typed_with_env(store, &fenv, assembly_script_compare_slot),
                 "assembly_script_set_bytecode" => Function::new_typed_with_env(store, &fenv, assembly_script_set_bytecode),
                 "assembly_script_set_bytecode_for" => Function::new_typed_with_env(store, &fenv, assembly_script_set_bytecode_for),
                 "assembly_script_get_op_keys" => Function::new_typed_with_env(store, &fenv, assembly_script_get_op_keys),
                 "assembly_script_get_op_keys_prefix" => Function::new_typed_with_env(store, &fenv, assembly_script_get_op_keys_prefix),
                 "assembly_script_get_keys" => Function::new_typed_with_env(store, &fenv, assembly_script_get_keys),
                 "assembly_script_get_keys_for" => Function::new_typed_with_env(store, &fenv, assembly_script_get_keys_for),
+                "assembly_script_get_keys_prefix" => Function::new_typed_with_env(store, &fenv, assembly_script_get_keys_prefix),
+                "assembly_script_datastore_diff" => Function::new_typed_with_env(store, &fenv, assembly_script_datastore_diff),
+                "assembly_script_get_writers" => Function::new_typed_with_env(store, &fenv, assembly_script_get_writers),
                 "assembly_script_has_op_key" => Function::new_typed_with_env(store, &fenv, assembly_script_has_op_key),
                 "assembly_script_get_op_data" => Function::new_typed_with_env(store, &fenv, assembly_script_get_op_data),
+                "assembly_script_get_op_data_multi" => Function::new_typed_with_env(store, &fenv, assembly_script_get_op_data_multi),
+                "assembly_script_get_message_data" => Function::new_typed_with_env(store, &fenv, assembly_script_get_message_data),
                 "assembly_script_get_bytecode" => Function::new_typed_with_env(store, &fenv, assembly_script_get_bytecode),
                 "assembly_script_get_bytecode_for" => Function::new_typed_with_env(store, &fenv, assembly_script_get_bytecode_for),
                 "assembly_script_local_call" => Function::new_typed_with_env(store, &fenv, assembly_script_local_call),
@@ -281,6 +489,7 @@ impl ASContext {
                 "assembly_script_caller_has_write_access" => Function::new_typed_with_env(store, &fenv, assembly_script_caller_has_write_access),
                 "assembly_script_function_exists" => Function::new_typed_with_env(store, &fenv, assembly_script_function_exists),
                 "assembly_script_chain_id" => Function::new_typed_with_env(store, &fenv, assembly_script_chain_id),
+                "assembly_script_get_fee_split" => Function::new_typed_with_env(store, &fenv, assembly_script_get_fee_split),
             },
         };
 
@@ -4,10 +4,16 @@ use crate::types::Interface;
 #[cfg(feature = "execution-trace")]
 use crate::types::AbiTrace;
 
-use crate::GasCosts;
+use crate::execution::CallContext;
+use crate::{AbortInfo, GasCosts, RunOptions};
+use parking_lot::Mutex;
 use std::{
-    collections::HashMap,
-    sync::{atomic::AtomicBool, Arc},
+    collections::{BTreeSet, HashMap},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 use wasmer::{AsStoreMut, Global};
 
@@ -34,20 +40,84 @@ pub struct ASEnv {
     gas_costs: GasCosts,
     /// Initially added for gas calibration but unused at the moment.
     param_size_map: HashMap<String, Option<Global>>,
+    /// Cumulated number of bytes copied into the wasm memory by read ABIs.
+    bytes_read: u64,
+    /// Cumulated number of operation datastore bytes read by the op-data ABIs.
+    op_data_read: u64,
+    /// Gas remaining when the top-level execution started, see
+    /// `assembly_script_get_logical_clock`.
+    pub(crate) clock_origin: Option<u64>,
+    /// Metering points given to the call, see `assembly_script_get_op_count`.
+    pub(crate) start_points: Option<u64>,
+    /// End of the execution time limit, shared with the sub-calls, see
+    /// `RunOptions::max_execution_time`.
+    pub(crate) execution_deadline: Option<Instant>,
+    /// Number of nested calls above the current execution, see
+    /// `GasCosts::max_call_depth`.
+    pub(crate) call_depth: u32,
+    /// Balance of the current address when the execution started, see
+    /// `assembly_script_get_balance_delta`. `None` if the interface failed to
+    /// provide it.
+    pub(crate) initial_balance: Option<u64>,
+    /// Options of the current execution, inherited by its sub-calls.
+    pub(crate) options: RunOptions,
+    /// Cumulated size of the events generated during the current execution.
+    pub(crate) event_bytes: u64,
+    /// Events generated during the current execution, see `Response::events`.
+    pub(crate) events: Vec<String>,
+    /// Coins sent by the current execution to each address, see
+    /// `assembly_script_get_paid_to`.
+    pub(crate) paid_to: HashMap<String, u64>,
+    /// Arguments of the `abort` call of the contract, if it called it.
+    pub(crate) abort_info: Option<AbortInfo>,
+    /// Savepoints taken during the current execution, oldest first.
+    pub(crate) savepoints: Vec<u64>,
+    /// Wall-clock time spent in each host function, `None` unless
+    /// `RunOptions::profile_abis` is set. Shared with the profiled imports.
+    pub(crate) abi_timings: Option<Arc<Mutex<HashMap<String, Duration>>>>,
+    /// Names of the host functions called, `None` unless
+    /// `RunOptions::record_abi_usage` is set. Shared with the profiled imports.
+    pub(crate) used_abis: Option<Arc<Mutex<BTreeSet<String>>>>,
+    /// Cumulated gas charged by the host functions, see `Response::abi_gas`.
+    pub(crate) abi_gas: Arc<AtomicU64>,
     #[cfg(feature = "execution-trace")]
     pub trace: Vec<AbiTrace>,
 }
 
 impl ASEnv {
     pub fn new(interface: &dyn Interface, gas_costs: GasCosts) -> Self {
+        Self::with_options(interface, gas_costs, RunOptions::default())
+    }
+    /// Same as `new` for an execution run with the given options.
+    pub fn with_options(
+        interface: &dyn Interface,
+        gas_costs: GasCosts,
+        options: RunOptions,
+    ) -> Self {
         Self {
             ffi_env: Default::default(),
             abi_enabled: Arc::new(AtomicBool::new(false)),
+            abi_timings: options.profile_abis.then(Default::default),
+            used_abis: options.record_abi_usage.then(Default::default),
+            execution_deadline: execution_deadline(&options),
             gas_costs,
+            options,
             interface: interface.clone_box(),
             remaining_points: None,
             exhausted_points: None,
             param_size_map: Default::default(),
+            bytes_read: 0,
+            op_data_read: 0,
+            clock_origin: None,
+            call_depth: 0,
+            start_points: None,
+            initial_balance: None,
+            event_bytes: 0,
+            events: Vec::new(),
+            paid_to: HashMap::new(),
+            abort_info: None,
+            savepoints: Vec::new(),
+            abi_gas: Default::default(),
             #[cfg(feature = "execution-trace")]
             trace: Default::default(),
         }
@@ -61,6 +131,106 @@ impl ASEnv {
     pub fn get_ffi_env_as_mut(&mut self) -> &mut as_ffi_bindings::Env {
         &mut self.ffi_env
     }
+    /// Remaining gas of the execution running in `store`, for host code
+    /// (ABI implementations, tests) peeking at it mid-execution.
+    ///
+    /// Fails when gas is not metered (`gas_calibration` feature) or if the env
+    /// is not bound to an instance yet.
+    pub fn remaining_gas(&self, store: &mut impl AsStoreMut) -> anyhow::Result<u64> {
+        if cfg!(feature = "gas_calibration") {
+            anyhow::bail!("remaining gas is not metered with the gas_calibration feature");
+        }
+        Ok(get_remaining_points(self, store)?)
+    }
+    /// Account for `len` bytes about to be copied into the wasm memory by a
+    /// read ABI, fails if the read budget is exceeded.
+    pub(crate) fn consume_read_budget(&mut self, len: usize) -> ABIResult<()> {
+        self.bytes_read = self.bytes_read.saturating_add(len as u64);
+        match self.gas_costs.max_bytes_read {
+            Some(max_bytes_read) if self.bytes_read > max_bytes_read => {
+                abi_bail!("read budget exceeded")
+            }
+            _ => Ok(()),
+        }
+    }
+    /// Account for `len` bytes of the operation datastore about to be read by
+    /// an op-data ABI, fails if the op-data read budget is exceeded.
+    pub(crate) fn consume_op_data_budget(&mut self, len: usize) -> ABIResult<()> {
+        self.op_data_read = self.op_data_read.saturating_add(len as u64);
+        if self.op_data_read > self.gas_costs.max_op_data_read {
+            abi_bail!("operation datastore read budget exceeded")
+        }
+        Ok(())
+    }
+    /// Account for `amount` coins sent by the current execution to `address`.
+    pub(crate) fn record_payment(&mut self, address: &str, amount: u64) {
+        let paid = self.paid_to.entry(address.to_string()).or_default();
+        *paid = paid.saturating_add(amount);
+    }
+    /// Account for an event of `len` bytes about to be generated, fails if
+    /// the cumulative event size limit is exceeded.
+    pub(crate) fn consume_event_budget(&mut self, len: usize) -> ABIResult<()> {
+        self.event_bytes = self.event_bytes.saturating_add(len as u64);
+        if self.event_bytes > self.gas_costs.max_total_event_bytes {
+            abi_bail!("total event size limit exceeded")
+        }
+        Ok(())
+    }
+    /// Clear the per-execution state so the env can be reused for another,
+    /// isolated, execution without cloning the interface again.
+    ///
+    /// Cleared: the AS ffi env, the metering globals and the gas calibration
+    /// parameters (all bound to the previous instance), the ABI enabling flag,
+    /// the ABI timings and usage (shared with the previous instance imports)
+    /// and everything cleared by `reset_run`.
+    ///
+    /// Retained: the interface, the gas costs and the run options, with the
+    /// modes derived from them (bootstrap, pure, profiling, usage recording).
+    /// State kept by the interface itself (call stack, pending writes, events)
+    /// is not touched and must be reset on the node side.
+    pub fn reset(&mut self) {
+        self.ffi_env = Default::default();
+        self.abi_enabled = Arc::new(AtomicBool::new(false));
+        self.remaining_points = None;
+        self.exhausted_points = None;
+        self.param_size_map.clear();
+        self.reset_run();
+        self.abi_timings = self.options.profile_abis.then(Default::default);
+        self.used_abis = self.options.record_abi_usage.then(Default::default);
+        self.abi_gas = Default::default();
+    }
+    /// Clear the state of the current top-level run only, keeping what is
+    /// bound to the instance, so that the same instance can run again as a new
+    /// top-level execution, see `exec_as_module_with_shared_param`.
+    ///
+    /// Cleared: the read, op-data read and event budgets, the events, the
+    /// payments, the abort info, the savepoints, the logical clock origin, the
+    /// start points, the initial balance, the ABI gas, timings and usage, and
+    /// the trace. The call depth is set back to 0 and the execution time limit
+    /// restarts from now.
+    pub(crate) fn reset_run(&mut self) {
+        self.bytes_read = 0;
+        self.op_data_read = 0;
+        self.clock_origin = None;
+        self.call_depth = 0;
+        self.start_points = None;
+        self.execution_deadline = execution_deadline(&self.options);
+        self.initial_balance = None;
+        self.event_bytes = 0;
+        self.events.clear();
+        self.paid_to.clear();
+        self.abort_info = None;
+        self.savepoints.clear();
+        if let Some(abi_timings) = &self.abi_timings {
+            abi_timings.lock().clear();
+        }
+        if let Some(used_abis) = &self.used_abis {
+            used_abis.lock().clear();
+        }
+        self.abi_gas.store(0, Ordering::Relaxed);
+        #[cfg(feature = "execution-trace")]
+        self.trace.clear();
+    }
 }
 
 impl Metered for ASEnv {
@@ -76,6 +246,48 @@ impl Metered for ASEnv {
     fn get_gas_costs(&self) -> GasCosts {
         self.gas_costs.clone()
     }
+    fn add_abi_gas(&self, gas: u64) {
+        self.abi_gas.fetch_add(gas, Ordering::Relaxed);
+    }
+    fn check_deadline(&self) -> ABIResult<()> {
+        if self.timed_out() {
+            abi_bail!("execution time limit exceeded");
+        }
+        Ok(())
+    }
+}
+
+impl ASEnv {
+    /// Run as a sub-call in the given context: its call depth, and the
+    /// execution time limit of the top-level execution if it has one
+    pub(crate) fn inherit(&mut self, call_context: &CallContext) {
+        self.call_depth = call_context.call_depth;
+        if call_context.execution_deadline.is_some() {
+            self.execution_deadline = call_context.execution_deadline;
+        }
+    }
+    /// Context given by the current execution to its sub-calls
+    pub(crate) fn subcall_context(&self) -> CallContext {
+        CallContext {
+            clock_origin: self.clock_origin,
+            execution_deadline: self.execution_deadline,
+            call_depth: self.call_depth + 1,
+        }
+    }
+    /// Whether the execution time limit is exceeded, see
+    /// `RunOptions::max_execution_time`
+    pub(crate) fn timed_out(&self) -> bool {
+        self.execution_deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+/// End of the execution time limit of a top-level execution run with
+/// `options`, starting now
+fn execution_deadline(options: &RunOptions) -> Option<Instant> {
+    options
+        .max_execution_time
+        .map(|limit| Instant::now() + limit)
 }
 
 /// Trait describing a metered object.
@@ -86,6 +298,12 @@ pub(crate) trait Metered {
     fn get_remaining_points(&self) -> Option<&Global>;
     fn get_gc_param(&self, name: &str) -> Option<&Global>;
     fn get_gas_costs(&self) -> GasCosts;
+    /// Account for `gas` charged by a host function
+    fn add_abi_gas(&self, _gas: u64) {}
+    /// Abort if the execution time limit is exceeded
+    fn check_deadline(&self) -> ABIResult<()> {
+        Ok(())
+    }
 }
 
 /// Get remaining metering points.
@@ -160,6 +378,7 @@ pub(crate) fn sub_remaining_gas(
     let remaining_gas = get_remaining_points(env, store)?;
     if let Some(remaining_gas) = remaining_gas.checked_sub(gas) {
         set_remaining_points(env, store, remaining_gas)?;
+        env.add_abi_gas(gas);
     } else {
         abi_bail!("Out of gas")
     }
@@ -171,6 +390,7 @@ pub(crate) fn sub_remaining_gas_abi(
     store: &mut impl AsStoreMut,
     abi_name: &str,
 ) -> ABIResult<()> {
+    env.check_deadline()?;
     sub_remaining_gas(
         env,
         store,
@@ -16,6 +16,13 @@ pub enum ABIError {
     VMError(#[from] VMError),
 }
 
+/// The remaining gas does not cover the launch cost of the virtual machine
+#[derive(Error, Display, Debug)]
+pub(crate) enum LaunchError {
+    /// Not enough gas to launch the virtual machine
+    InsufficientGas { required: u64, available: u64 },
+}
+
 macro_rules! abi_bail {
     ($err:expr) => {
         return Err(super::ABIError::Error(anyhow::anyhow!($err.to_string())))
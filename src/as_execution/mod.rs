@@ -4,23 +4,31 @@ mod context;
 pub(crate) mod env;
 mod error;
 
-use crate::error::{exec_bail, VMResult};
-use crate::execution::Compiler;
+use crate::error::{exec_bail, TrapDump, TrapKind, VMError, VMResult};
+use crate::execution::{CallContext, Compiler};
 use crate::middlewares::gas_calibration::{get_gas_calibration_result, GasCalibrationResult};
+use crate::middlewares::global_exporter::{GlobalExporter, GLOBALS_EXPORTED_MARKER};
 use crate::middlewares::{dumper::Dumper, gas_calibration::GasCalibration};
 use crate::settings::max_number_of_pages;
 use crate::tunable_memory::LimitingTunables;
-use crate::{GasCosts, Interface, Response};
-use anyhow::Result;
-use std::sync::Arc;
+use crate::{GasCosts, Interface, Response, RunOptions};
+use anyhow::{anyhow, bail, Result};
+use sha2::{Digest, Sha256};
+use std::sync::{atomic::Ordering, Arc};
+use wasmer::wasmparser::{Operator, Parser, Payload, TypeRef};
 use wasmer::NativeEngineExt;
-use wasmer::{wasmparser::Operator, BaseTunables, Engine, EngineBuilder, Pages, Target};
-use wasmer::{CompilerConfig, Cranelift, Features, Module, Store};
+use wasmer::{BaseTunables, Engine, EngineBuilder, Pages, Target};
+use wasmer::{
+    CompilerConfig, Cranelift, Features, FunctionEnv, Global, Instance, Module, Mutability, Store,
+    Value,
+};
 use wasmer_compiler_singlepass::Singlepass;
 use wasmer_middlewares::metering::MeteringPoints;
 use wasmer_middlewares::{metering, Metering};
 
+pub use context::ExtraImports;
 pub(crate) use context::*;
+pub use env::ASEnv;
 pub(crate) use error::*;
 
 /// An executable runtime module compiled from an AssemblyScript SC
@@ -29,6 +37,8 @@ pub struct ASModule {
     pub(crate) binary_module: Module,
     pub(crate) initial_limit: u64,
     pub compiler: Compiler,
+    /// Hash of the bytecode this module was compiled from
+    pub(crate) code_hash: [u8; 32],
     // Compilation engine can not be dropped
     pub(crate) _engine: Engine,
 }
@@ -40,21 +50,68 @@ impl ASModule {
         gas_costs: GasCosts,
         compiler: Compiler,
     ) -> Result<Self> {
+        Self::compile(bytecode, limit, gas_costs, compiler, false)
+    }
+
+    /// Same as `new`, but the mutable globals of the module are exported by
+    /// the `GlobalExporter` middleware so that its instances can be
+    /// snapshotted, see `run_function_with_memory_snapshot`
+    pub(crate) fn new_with_exported_globals(
+        bytecode: &[u8],
+        limit: u64,
+        gas_costs: GasCosts,
+        compiler: Compiler,
+    ) -> Result<Self> {
+        Self::compile(bytecode, limit, gas_costs, compiler, true)
+    }
+
+    fn compile(
+        bytecode: &[u8],
+        limit: u64,
+        gas_costs: GasCosts,
+        compiler: Compiler,
+        export_globals: bool,
+    ) -> Result<Self> {
+        validate_module(bytecode, &gas_costs)?;
         let engine = match compiler {
-            Compiler::CL => init_cl_engine(limit, gas_costs),
-            Compiler::SP => init_sp_engine(limit, gas_costs),
+            Compiler::CL => init_cl_engine(limit, gas_costs, export_globals),
+            Compiler::SP => init_sp_engine(limit, gas_costs, export_globals),
         };
+        let binary_module = Module::new(&engine, bytecode)?;
+        let compiler_label = match compiler {
+            Compiler::CL => "cl",
+            Compiler::SP => "sp",
+        };
+        metrics::increment_counter!("massa_sc_runtime_compilations_total", "compiler" => compiler_label);
         Ok(Self {
-            binary_module: Module::new(&engine, bytecode)?,
+            binary_module,
             initial_limit: limit,
             compiler,
+            code_hash: Sha256::digest(bytecode).into(),
             _engine: engine,
         })
     }
 
+    /// Hash of the bytecode this module was compiled from.
+    ///
+    /// The hashed bytecode is the tag-stripped one, i.e. the plain wasm
+    /// binary without any runtime dispatch byte. For AssemblyScript modules
+    /// the dispatch byte is the first byte of the wasm magic number, so the
+    /// whole bytecode is hashed.
+    ///
+    /// This is the key used to identify a module in a compiled module cache.
+    pub fn code_hash(&self) -> [u8; 32] {
+        self.code_hash
+    }
+
+    /// Serialize the compiled module, the code hash is appended at the end
     pub fn serialize(&self) -> Result<Vec<u8>> {
         match self.compiler {
-            Compiler::CL => Ok(self.binary_module.serialize()?.to_vec()),
+            Compiler::CL => {
+                let mut ser = self.binary_module.serialize()?.to_vec();
+                ser.extend_from_slice(&self.code_hash);
+                Ok(ser)
+            }
             Compiler::SP => {
                 panic!("cannot serialize a module compiled with Singlepass")
             }
@@ -62,8 +119,12 @@ impl ASModule {
     }
 
     pub fn deserialize(ser_module: &[u8], limit: u64, gas_costs: GasCosts) -> Result<Self> {
+        let Some(split_index) = ser_module.len().checked_sub(32) else {
+            return Err(anyhow!("Serialized module is missing its code hash"));
+        };
+        let (ser_module, code_hash) = ser_module.split_at(split_index);
         // Deserialization is only meant for Cranelift modules
-        let engine = init_cl_engine(limit, gas_costs);
+        let engine = init_cl_engine(limit, gas_costs, false);
         let store = Store::new(engine.clone());
         // Unsafe because code injection is possible
         // That's not an issue because we only deserialize modules we have
@@ -73,6 +134,7 @@ impl ASModule {
             binary_module: module,
             initial_limit: limit,
             compiler: Compiler::CL,
+            code_hash: code_hash.try_into()?,
             _engine: engine,
         })
     }
@@ -85,6 +147,15 @@ impl ASModule {
             .functions()
             .any(|export| export.name() == function)
     }
+
+    /// Names of the functions exported by the compiled module
+    pub(crate) fn exported_functions(&self) -> Vec<String> {
+        self.binary_module
+            .exports()
+            .functions()
+            .map(|export| export.name().to_string())
+            .collect()
+    }
 }
 
 // Compiler feature.
@@ -111,7 +182,86 @@ const FEATURES: Features = Features {
     extended_const: false,  // experimental
 };
 
-pub(crate) fn init_sp_engine(limit: u64, gas_costs: GasCosts) -> Engine {
+/// Reject modules exceeding one of the limits of `gas_costs` before they
+/// reach the compiler, in a single pass over the bytecode:
+/// * `GasCosts::compilation_limits`
+/// * `GasCosts::max_locals_per_function`, checked before the compiler
+///   register allocation
+/// * `GasCosts::reject_start_section`
+fn validate_module(bytecode: &[u8], gas_costs: &GasCosts) -> Result<()> {
+    let limits = &gas_costs.compilation_limits;
+    let check = |what: &str, count: u64, limit: u32| {
+        if count > u64::from(limit) {
+            return Err(anyhow!(
+                "Module declares {} {}, the limit is {}",
+                count,
+                what,
+                limit
+            ));
+        }
+        Ok(())
+    };
+    let mut function_index = 0u32;
+    for payload in Parser::new(0).parse_all(bytecode) {
+        match payload? {
+            Payload::ImportSection(reader) => {
+                check("imports", reader.count().into(), limits.max_imports)?;
+                for import in reader {
+                    if let TypeRef::Func(_) = import?.ty {
+                        function_index += 1;
+                    }
+                }
+            }
+            Payload::FunctionSection(reader) => {
+                check("functions", reader.count().into(), limits.max_functions)?;
+            }
+            Payload::GlobalSection(reader) => {
+                check("globals", reader.count().into(), limits.max_globals)?;
+            }
+            Payload::StartSection { .. } if gas_costs.reject_start_section => {
+                bail!("modules with a wasm start section are not allowed");
+            }
+            Payload::CodeSectionEntry(body) => {
+                let size = body.range().len() as u64;
+                if size > u64::from(limits.max_function_body_size) {
+                    bail!(
+                        "Function {} body is {} bytes long, the limit is {}",
+                        function_index,
+                        size,
+                        limits.max_function_body_size
+                    );
+                }
+                let mut locals = body.get_locals_reader()?;
+                let mut count = 0u64;
+                for _ in 0..locals.get_count() {
+                    count += u64::from(locals.read()?.0);
+                }
+                if count > u64::from(gas_costs.max_locals_per_function) {
+                    bail!(
+                        "Function {} declares {} locals, the limit is {}",
+                        function_index,
+                        count,
+                        gas_costs.max_locals_per_function
+                    );
+                }
+                function_index += 1;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Build an engine compiling with Singlepass, with the `GlobalExporter`
+/// middleware if `export_globals` is set.
+///
+/// NOTE: engines must not be cached nor shared between modules: the wasmer
+/// `Metering` middleware keeps the indexes of the globals it injects in the
+/// first module it transforms and panics if used for another one.
+pub(crate) fn init_sp_engine(limit: u64, gas_costs: GasCosts, export_globals: bool) -> Engine {
+    let pages_limit = gas_costs
+        .memory_pages_limit
+        .unwrap_or_else(max_number_of_pages);
     // Singlepass is used to compile arbitrary bytecode.
     //
     // Reference:
@@ -127,14 +277,18 @@ pub(crate) fn init_sp_engine(limit: u64, gas_costs: GasCosts) -> Engine {
         compiler_config.push_middleware(gas_calibration);
     } else {
         // Add metering middleware
-        let metering = Arc::new(Metering::new(limit, move |_: &Operator| -> u64 {
-            gas_costs.operator_cost
+        let metering = Arc::new(Metering::new(limit, move |operator: &Operator| -> u64 {
+            gas_costs.metered_operator_cost(operator)
         }));
         compiler_config.push_middleware(metering);
     }
+    // Export the globals, for the instance snapshots
+    if export_globals {
+        compiler_config.push_middleware(Arc::new(GlobalExporter));
+    }
 
     let base = BaseTunables::for_target(&Target::default());
-    let tunables = LimitingTunables::new(base, Pages(max_number_of_pages()));
+    let tunables = LimitingTunables::new(base, Pages(pages_limit));
 
     let mut engine = Engine::from(
         EngineBuilder::new(compiler_config)
@@ -145,7 +299,12 @@ pub(crate) fn init_sp_engine(limit: u64, gas_costs: GasCosts) -> Engine {
     engine
 }
 
-pub(crate) fn init_cl_engine(limit: u64, gas_costs: GasCosts) -> Engine {
+/// Build an engine compiling with Cranelift, with the `GlobalExporter`
+/// middleware if `export_globals` is set.
+pub(crate) fn init_cl_engine(limit: u64, gas_costs: GasCosts, export_globals: bool) -> Engine {
+    let pages_limit = gas_costs
+        .memory_pages_limit
+        .unwrap_or_else(max_number_of_pages);
     // Cranelift is used to compile bytecode that will be cached.
     //
     // Reference:
@@ -165,14 +324,18 @@ pub(crate) fn init_cl_engine(limit: u64, gas_costs: GasCosts) -> Engine {
         compiler_config.push_middleware(dumper);
     } else {
         // Add metering middleware
-        let metering = Arc::new(Metering::new(limit, move |_: &Operator| -> u64 {
-            gas_costs.operator_cost
+        let metering = Arc::new(Metering::new(limit, move |operator: &Operator| -> u64 {
+            gas_costs.metered_operator_cost(operator)
         }));
         compiler_config.push_middleware(metering);
     }
+    // Export the globals, for the instance snapshots
+    if export_globals {
+        compiler_config.push_middleware(Arc::new(GlobalExporter));
+    }
 
     let base = BaseTunables::for_target(&Target::default());
-    let tunables = LimitingTunables::new(base, Pages(max_number_of_pages()));
+    let tunables = LimitingTunables::new(base, Pages(pages_limit));
 
     let mut engine = Engine::from(
         EngineBuilder::new(compiler_config)
@@ -191,11 +354,15 @@ pub(crate) fn init_cl_engine(limit: u64, gas_costs: GasCosts) -> Engine {
 /// * `param`: Parameter passed to the function
 /// * `cache`: Cache of pre compiled modules
 /// * `gas_costs`: Cost in gas of every VM operation
+/// * `options`: Options of the execution, see `RunOptions`
+/// * `call_context`: Context given by the caller of a sub-call
+/// * `extra_imports`: Experimental host functions added to the imports
 ///
 /// Return:
 /// * Output of the executed function, remaininng gas after execution and the
 ///   initialization cost
 /// * Gas calibration result if it has been enabled
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn exec_as_module(
     interface: &dyn Interface,
     as_module: ASModule,
@@ -203,56 +370,303 @@ pub(crate) fn exec_as_module(
     param: &[u8],
     limit: u64,
     gas_costs: GasCosts,
+    options: RunOptions,
+    call_context: CallContext,
+    extra_imports: Option<ExtraImports>,
 ) -> VMResult<(Response, Option<GasCalibrationResult>)> {
     let engine = match as_module.compiler {
-        Compiler::CL => init_cl_engine(limit, gas_costs.clone()),
-        Compiler::SP => init_sp_engine(limit, gas_costs.clone()),
+        Compiler::CL => init_cl_engine(limit, gas_costs.clone(), false),
+        Compiler::SP => init_sp_engine(limit, gas_costs.clone(), false),
     };
     let mut store = Store::new(engine);
-    let mut context = ASContext::new(interface, as_module.binary_module, gas_costs);
+    let verbose_traps = options.verbose_traps;
+    #[cfg(any(test, feature = "testing"))]
+    let force_oog_after = gas_costs.force_oog_after;
+    let mut context = ASContext::new(interface, as_module.binary_module, gas_costs, options);
+    context.extra_imports = extra_imports;
+    context.env.inherit(&call_context);
 
     // save the gas remaining before sub-execution: used by readonly execution
     interface.save_gas_remaining_before_subexecution(limit);
 
-    let (instance, _fenv, init_rem_points) = context.create_vm_instance_and_init_env(&mut store)?;
+    let (instance, fenv, init_rem_points) = context.create_vm_instance_and_init_env(&mut store)?;
     let init_cost = as_module.initial_limit.saturating_sub(init_rem_points);
 
     if cfg!(not(feature = "gas_calibration")) {
-        metering::set_remaining_points(&mut store, &instance, limit.saturating_sub(init_cost));
+        let points = limit.saturating_sub(init_cost);
+        #[cfg(any(test, feature = "testing"))]
+        let points = force_oog_after.unwrap_or(points);
+        metering::set_remaining_points(&mut store, &instance, points);
+        let env = fenv.as_mut(&mut store);
+        env.start_points = Some(points);
+        env.clock_origin = Some(call_context.clock_origin.unwrap_or(points));
     }
+    fenv.as_mut(&mut store).initial_balance = interface.get_balance().ok();
 
+    metrics::increment_counter!("massa_sc_runtime_executions_total");
     match context.execution(&mut store, &instance, function, param) {
         Ok(mut response) => {
+            metrics::histogram!(
+                "massa_sc_runtime_gas_used",
+                limit.saturating_sub(response.remaining_gas) as f64
+            );
             let gc_result = if cfg!(feature = "gas_calibration") {
                 Some(get_gas_calibration_result(&instance, &mut store))
             } else {
                 None
             };
             response.init_gas_cost = init_cost;
+            response.gas_used = limit.saturating_sub(response.remaining_gas);
+            response.abi_gas = Some(context.env.abi_gas.load(Ordering::Relaxed));
+            if let Some(abi_timings) = &context.env.abi_timings {
+                response.abi_timings = abi_timings.lock().clone();
+            }
+            if let Some(used_abis) = &context.env.used_abis {
+                response.used_abis = used_abis.lock().clone();
+            }
+            response.events = std::mem::take(&mut fenv.as_mut(&mut store).events);
 
             #[cfg(feature = "execution-trace")]
             {
-                response.trace = _fenv.as_ref(&store).trace.clone();
+                response.trace = fenv.as_ref(&store).trace.clone();
             }
 
             Ok((response, gc_result))
         }
-        Err(err) => {
-            if cfg!(feature = "gas_calibration") {
-                exec_bail!(err, init_cost)
-            } else {
-                // Because the last needed more than the remaining points, we
-                // should have an error.
-                match metering::get_remaining_points(&mut store, &instance) {
-                    MeteringPoints::Remaining(..) => exec_bail!(err, init_cost),
-                    MeteringPoints::Exhausted => {
-                        exec_bail!(
-                            format!("Not enough gas, limit reached at: {function}"),
-                            init_cost
-                        )
-                    }
+        Err(err) => execution_error(
+            err,
+            &mut store,
+            &instance,
+            &fenv,
+            function,
+            limit,
+            init_cost,
+            verbose_traps,
+        ),
+    }
+}
+
+/// Map the error of a failed execution of `function` to the matching
+/// `VMError`, recording the trap in the metrics
+#[allow(clippy::too_many_arguments)]
+fn execution_error<T>(
+    err: anyhow::Error,
+    store: &mut Store,
+    instance: &Instance,
+    fenv: &FunctionEnv<ASEnv>,
+    function: &str,
+    limit: u64,
+    init_cost: u64,
+    verbose_traps: bool,
+) -> VMResult<T> {
+    if let Some(LaunchError::InsufficientGas {
+        required,
+        available,
+    }) = err.downcast_ref::<LaunchError>()
+    {
+        TrapKind::InsufficientLaunchGas.record();
+        return Err(VMError::InsufficientLaunchGas {
+            required: *required,
+            available: *available,
+            init_gas_cost: init_cost,
+        });
+    }
+    let trap_dump = verbose_traps.then(|| TrapDump::from_error(&err)).flatten();
+    if cfg!(feature = "gas_calibration") {
+        exec_bail!(err, init_cost, trap_dump)
+    }
+    let abort = fenv.as_ref(store).abort_info.clone();
+    // Because the last needed more than the remaining points, we
+    // should have an error.
+    let trap_kind = match metering::get_remaining_points(store, instance) {
+        MeteringPoints::Exhausted => TrapKind::OutOfGas,
+        MeteringPoints::Remaining(..) if fenv.as_ref(store).timed_out() => TrapKind::Timeout,
+        MeteringPoints::Remaining(..) => TrapKind::from_error(&err, abort.is_some()),
+    };
+    trap_kind.record();
+    match trap_kind {
+        TrapKind::OutOfGas => Err(VMError::OutOfGas {
+            function: function.to_string(),
+            limit,
+            init_gas_cost: init_cost,
+            trap_dump,
+        }),
+        TrapKind::Timeout => Err(VMError::Timeout {
+            init_gas_cost: init_cost,
+        }),
+        TrapKind::StackOverflow => Err(VMError::StackOverflow {
+            init_gas_cost: init_cost,
+        }),
+        _ => exec_bail!(err, init_cost, trap_dump, abort),
+    }
+}
+
+/// Execute the same function of an AS module several times on a single
+/// instance, with a parameter allocated only once in the instance memory.
+///
+/// Every run is given `limit` minus the instance creation cost as gas.
+///
+/// Reusing an instance breaks the isolation between runs: globals, the heap
+/// and the whole linear memory are kept from one run to the next, so a run can
+/// observe and alter what the previous ones left behind. Only use this with
+/// trusted modules that do not rely on a fresh instance.
+///
+/// With `restore_memory`, the linear memory and the mutable globals are
+/// snapshotted after the parameter allocation and restored before every run
+/// but the first one.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn exec_as_module_with_shared_param(
+    interface: &dyn Interface,
+    as_module: ASModule,
+    function: &str,
+    param: &[u8],
+    runs: usize,
+    limit: u64,
+    gas_costs: GasCosts,
+    options: RunOptions,
+    restore_memory: bool,
+) -> VMResult<Vec<Response>> {
+    let engine = match as_module.compiler {
+        Compiler::CL => init_cl_engine(limit, gas_costs.clone(), false),
+        Compiler::SP => init_sp_engine(limit, gas_costs.clone(), false),
+    };
+    let mut store = Store::new(engine);
+    let verbose_traps = options.verbose_traps;
+    let mut context = ASContext::new(interface, as_module.binary_module, gas_costs, options);
+
+    interface.save_gas_remaining_before_subexecution(limit);
+
+    let (instance, fenv, init_rem_points) = context.create_vm_instance_and_init_env(&mut store)?;
+    let init_cost = as_module.initial_limit.saturating_sub(init_rem_points);
+
+    // The allocation is paid with the gas remaining after instantiation
+    let param_ptr = match context.alloc_pinned_param(&mut store, &instance, param) {
+        Ok(param_ptr) => param_ptr,
+        Err(err) => exec_bail!(err, init_cost),
+    };
+
+    let snapshot = if restore_memory {
+        Some(InstanceSnapshot::take(&mut store, &instance)?)
+    } else {
+        None
+    };
+
+    let mut responses = Vec::with_capacity(runs);
+    for run in 0..runs {
+        if let (true, Some(snapshot)) = (run > 0, &snapshot) {
+            snapshot.restore(&mut store, &instance)?;
+            #[cfg(test)]
+            snapshot.verify_clean_state(&mut store, &instance)?;
+        }
+        // every run is a new top-level execution
+        let env = fenv.as_mut(&mut store);
+        env.reset_run();
+        if cfg!(not(feature = "gas_calibration")) {
+            let points = limit.saturating_sub(init_cost);
+            metering::set_remaining_points(&mut store, &instance, points);
+            let env = fenv.as_mut(&mut store);
+            env.start_points = Some(points);
+            env.clock_origin = Some(points);
+        }
+        fenv.as_mut(&mut store).initial_balance = interface.get_balance().ok();
+        match context.execution_with_param_ptr(&mut store, &instance, function, param_ptr) {
+            Ok(mut response) => {
+                response.init_gas_cost = init_cost;
+                response.gas_used = limit.saturating_sub(response.remaining_gas);
+                response.abi_gas = Some(context.env.abi_gas.load(Ordering::Relaxed));
+                if let Some(abi_timings) = &context.env.abi_timings {
+                    response.abi_timings = abi_timings.lock().clone();
+                }
+                if let Some(used_abis) = &context.env.used_abis {
+                    response.used_abis = used_abis.lock().clone();
                 }
+                response.events = std::mem::take(&mut fenv.as_mut(&mut store).events);
+                responses.push(response);
+            }
+            Err(err) => {
+                return execution_error(
+                    err,
+                    &mut store,
+                    &instance,
+                    &fenv,
+                    function,
+                    limit,
+                    init_cost,
+                    verbose_traps,
+                )
+            }
+        }
+    }
+    Ok(responses)
+}
+
+/// Copy of the linear memory and of the mutable globals of an instance
+///
+/// The globals a module does not export itself are reached through the
+/// exports added by the `GlobalExporter` middleware.
+pub(crate) struct InstanceSnapshot {
+    memory: Vec<u8>,
+    globals: Vec<(Global, Value)>,
+}
+
+impl InstanceSnapshot {
+    pub(crate) fn take(store: &mut Store, instance: &Instance) -> Result<Self> {
+        if instance
+            .exports
+            .get_global(GLOBALS_EXPORTED_MARKER)
+            .is_err()
+        {
+            bail!(
+                "module compiled without its globals exported, it cannot be snapshotted: \
+                 compile it with RuntimeModule::new_for_snapshot"
+            );
+        }
+        let memory = instance
+            .exports
+            .get_memory("memory")?
+            .view(store)
+            .copy_to_vec()?;
+        let mut globals = Vec::new();
+        for (_, global) in instance.exports.iter().globals() {
+            if global.ty(store).mutability == Mutability::Var {
+                globals.push((global.clone(), global.get(store)));
+            }
+        }
+        Ok(Self { memory, globals })
+    }
+
+    /// Write the snapshot back, zeroing the pages grown since it was taken
+    pub(crate) fn restore(&self, store: &mut Store, instance: &Instance) -> Result<()> {
+        let view = instance.exports.get_memory("memory")?.view(store);
+        view.write(0, &self.memory)?;
+        let grown = (view.data_size() as usize).saturating_sub(self.memory.len());
+        if grown > 0 {
+            view.write(self.memory.len() as u64, &vec![0; grown])?;
+        }
+        for (global, value) in &self.globals {
+            global.set(store, value.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Check that the instance is back to the snapshot: same globals, same
+    /// memory, the pages grown since it was taken being all zeroes. Nothing
+    /// written by a previous run must leak into the next one.
+    pub(crate) fn verify_clean_state(&self, store: &mut Store, instance: &Instance) -> Result<()> {
+        let memory = instance
+            .exports
+            .get_memory("memory")?
+            .view(store)
+            .copy_to_vec()?;
+        let (restored, grown) = memory.split_at(self.memory.len().min(memory.len()));
+        if restored != self.memory.as_slice() || grown.iter().any(|byte| *byte != 0) {
+            bail!("memory was not restored to the snapshot");
+        }
+        for (global, value) in &self.globals {
+            if global.get(store) != *value {
+                bail!("globals were not restored to the snapshot");
             }
         }
+        Ok(())
     }
 }
@@ -0,0 +1,336 @@
+//! Base of the interface wrappers.
+//!
+//! An [`InterfaceDelegate`] implements [`Interface`] by routing every method
+//! through [`InterfaceDelegate::delegate`], which receives the method name and
+//! the call to make on the wrapped interface. A wrapper implements `delegate`
+//! to forward, record or replay the calls, and only overrides the methods it
+//! handles itself.
+//!
+//! The responses of the interface are converted to a serde value with
+//! [`Recordable`], which covers the return types of every method.
+
+use crate::execution::RuntimeModule;
+use crate::types::{CounterOverflow, Interface, InterfaceClone};
+use anyhow::{bail, Result};
+use massa_proto_rs::massa::model::v1::{
+    AddressCategory, ComparisonResult, NativeAmount, NativeTime, Slot,
+};
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+/// Response of an interface method that can be recorded
+pub trait Recordable: Sized {
+    fn to_record(&self) -> Value;
+    fn from_record(record: Value) -> Result<Self>;
+}
+
+macro_rules! recordable_serde {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl Recordable for $ty {
+                fn to_record(&self) -> Value {
+                    serde_json::to_value(self).unwrap_or_default()
+                }
+
+                fn from_record(record: Value) -> Result<Self> {
+                    Ok(serde_json::from_value(record)?)
+                }
+            }
+        )*
+    };
+}
+
+recordable_serde!(
+    (),
+    bool,
+    u8,
+    u16,
+    u32,
+    u64,
+    i64,
+    f64,
+    String,
+    [u8; 32],
+    Vec<u8>,
+    Vec<Vec<u8>>,
+    Vec<String>,
+    Vec<(u64, u8)>,
+    Vec<Option<Vec<u8>>>,
+    Option<u32>,
+    Option<u64>,
+    Option<String>,
+    Option<Vec<u8>>,
+    BTreeSet<String>,
+    BTreeSet<Vec<u8>>,
+);
+
+impl<A: Recordable, B: Recordable> Recordable for (A, B) {
+    fn to_record(&self) -> Value {
+        Value::Array(vec![self.0.to_record(), self.1.to_record()])
+    }
+
+    fn from_record(record: Value) -> Result<Self> {
+        let (a, b): (Value, Value) = serde_json::from_value(record)?;
+        Ok((A::from_record(a)?, B::from_record(b)?))
+    }
+}
+
+impl Recordable for Slot {
+    fn to_record(&self) -> Value {
+        (self.period, self.thread).to_record()
+    }
+
+    fn from_record(record: Value) -> Result<Self> {
+        let (period, thread) = Recordable::from_record(record)?;
+        Ok(Slot { period, thread })
+    }
+}
+
+impl Recordable for NativeAmount {
+    fn to_record(&self) -> Value {
+        (self.mantissa, self.scale).to_record()
+    }
+
+    fn from_record(record: Value) -> Result<Self> {
+        let (mantissa, scale) = Recordable::from_record(record)?;
+        Ok(NativeAmount { mantissa, scale })
+    }
+}
+
+impl Recordable for NativeTime {
+    fn to_record(&self) -> Value {
+        self.milliseconds.to_record()
+    }
+
+    fn from_record(record: Value) -> Result<Self> {
+        Ok(NativeTime {
+            milliseconds: Recordable::from_record(record)?,
+        })
+    }
+}
+
+impl Recordable for AddressCategory {
+    fn to_record(&self) -> Value {
+        Value::from(*self as i32)
+    }
+
+    fn from_record(record: Value) -> Result<Self> {
+        Ok(Self::try_from(serde_json::from_value::<i32>(record)?)?)
+    }
+}
+
+impl Recordable for ComparisonResult {
+    fn to_record(&self) -> Value {
+        Value::from(*self as i32)
+    }
+
+    fn from_record(record: Value) -> Result<Self> {
+        Ok(Self::try_from(serde_json::from_value::<i32>(record)?)?)
+    }
+}
+
+/// Compiled modules are not recorded, they are compiled again from the
+/// bytecode given to `get_module` and `get_tmp_module`
+impl Recordable for RuntimeModule {
+    fn to_record(&self) -> Value {
+        Value::Null
+    }
+
+    fn from_record(_record: Value) -> Result<Self> {
+        bail!("compiled modules cannot be read from a record")
+    }
+}
+
+/// Declare the delegated methods of `InterfaceDelegate` and implement
+/// `Interface` with them
+macro_rules! delegate_interface {
+    ($($(#[$attr:meta])* fn $method:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty;)*) => {
+        /// Interface wrapper, see the module documentation
+        pub trait InterfaceDelegate: Clone + Send + Sync + 'static {
+            /// Make the `call` to the wrapped interface on behalf of `method`
+            fn delegate<T: Recordable>(
+                &self,
+                method: &'static str,
+                call: impl FnOnce(&dyn Interface) -> Result<T>,
+            ) -> Result<T>;
+
+            $(
+                $(#[$attr])*
+                fn $method(&self $(, $arg: $ty)*) -> $ret {
+                    self.delegate(stringify!($method), |interface| {
+                        Interface::$method(interface $(, $arg)*)
+                    })
+                }
+            )*
+
+            /// A delegation failure, i.e. a diverging replay, gives an empty
+            /// string and is reported by the following call
+            fn bytes_to_base58_check_wasmv1(&self, bytes: &[u8]) -> String {
+                self.delegate("bytes_to_base58_check_wasmv1", |interface| {
+                    Ok(interface.bytes_to_base58_check_wasmv1(bytes))
+                })
+                .unwrap_or_default()
+            }
+
+            fn save_gas_remaining_before_subexecution(&self, gas_used_until: u64) {
+                let _ = self.delegate("save_gas_remaining_before_subexecution", |interface| {
+                    interface.save_gas_remaining_before_subexecution(gas_used_until);
+                    Ok(())
+                });
+            }
+        }
+
+        impl<D: InterfaceDelegate> Interface for D {
+            $(
+                $(#[$attr])*
+                fn $method(&self $(, $arg: $ty)*) -> $ret {
+                    InterfaceDelegate::$method(self $(, $arg)*)
+                }
+            )*
+
+            fn bytes_to_base58_check_wasmv1(&self, bytes: &[u8]) -> String {
+                InterfaceDelegate::bytes_to_base58_check_wasmv1(self, bytes)
+            }
+
+            fn save_gas_remaining_before_subexecution(&self, gas_used_until: u64) {
+                InterfaceDelegate::save_gas_remaining_before_subexecution(self, gas_used_until)
+            }
+        }
+    };
+}
+
+impl<D: InterfaceDelegate> InterfaceClone for D {
+    fn clone_box(&self) -> Box<dyn Interface> {
+        Box::new(self.clone())
+    }
+}
+
+delegate_interface! {
+    fn init_call(&self, address: &str, raw_coins: u64) -> Result<Vec<u8>>;
+    fn init_call_wasmv1(&self, address: &str, raw_coins: NativeAmount) -> Result<Vec<u8>>;
+    fn finish_call(&self) -> Result<()>;
+    fn get_balance(&self) -> Result<u64>;
+    fn get_balance_for(&self, address: &str) -> Result<u64>;
+    fn get_remaining_transferable(&self) -> Result<u64>;
+    fn get_balance_wasmv1(&self, address: Option<String>) -> Result<NativeAmount>;
+    fn transfer_coins(&self, to_address: &str, raw_amount: u64) -> Result<()>;
+    fn transfer_coins_for(&self, from_address: &str, to_address: &str, raw_amount: u64) -> Result<()>;
+    fn transfer_coins_bootstrap(&self, from_address: Option<&str>, to_address: &str, raw_amount: u64) -> Result<()>;
+    fn transfer_coins_wasmv1(&self, to_address: String, raw_amount: NativeAmount, from_address: Option<String>) -> Result<()>;
+    fn get_call_coins(&self) -> Result<u64>;
+    fn get_call_coins_wasmv1(&self) -> Result<NativeAmount>;
+    fn get_message_data(&self) -> Result<Vec<u8>>;
+    fn get_op_deposit(&self) -> Result<u64>;
+    fn get_priority_tier(&self) -> Result<u8>;
+    fn raw_set_bytecode(&self, bytecode: &[u8]) -> Result<()>;
+    fn raw_set_bytecode_for(&self, address: &str, bytecode: &[u8]) -> Result<()>;
+    fn set_bytecode_wasmv1(&self, bytecode: &[u8], address: Option<String>) -> Result<()>;
+    fn create_module(&self, module: &[u8]) -> Result<String>;
+    fn print(&self, message: &str) -> Result<()>;
+    fn get_keys(&self, prefix: Option<&[u8]>) -> Result<BTreeSet<Vec<u8>>>;
+    fn get_keys_for(&self, address: &str, prefix: Option<&[u8]>) -> Result<BTreeSet<Vec<u8>>>;
+    fn get_keys_prefix(&self, prefix: &[u8], max: usize) -> Result<BTreeSet<Vec<u8>>>;
+    fn datastore_diff(&self, address_a: &str, address_b: &str, prefix: Option<&[u8]>) -> Result<(BTreeSet<Vec<u8>>, u64)>;
+    fn get_ds_keys_wasmv1(&self, prefix: &[u8], address: Option<String>) -> Result<BTreeSet<Vec<u8>>>;
+    fn raw_get_data(&self, key: &[u8]) -> Result<Vec<u8>>;
+    fn get_data_multi(&self, keys: &[Vec<u8>]) -> Result<Vec<Option<Vec<u8>>>>;
+    fn get_data_at_slot(&self, key: &[u8], slot: (u64, u8)) -> Result<Option<Vec<u8>>>;
+    fn raw_get_data_for(&self, address: &str, key: &[u8]) -> Result<Vec<u8>>;
+    fn get_ds_value_wasmv1(&self, key: &[u8], address: Option<String>) -> Result<Vec<u8>>;
+    fn raw_set_data(&self, key: &[u8], value: &[u8]) -> Result<()>;
+    fn map_key(&self, address: Option<&str>, key: &[u8]) -> Result<Vec<u8>>;
+    fn set_data_if_absent(&self, key: &[u8], value: &[u8]) -> Result<bool>;
+    fn swap_data(&self, key_a: &[u8], key_b: &[u8]) -> Result<()>;
+    fn rate_limit(&self, key: &[u8], max_per_period: u64, period: u64) -> Result<bool>;
+    fn add_to_data(&self, key: &[u8], delta: i64, overflow: CounterOverflow) -> Result<u64>;
+    fn savepoint(&self) -> Result<Option<u64>>;
+    fn rollback_to(&self, id: u64) -> Result<()>;
+    fn raw_set_data_for(&self, address: &str, key: &[u8], value: &[u8]) -> Result<()>;
+    fn set_ds_value_wasmv1(&self, key: &[u8], value: &[u8], address: Option<String>) -> Result<()>;
+    fn raw_append_data(&self, key: &[u8], value: &[u8]) -> Result<()>;
+    fn raw_append_data_for(&self, address: &str, key: &[u8], value: &[u8]) -> Result<()>;
+    fn append_ds_value_wasmv1(&self, key: &[u8], value: &[u8], address: Option<String>) -> Result<()>;
+    fn raw_delete_data(&self, key: &[u8]) -> Result<()>;
+    fn raw_delete_data_for(&self, address: &str, key: &[u8]) -> Result<()>;
+    fn delete_ds_entry_wasmv1(&self, key: &[u8], address: Option<String>) -> Result<()>;
+    fn has_data(&self, key: &[u8]) -> Result<bool>;
+    fn has_data_for(&self, address: &str, key: &[u8]) -> Result<bool>;
+    fn ds_entry_exists_wasmv1(&self, key: &[u8], address: Option<String>) -> Result<bool>;
+    fn raw_get_bytecode(&self) -> Result<Vec<u8>>;
+    fn raw_get_bytecode_for(&self, address: &str) -> Result<Vec<u8>>;
+    fn get_bytecode_wasmv1(&self, address: Option<String>) -> Result<Vec<u8>>;
+    fn get_op_keys(&self, prefix: Option<&[u8]>) -> Result<Vec<Vec<u8>>>;
+    fn get_op_keys_wasmv1(&self, prefix: &[u8]) -> Result<Vec<Vec<u8>>>;
+    fn op_entry_exists(&self, key: &[u8]) -> Result<bool>;
+    fn get_op_data(&self, key: &[u8]) -> Result<Vec<u8>>;
+    fn get_op_data_multi(&self, keys: &[Vec<u8>]) -> Result<Vec<Option<Vec<u8>>>>;
+    fn caller_has_write_access(&self) -> Result<bool>;
+    fn hash(&self, data: &[u8]) -> Result<[u8; 32]>;
+    fn hash_blake3(&self, bytes: &[u8]) -> Result<[u8; 32]>;
+    fn signature_verify(&self, data: &[u8], signature: &str, public_key: &str) -> Result<bool>;
+    fn verify_threshold(&self, data: &[u8], signatures: &[String], public_keys: &[String], threshold: u32) -> Result<bool>;
+    fn verify_state_inclusion(&self, address: &str, key: &[u8], value: &[u8], proof: &[u8]) -> Result<bool>;
+    fn evm_signature_verify(&self, message: &[u8], signature: &[u8], public_key: &[u8]) -> Result<bool>;
+    fn evm_get_address_from_pubkey(&self, public_key: &[u8]) -> Result<Vec<u8>>;
+    fn evm_get_pubkey_from_signature(&self, hash: &[u8], signature: &[u8]) -> Result<Vec<u8>>;
+    fn is_address_eoa(&self, address: &str) -> Result<bool>;
+    fn address_from_public_key(&self, public_key: &str) -> Result<String>;
+    fn resolve_name(&self, name: &str) -> Result<Option<String>>;
+    fn validate_address(&self, address: &str) -> Result<bool>;
+    fn get_time(&self) -> Result<u64>;
+    fn unsafe_random(&self) -> Result<i64>;
+    fn unsafe_random_f64(&self) -> Result<f64>;
+    fn unsafe_random_wasmv1(&self, num_bytes: u64) -> Result<Vec<u8>>;
+    fn get_current_period(&self) -> Result<u64>;
+    fn get_current_thread(&self) -> Result<u8>;
+    fn get_current_slot(&self) -> Result<Slot>;
+    fn random_from_seed(&self, seed: &[u8]) -> Result<i64>;
+    fn get_scheduled_slots(&self, address: &str) -> Result<Vec<(u64, u8)>>;
+    fn get_deferred_coins(&self, address: &str) -> Result<u64>;
+    fn get_writers(&self, address: &str) -> Result<BTreeSet<String>>;
+    fn get_min_balance(&self) -> Result<u64>;
+    fn get_gas_price(&self) -> Result<u64>;
+    fn get_interface_version(&self, address: &str) -> Result<Option<u32>>;
+    fn generate_error_event(&self, code: u32, message: String) -> Result<()>;
+    fn get_block_producer(&self) -> Result<String>;
+    fn get_storage_bytes(&self, address: &str) -> Result<u64>;
+    fn get_owned_addresses(&self) -> Result<Vec<String>>;
+    fn get_call_stack(&self) -> Result<Vec<String>>;
+    fn generate_event(&self, event: String) -> Result<()>;
+    fn generate_event_wasmv1(&self, event: Vec<u8>) -> Result<()>;
+    fn get_module(&self, bytecode: &[u8], gas_limit: u64) -> Result<RuntimeModule>;
+    fn get_tmp_module(&self, bytecode: &[u8], gas_limit: u64) -> Result<RuntimeModule>;
+    #[allow(clippy::too_many_arguments)]
+    fn send_message(&self, target_address: &str, target_handler: &str, validity_start: (u64, u8), validity_end: (u64, u8), max_gas: u64, raw_fee: u64, raw_coins: u64, data: &[u8], filter: Option<(&str, Option<&[u8]>)>) -> Result<()>;
+    fn get_origin_operation_id(&self) -> Result<Option<String>>;
+    fn hash_sha256(&self, bytes: &[u8]) -> Result<[u8; 32]>;
+    fn hash_keccak256(&self, bytes: &[u8]) -> Result<[u8; 32]>;
+    fn hash_sha3_256(&self, bytes: &[u8]) -> Result<[u8; 32]>;
+    fn chain_id(&self) -> Result<u64>;
+    fn get_fee_split(&self) -> Result<u16>;
+    fn native_amount_from_str_wasmv1(&self, amount: &str) -> Result<NativeAmount>;
+    fn native_amount_to_string_wasmv1(&self, amount: &NativeAmount) -> Result<String>;
+    fn check_native_amount_wasmv1(&self, amount: &NativeAmount) -> Result<bool>;
+    fn add_native_amount_wasmv1(&self, amount1: &NativeAmount, amount2: &NativeAmount) -> Result<NativeAmount>;
+    fn sub_native_amount_wasmv1(&self, amount1: &NativeAmount, amount2: &NativeAmount) -> Result<NativeAmount>;
+    fn scalar_mul_native_amount_wasmv1(&self, amount: &NativeAmount, factor: u64) -> Result<NativeAmount>;
+    fn scalar_div_rem_native_amount_wasmv1(&self, dividend: &NativeAmount, divisor: u64) -> Result<(NativeAmount, NativeAmount)>;
+    fn div_rem_native_amount_wasmv1(&self, dividend: &NativeAmount, divisor: &NativeAmount) -> Result<(u64, NativeAmount)>;
+    fn check_address_wasmv1(&self, to_check: &str) -> Result<bool>;
+    fn check_pubkey_wasmv1(&self, to_check: &str) -> Result<bool>;
+    fn check_signature_wasmv1(&self, to_check: &str) -> Result<bool>;
+    fn get_address_category_wasmv1(&self, to_check: &str) -> Result<AddressCategory>;
+    fn get_address_version_wasmv1(&self, address: &str) -> Result<u64>;
+    fn get_pubkey_version_wasmv1(&self, pubkey: &str) -> Result<u64>;
+    fn get_signature_version_wasmv1(&self, signature: &str) -> Result<u64>;
+    fn checked_add_native_time_wasmv1(&self, time1: &NativeTime, time2: &NativeTime) -> Result<NativeTime>;
+    fn checked_sub_native_time_wasmv1(&self, time1: &NativeTime, time2: &NativeTime) -> Result<NativeTime>;
+    fn checked_mul_native_time_wasmv1(&self, time: &NativeTime, factor: u64) -> Result<NativeTime>;
+    fn checked_scalar_div_native_time_wasmv1(&self, dividend: &NativeTime, divisor: u64) -> Result<(NativeTime, NativeTime)>;
+    fn checked_div_native_time_wasmv1(&self, dividend: &NativeTime, divisor: &NativeTime) -> Result<(u64, NativeTime)>;
+    fn base58_check_to_bytes_wasmv1(&self, s: &str) -> Result<Vec<u8>>;
+    fn compare_address_wasmv1(&self, left: &str, right: &str) -> Result<ComparisonResult>;
+    fn compare_native_amount_wasmv1(&self, left: &NativeAmount, right: &NativeAmount) -> Result<ComparisonResult>;
+    fn compare_native_time_wasmv1(&self, left: &NativeTime, right: &NativeTime) -> Result<ComparisonResult>;
+    fn compare_pub_key_wasmv1(&self, left: &str, right: &str) -> Result<ComparisonResult>;
+}
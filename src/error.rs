@@ -1,4 +1,6 @@
 use displaydoc::Display;
+#[cfg(feature = "trap-metrics")]
+use std::sync::atomic::{AtomicU64, Ordering};
 use thiserror::Error;
 
 pub type VMResult<T> = Result<T, VMError>;
@@ -7,8 +9,50 @@ pub type VMResult<T> = Result<T, VMError>;
 pub enum VMError {
     /// VM instance error: {0}
     InstanceError(String),
+    /// Module compilation error: {0}
+    CompilationError(String),
     /// VM execution error: {error}
-    ExecutionError { error: String, init_gas_cost: u64 },
+    ExecutionError {
+        error: String,
+        init_gas_cost: u64,
+        /// Frame information captured on a trap, only with
+        /// `RunOptions::verbose_traps`
+        trap_dump: Option<TrapDump>,
+        /// Arguments of the AssemblyScript `abort` call that ended the
+        /// execution, if it did
+        abort: Option<AbortInfo>,
+    },
+    /// Not enough gas, limit reached at: {function}
+    OutOfGas {
+        /// Called function
+        function: String,
+        /// Gas limit of the execution
+        limit: u64,
+        init_gas_cost: u64,
+        /// Frame information captured on the trap, only with
+        /// `RunOptions::verbose_traps`
+        trap_dump: Option<TrapDump>,
+    },
+    /// VM execution error: execution time limit exceeded
+    Timeout { init_gas_cost: u64 },
+    /// VM execution error: stack overflow
+    StackOverflow { init_gas_cost: u64 },
+    /// Not enough gas to launch the virtual machine: {required} required, {available} available
+    InsufficientLaunchGas {
+        required: u64,
+        available: u64,
+        init_gas_cost: u64,
+    },
+}
+
+/// Arguments of an AssemblyScript `abort` call
+#[derive(Clone, Debug, Display, PartialEq)]
+/// Contract aborted: {message} at {filename}:{line}:{column}
+pub struct AbortInfo {
+    pub message: String,
+    pub filename: String,
+    pub line: i32,
+    pub column: i32,
 }
 
 impl From<anyhow::Error> for VMError {
@@ -17,11 +61,202 @@ impl From<anyhow::Error> for VMError {
     }
 }
 
+/// Frame information captured when an execution traps
+///
+/// Only what wasmer records in its trap backtraces is available: for each wasm
+/// frame, the function index, its name if the module has a name section, and
+/// the offset of the trapping or calling instruction. Both Cranelift and
+/// Singlepass emit the address maps these come from, offsets pointing to the
+/// closest mapped instruction. Locals and value stack contents are not
+/// available with either compiler: they live in machine registers and native
+/// stack slots that wasmer does not map back to wasm.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrapDump {
+    /// Wasm frames, innermost first
+    pub frames: Vec<TrapFrame>,
+}
+
+/// A wasm frame of a [`TrapDump`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrapFrame {
+    /// Index of the function in the module, imports included
+    pub func_index: u32,
+    /// Function name from the module name section
+    pub function_name: Option<String>,
+    /// Instruction offset relative to the function body start
+    pub func_offset: usize,
+    /// Instruction offset relative to the module start
+    pub module_offset: usize,
+}
+
+impl TrapDump {
+    /// Build a dump from an execution error, if it is a wasm trap
+    pub(crate) fn from_error(error: &anyhow::Error) -> Option<Self> {
+        let trap = error.downcast_ref::<wasmer::RuntimeError>()?;
+        Some(Self {
+            frames: trap
+                .trace()
+                .iter()
+                .map(|frame| TrapFrame {
+                    func_index: frame.func_index(),
+                    function_name: frame.function_name().map(str::to_string),
+                    func_offset: frame.func_offset(),
+                    module_offset: frame.module_offset(),
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Kind of trap ending a failed execution
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TrapKind {
+    /// The gas limit was reached
+    OutOfGas,
+    /// The gas limit did not cover the instantiation of the module
+    InsufficientLaunchGas,
+    /// The execution time limit was exceeded
+    Timeout,
+    /// Out of bounds or misaligned memory or table access
+    MemoryAccess,
+    /// An `unreachable` instruction was executed
+    Unreachable,
+    /// The native call stack was exhausted, typically by a deep recursion
+    StackOverflow,
+    /// The contract called the AssemblyScript `abort` function
+    Abort,
+    /// Any other trap or host function error
+    Other,
+}
+
+impl TrapKind {
+    /// Classify the error of an execution that did not run out of gas
+    pub(crate) fn from_error(error: &anyhow::Error, aborted: bool) -> Self {
+        use wasmer::TrapCode;
+        if aborted {
+            return Self::Abort;
+        }
+        match error
+            .downcast_ref::<wasmer::RuntimeError>()
+            .and_then(|trap| trap.clone().to_trap())
+        {
+            Some(
+                TrapCode::HeapAccessOutOfBounds
+                | TrapCode::HeapMisaligned
+                | TrapCode::TableAccessOutOfBounds,
+            ) => Self::MemoryAccess,
+            Some(TrapCode::UnreachableCodeReached) => Self::Unreachable,
+            Some(TrapCode::StackOverflow) => Self::StackOverflow,
+            _ => Self::Other,
+        }
+    }
+
+    /// Count a failed execution in the `massa_sc_runtime_traps_total` metric
+    /// and, with the `trap-metrics` feature, in `TRAP_STATS`
+    pub(crate) fn record(self) {
+        let label = match self {
+            Self::OutOfGas => "out_of_gas",
+            Self::InsufficientLaunchGas => "insufficient_launch_gas",
+            Self::Timeout => "timeout",
+            _ => "error",
+        };
+        metrics::increment_counter!("massa_sc_runtime_traps_total", "type" => label);
+        #[cfg(feature = "trap-metrics")]
+        TRAP_STATS.record(self);
+    }
+}
+
+/// Number of failed AssemblyScript executions per [`TrapKind`], accumulated
+/// over all the executions of the process. Meant as a fleet health signal for
+/// node operators, see [`TRAP_STATS`].
+#[cfg(feature = "trap-metrics")]
+#[derive(Debug, Default)]
+pub struct TrapStats {
+    out_of_gas: AtomicU64,
+    insufficient_launch_gas: AtomicU64,
+    timeout: AtomicU64,
+    memory_access: AtomicU64,
+    unreachable: AtomicU64,
+    stack_overflow: AtomicU64,
+    abort: AtomicU64,
+    other: AtomicU64,
+}
+
+/// Point in time copy of the [`TrapStats`] counters
+#[cfg(feature = "trap-metrics")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TrapStatsSnapshot {
+    pub out_of_gas: u64,
+    pub insufficient_launch_gas: u64,
+    pub timeout: u64,
+    pub memory_access: u64,
+    pub unreachable: u64,
+    pub stack_overflow: u64,
+    pub abort: u64,
+    pub other: u64,
+}
+
+#[cfg(feature = "trap-metrics")]
+impl TrapStats {
+    const fn new() -> Self {
+        Self {
+            out_of_gas: AtomicU64::new(0),
+            insufficient_launch_gas: AtomicU64::new(0),
+            timeout: AtomicU64::new(0),
+            memory_access: AtomicU64::new(0),
+            unreachable: AtomicU64::new(0),
+            stack_overflow: AtomicU64::new(0),
+            abort: AtomicU64::new(0),
+            other: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn record(&self, kind: TrapKind) {
+        let counter = match kind {
+            TrapKind::OutOfGas => &self.out_of_gas,
+            TrapKind::InsufficientLaunchGas => &self.insufficient_launch_gas,
+            TrapKind::Timeout => &self.timeout,
+            TrapKind::MemoryAccess => &self.memory_access,
+            TrapKind::Unreachable => &self.unreachable,
+            TrapKind::StackOverflow => &self.stack_overflow,
+            TrapKind::Abort => &self.abort,
+            TrapKind::Other => &self.other,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Read the current value of the counters
+    pub fn snapshot(&self) -> TrapStatsSnapshot {
+        TrapStatsSnapshot {
+            out_of_gas: self.out_of_gas.load(Ordering::Relaxed),
+            insufficient_launch_gas: self.insufficient_launch_gas.load(Ordering::Relaxed),
+            timeout: self.timeout.load(Ordering::Relaxed),
+            memory_access: self.memory_access.load(Ordering::Relaxed),
+            unreachable: self.unreachable.load(Ordering::Relaxed),
+            stack_overflow: self.stack_overflow.load(Ordering::Relaxed),
+            abort: self.abort.load(Ordering::Relaxed),
+            other: self.other.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Trap statistics of the process
+#[cfg(feature = "trap-metrics")]
+pub static TRAP_STATS: TrapStats = TrapStats::new();
+
 macro_rules! exec_bail {
     ($err:expr, $init_gas_cost:expr) => {
+        crate::error::exec_bail!($err, $init_gas_cost, None)
+    };
+    ($err:expr, $init_gas_cost:expr, $trap_dump:expr) => {
+        crate::error::exec_bail!($err, $init_gas_cost, $trap_dump, None)
+    };
+    ($err:expr, $init_gas_cost:expr, $trap_dump:expr, $abort:expr) => {
         return Err(crate::VMError::ExecutionError {
             error: $err.to_string(),
             init_gas_cost: $init_gas_cost,
+            trap_dump: $trap_dump,
+            abort: $abort,
         })
     };
 }
@@ -0,0 +1,511 @@
+//! Gas estimation of an execution without committing its state changes.
+//!
+//! [`run_estimate`] runs a function through a [`BufferingInterface`], which
+//! wraps the node interface and keeps the changes requested by the contract
+//! instead of applying them:
+//! * datastore writes, appends and deletions go to an overlay, read back by
+//!   the datastore getters so that the execution sees its own writes
+//! * coin transfers, including the coins attached to a call to another
+//!   contract, bytecode updates, smart contract creations and sent messages
+//!   are only recorded
+//!
+//! Every change is listed in order as a [`PlannedEffect`]. All the other
+//! methods are forwarded to the wrapped interface. In particular, the balances
+//! do not reflect the recorded transfers, and the calls to other contracts go
+//! through `init_call` of the wrapped interface with no coins, so the called
+//! contract sees no call coins. The address returned for a created smart
+//! contract is a placeholder unknown to the wrapped interface.
+
+use crate::delegate::{InterfaceDelegate, Recordable};
+use crate::error::VMResult;
+use crate::execution::{run_function, RuntimeModule};
+use crate::types::{
+    default_add_to_data, default_datastore_diff, default_get_data_multi, default_get_keys_prefix,
+    default_rate_limit, default_set_data_if_absent, default_swap_data, CounterOverflow, Interface,
+    Response,
+};
+use crate::GasCosts;
+use anyhow::{anyhow, bail, Result};
+use massa_proto_rs::massa::model::v1::NativeAmount;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+/// State change requested by an execution, see [`BufferingInterface`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlannedEffect {
+    /// Datastore entry set
+    SetData {
+        address: String,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    /// Bytes appended to a datastore entry
+    AppendData {
+        address: String,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    /// Datastore entry deleted
+    DeleteData { address: String, key: Vec<u8> },
+    /// Coins transferred by an AssemblyScript contract, or attached to its
+    /// call to another contract
+    Transfer {
+        from_address: String,
+        to_address: String,
+        raw_amount: u64,
+    },
+    /// Coins transferred by a WasmV1 contract, or attached to its call to
+    /// another contract
+    TransferWasmV1 {
+        from_address: String,
+        to_address: String,
+        amount: NativeAmount,
+    },
+    /// Bytecode of an address replaced
+    SetBytecode { address: String, bytecode: Vec<u8> },
+    /// Smart contract created at a placeholder address
+    CreateSc { address: String, bytecode: Vec<u8> },
+    /// Asynchronous message sent
+    SendMessage {
+        target_address: String,
+        target_handler: String,
+        validity_start: (u64, u8),
+        validity_end: (u64, u8),
+        max_gas: u64,
+        raw_fee: u64,
+        raw_coins: u64,
+        data: Vec<u8>,
+        filter: Option<(String, Option<Vec<u8>>)>,
+    },
+}
+
+/// Result of [`run_estimate`]
+#[derive(Debug)]
+pub struct Estimate {
+    /// Response of the execution, `gas_used` being the estimated gas
+    pub response: Response,
+    /// Changes the execution would have applied, in order
+    pub effects: Vec<PlannedEffect>,
+}
+
+#[derive(Default)]
+struct BufferState {
+    /// Datastore entries written (`Some`) or deleted (`None`), per address
+    overlay: HashMap<String, BTreeMap<Vec<u8>, Option<Vec<u8>>>>,
+    effects: Vec<PlannedEffect>,
+    created: u64,
+}
+
+/// Interface wrapper buffering the state changes of an execution, see the
+/// module documentation
+#[derive(Clone)]
+pub struct BufferingInterface {
+    inner: Box<dyn Interface>,
+    state: Arc<Mutex<BufferState>>,
+}
+
+impl BufferingInterface {
+    /// Wrap `interface`, with no buffered change
+    pub fn new(interface: &dyn Interface) -> Self {
+        Self {
+            inner: interface.clone_box(),
+            state: Default::default(),
+        }
+    }
+
+    /// Changes requested so far, in order
+    pub fn effects(&self) -> Vec<PlannedEffect> {
+        self.state.lock().effects.clone()
+    }
+
+    fn current_address(&self) -> Result<String> {
+        self.inner
+            .get_call_stack()?
+            .last()
+            .cloned()
+            .ok_or_else(|| anyhow!("empty call stack"))
+    }
+
+    fn address_or_current(&self, address: Option<String>) -> Result<String> {
+        match address {
+            Some(address) => Ok(address),
+            None => self.current_address(),
+        }
+    }
+
+    fn record(&self, effect: PlannedEffect) {
+        self.state.lock().effects.push(effect);
+    }
+
+    /// Buffered value of an entry: `Some(None)` if deleted, `None` if untouched
+    fn buffered(&self, address: &str, key: &[u8]) -> Option<Option<Vec<u8>>> {
+        self.state
+            .lock()
+            .overlay
+            .get(address)
+            .and_then(|entries| entries.get(key).cloned())
+    }
+
+    fn get_data(&self, address: &str, key: &[u8]) -> Result<Vec<u8>> {
+        match self.buffered(address, key) {
+            Some(Some(value)) => Ok(value),
+            Some(None) => bail!("data entry not found"),
+            None => self.inner.raw_get_data_for(address, key),
+        }
+    }
+
+    fn data_exists(&self, address: &str, key: &[u8]) -> Result<bool> {
+        match self.buffered(address, key) {
+            Some(value) => Ok(value.is_some()),
+            None => self.inner.has_data_for(address, key),
+        }
+    }
+
+    fn keys(&self, address: &str, prefix: Option<&[u8]>) -> Result<BTreeSet<Vec<u8>>> {
+        let mut keys = self.inner.get_keys_for(address, prefix)?;
+        if let Some(entries) = self.state.lock().overlay.get(address) {
+            let prefix = prefix.unwrap_or_default();
+            for (key, value) in entries.iter().filter(|(key, _)| key.starts_with(prefix)) {
+                match value {
+                    Some(_) => keys.insert(key.clone()),
+                    None => keys.remove(key),
+                };
+            }
+        }
+        Ok(keys)
+    }
+
+    fn set_data(&self, address: String, key: &[u8], value: &[u8]) {
+        let mut state = self.state.lock();
+        state
+            .overlay
+            .entry(address.clone())
+            .or_default()
+            .insert(key.to_vec(), Some(value.to_vec()));
+        state.effects.push(PlannedEffect::SetData {
+            address,
+            key: key.to_vec(),
+            value: value.to_vec(),
+        });
+    }
+
+    fn append_data(&self, address: String, key: &[u8], value: &[u8]) -> Result<()> {
+        let mut new_value = self.get_data(&address, key)?;
+        new_value.extend_from_slice(value);
+        let mut state = self.state.lock();
+        state
+            .overlay
+            .entry(address.clone())
+            .or_default()
+            .insert(key.to_vec(), Some(new_value));
+        state.effects.push(PlannedEffect::AppendData {
+            address,
+            key: key.to_vec(),
+            value: value.to_vec(),
+        });
+        Ok(())
+    }
+
+    fn delete_data(&self, address: String, key: &[u8]) -> Result<()> {
+        if !self.data_exists(&address, key)? {
+            bail!("data entry not found");
+        }
+        let mut state = self.state.lock();
+        state
+            .overlay
+            .entry(address.clone())
+            .or_default()
+            .insert(key.to_vec(), None);
+        state.effects.push(PlannedEffect::DeleteData {
+            address,
+            key: key.to_vec(),
+        });
+        Ok(())
+    }
+
+    fn transfer(&self, from_address: String, to_address: &str, raw_amount: u64) {
+        self.record(PlannedEffect::Transfer {
+            from_address,
+            to_address: to_address.to_string(),
+            raw_amount,
+        });
+    }
+
+    fn set_bytecode(&self, address: String, bytecode: &[u8]) {
+        self.record(PlannedEffect::SetBytecode {
+            address,
+            bytecode: bytecode.to_vec(),
+        });
+    }
+}
+
+/// Run a function like `run_function`, without applying its state changes,
+/// to estimate its gas usage
+///
+/// Returns the response of the execution along with the changes it requested,
+/// see [`BufferingInterface`] for what is buffered and what is not.
+pub fn run_estimate(
+    interface: &dyn Interface,
+    rt_module: RuntimeModule,
+    function: &str,
+    param: &[u8],
+    limit: u64,
+    gas_costs: GasCosts,
+) -> VMResult<Estimate> {
+    let buffering = BufferingInterface::new(interface);
+    let response = run_function(&buffering, rt_module, function, param, limit, gas_costs)?;
+    Ok(Estimate {
+        response,
+        effects: buffering.effects(),
+    })
+}
+
+impl InterfaceDelegate for BufferingInterface {
+    fn delegate<T: Recordable>(
+        &self,
+        _method: &'static str,
+        call: impl FnOnce(&dyn Interface) -> Result<T>,
+    ) -> Result<T> {
+        call(&*self.inner)
+    }
+
+    fn init_call(&self, address: &str, raw_coins: u64) -> Result<Vec<u8>> {
+        if raw_coins > 0 {
+            self.transfer(self.current_address()?, address, raw_coins);
+        }
+        self.inner.init_call(address, 0)
+    }
+
+    fn init_call_wasmv1(&self, address: &str, raw_coins: NativeAmount) -> Result<Vec<u8>> {
+        if raw_coins.mantissa > 0 {
+            self.record(PlannedEffect::TransferWasmV1 {
+                from_address: self.current_address()?,
+                to_address: address.to_string(),
+                amount: raw_coins,
+            });
+        }
+        self.inner
+            .init_call_wasmv1(address, NativeAmount::default())
+    }
+
+    fn transfer_coins(&self, to_address: &str, raw_amount: u64) -> Result<()> {
+        self.transfer(self.current_address()?, to_address, raw_amount);
+        Ok(())
+    }
+
+    fn transfer_coins_for(
+        &self,
+        from_address: &str,
+        to_address: &str,
+        raw_amount: u64,
+    ) -> Result<()> {
+        self.transfer(from_address.to_string(), to_address, raw_amount);
+        Ok(())
+    }
+
+    fn transfer_coins_bootstrap(
+        &self,
+        from_address: Option<&str>,
+        to_address: &str,
+        raw_amount: u64,
+    ) -> Result<()> {
+        let from_address = self.address_or_current(from_address.map(str::to_string))?;
+        self.transfer(from_address, to_address, raw_amount);
+        Ok(())
+    }
+
+    fn transfer_coins_wasmv1(
+        &self,
+        to_address: String,
+        raw_amount: NativeAmount,
+        from_address: Option<String>,
+    ) -> Result<()> {
+        self.record(PlannedEffect::TransferWasmV1 {
+            from_address: self.address_or_current(from_address)?,
+            to_address,
+            amount: raw_amount,
+        });
+        Ok(())
+    }
+
+    fn raw_set_bytecode(&self, bytecode: &[u8]) -> Result<()> {
+        self.set_bytecode(self.current_address()?, bytecode);
+        Ok(())
+    }
+
+    fn raw_set_bytecode_for(&self, address: &str, bytecode: &[u8]) -> Result<()> {
+        self.set_bytecode(address.to_string(), bytecode);
+        Ok(())
+    }
+
+    fn set_bytecode_wasmv1(&self, bytecode: &[u8], address: Option<String>) -> Result<()> {
+        self.set_bytecode(self.address_or_current(address)?, bytecode);
+        Ok(())
+    }
+
+    fn create_module(&self, module: &[u8]) -> Result<String> {
+        let mut state = self.state.lock();
+        state.created += 1;
+        let address = format!("AS1estimated{}", state.created);
+        state.effects.push(PlannedEffect::CreateSc {
+            address: address.clone(),
+            bytecode: module.to_vec(),
+        });
+        Ok(address)
+    }
+
+    fn send_message(
+        &self,
+        target_address: &str,
+        target_handler: &str,
+        validity_start: (u64, u8),
+        validity_end: (u64, u8),
+        max_gas: u64,
+        raw_fee: u64,
+        raw_coins: u64,
+        data: &[u8],
+        filter: Option<(&str, Option<&[u8]>)>,
+    ) -> Result<()> {
+        self.record(PlannedEffect::SendMessage {
+            target_address: target_address.to_string(),
+            target_handler: target_handler.to_string(),
+            validity_start,
+            validity_end,
+            max_gas,
+            raw_fee,
+            raw_coins,
+            data: data.to_vec(),
+            filter: filter.map(|(address, key)| (address.to_string(), key.map(|key| key.to_vec()))),
+        });
+        Ok(())
+    }
+
+    fn get_keys(&self, prefix: Option<&[u8]>) -> Result<BTreeSet<Vec<u8>>> {
+        self.keys(&self.current_address()?, prefix)
+    }
+
+    fn get_keys_for(&self, address: &str, prefix: Option<&[u8]>) -> Result<BTreeSet<Vec<u8>>> {
+        self.keys(address, prefix)
+    }
+
+    fn get_ds_keys_wasmv1(
+        &self,
+        prefix: &[u8],
+        address: Option<String>,
+    ) -> Result<BTreeSet<Vec<u8>>> {
+        self.keys(&self.address_or_current(address)?, Some(prefix))
+    }
+
+    fn raw_get_data(&self, key: &[u8]) -> Result<Vec<u8>> {
+        self.get_data(&self.current_address()?, key)
+    }
+
+    fn raw_get_data_for(&self, address: &str, key: &[u8]) -> Result<Vec<u8>> {
+        self.get_data(address, key)
+    }
+
+    fn get_ds_value_wasmv1(&self, key: &[u8], address: Option<String>) -> Result<Vec<u8>> {
+        self.get_data(&self.address_or_current(address)?, key)
+    }
+
+    fn raw_set_data(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.set_data(self.current_address()?, key, value);
+        Ok(())
+    }
+
+    fn raw_set_data_for(&self, address: &str, key: &[u8], value: &[u8]) -> Result<()> {
+        self.set_data(address.to_string(), key, value);
+        Ok(())
+    }
+
+    fn set_ds_value_wasmv1(&self, key: &[u8], value: &[u8], address: Option<String>) -> Result<()> {
+        self.set_data(self.address_or_current(address)?, key, value);
+        Ok(())
+    }
+
+    fn raw_append_data(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.append_data(self.current_address()?, key, value)
+    }
+
+    fn raw_append_data_for(&self, address: &str, key: &[u8], value: &[u8]) -> Result<()> {
+        self.append_data(address.to_string(), key, value)
+    }
+
+    fn append_ds_value_wasmv1(
+        &self,
+        key: &[u8],
+        value: &[u8],
+        address: Option<String>,
+    ) -> Result<()> {
+        self.append_data(self.address_or_current(address)?, key, value)
+    }
+
+    fn raw_delete_data(&self, key: &[u8]) -> Result<()> {
+        self.delete_data(self.current_address()?, key)
+    }
+
+    fn raw_delete_data_for(&self, address: &str, key: &[u8]) -> Result<()> {
+        self.delete_data(address.to_string(), key)
+    }
+
+    fn delete_ds_entry_wasmv1(&self, key: &[u8], address: Option<String>) -> Result<()> {
+        self.delete_data(self.address_or_current(address)?, key)
+    }
+
+    fn has_data(&self, key: &[u8]) -> Result<bool> {
+        self.data_exists(&self.current_address()?, key)
+    }
+
+    fn has_data_for(&self, address: &str, key: &[u8]) -> Result<bool> {
+        self.data_exists(address, key)
+    }
+
+    fn ds_entry_exists_wasmv1(&self, key: &[u8], address: Option<String>) -> Result<bool> {
+        self.data_exists(&self.address_or_current(address)?, key)
+    }
+
+    // The datastore helpers with a default implementation and the savepoints
+    // are not forwarded: they go through the buffered methods above.
+
+    fn get_keys_prefix(&self, prefix: &[u8], max: usize) -> Result<BTreeSet<Vec<u8>>> {
+        default_get_keys_prefix(self, prefix, max)
+    }
+
+    fn datastore_diff(
+        &self,
+        address_a: &str,
+        address_b: &str,
+        prefix: Option<&[u8]>,
+    ) -> Result<(BTreeSet<Vec<u8>>, u64)> {
+        default_datastore_diff(self, address_a, address_b, prefix)
+    }
+
+    fn get_data_multi(&self, keys: &[Vec<u8>]) -> Result<Vec<Option<Vec<u8>>>> {
+        default_get_data_multi(self, keys)
+    }
+
+    fn set_data_if_absent(&self, key: &[u8], value: &[u8]) -> Result<bool> {
+        default_set_data_if_absent(self, key, value)
+    }
+
+    fn swap_data(&self, key_a: &[u8], key_b: &[u8]) -> Result<()> {
+        default_swap_data(self, key_a, key_b)
+    }
+
+    fn rate_limit(&self, key: &[u8], max_per_period: u64, period: u64) -> Result<bool> {
+        default_rate_limit(self, key, max_per_period, period)
+    }
+
+    fn add_to_data(&self, key: &[u8], delta: i64, overflow: CounterOverflow) -> Result<u64> {
+        default_add_to_data(self, key, delta, overflow)
+    }
+
+    fn savepoint(&self) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    fn rollback_to(&self, _id: u64) -> Result<()> {
+        Ok(())
+    }
+}
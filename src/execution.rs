@@ -1,17 +1,29 @@
-use crate::as_execution::{exec_as_module, ASModule};
-use crate::error::VMResult;
+use crate::as_execution::{exec_as_module, exec_as_module_with_shared_param, ASEnv, ASModule};
+use crate::error::{VMError, VMResult};
 use crate::middlewares::gas_calibration::GasCalibrationResult;
 use crate::settings;
-use crate::types::{Interface, Response};
+use crate::types::{Interface, Response, RunOptions};
 use crate::wasmv1_execution::{exec_wasmv1_module, WasmV1Module};
 use crate::GasCosts;
 use anyhow::{anyhow, Result};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::info_span;
+use wasmer::{Function, FunctionEnv, Store};
 
 /// Enum listing the available compilers
+///
+/// Both compilers are deterministic as used by the runtime: the metering and
+/// the enabled wasm features are the same for both.
 #[derive(Clone)]
 pub enum Compiler {
+    /// Cranelift: slow to compile but fast to execute. The only compiler
+    /// whose modules can be serialized, meant for the cached modules.
     CL,
+    /// Singlepass: fast to compile but slower to execute, meant for the
+    /// modules compiled for a single execution. Its modules cannot be
+    /// serialized, so a cache never mixes them with Cranelift ones.
     SP,
 }
 
@@ -21,32 +33,51 @@ pub enum RuntimeModule {
     WasmV1Module(WasmV1Module),
 }
 
+/// Leading byte of a bytecode, selecting the runtime that executes it.
+///
+/// Wire format of a deployed bytecode:
+/// * `0x00`: AssemblyScript module. The byte is not a prefix but the first
+///   byte of the wasm magic number (`\0asm`), so a plain wasm binary is
+///   passed as is, without stripping anything.
+/// * `0x01`: WasmV1 module. The byte is a prefix stripped before compiling
+///   the wasm binary that follows.
+///
+/// Any other leading byte is rejected. These values are part of the
+/// consensus and must never be renumbered.
 #[repr(u8)]
 #[derive(IntoPrimitive, Debug, Eq, PartialEq, TryFromPrimitive)]
-
 enum RuntimeModuleId {
     ASModuleId = 0,
     WasmV1ModuleId = 1,
 }
 
 impl RuntimeModule {
-    /// Dispatch module creation corresponding to the first bytecode byte
+    /// Dispatch module creation corresponding to the first bytecode byte, see
+    /// `RuntimeModuleId` for the wire format
     ///
     /// * (0): legacy AssemblyScript module
     /// * (1): new agnostic module
     /// * (_): unsupported module
     pub fn new(bytecode: &[u8], gas_costs: GasCosts, compiler: Compiler) -> Result<Self> {
-        if bytecode.len() <= 2 {
-            return Err(anyhow!("Too small bytecode"));
+        match bytecode.len() {
+            0 => return Err(anyhow!("Empty bytecode")),
+            1 => {
+                return Err(anyhow!(
+                    "bytecode contains only a target tag, no module body"
+                ))
+            }
+            2 => return Err(anyhow!("Too small bytecode")),
+            _ => (),
         }
 
-        let module_id = bytecode
-            .first()
-            .map(|&id| RuntimeModuleId::try_from(id))
-            .transpose()
-            .map_err(|err| anyhow!("Unsupported file format for SC({})", err))?
-            .unwrap(); // Safe to unwrap as we checked the bytecode length and for conversion
-                       // errors
+        // Safe to index as we checked the bytecode length
+        let module_id = RuntimeModuleId::try_from(bytecode[0]).map_err(|_| {
+            anyhow!(
+                "Unknown bytecode tag {:#04x}: expected 0x00 (AssemblyScript module) or 0x01 \
+                 (WasmV1 module)",
+                bytecode[0]
+            )
+        })?;
 
         match module_id {
             RuntimeModuleId::ASModuleId => Ok(Self::ASModule(ASModule::new(
@@ -70,6 +101,32 @@ impl RuntimeModule {
         }
     }
 
+    /// Same as `new`, for the AssemblyScript modules of trusted contracts run
+    /// with `run_function_with_memory_snapshot`.
+    ///
+    /// Their mutable globals are exported so that the instance state can be
+    /// snapshotted: the module gets extra `massa_global_*` exports and a
+    /// marker global. Never use it for regular executions, whose compiled
+    /// modules must not depend on this opt-in.
+    pub fn new_for_snapshot(
+        bytecode: &[u8],
+        gas_costs: GasCosts,
+        compiler: Compiler,
+    ) -> Result<Self> {
+        match bytecode.first() {
+            Some(0) => Ok(Self::ASModule(ASModule::new_with_exported_globals(
+                bytecode,
+                gas_costs.max_instance_cost,
+                gas_costs,
+                compiler,
+            )?)),
+            Some(_) => Err(anyhow!(
+                "Memory snapshots are only supported for AssemblyScript modules"
+            )),
+            None => Err(anyhow!("Empty bytecode")),
+        }
+    }
+
     /// Used compiler for the current module
     pub fn compiler(&self) -> Compiler {
         match self {
@@ -78,51 +135,124 @@ impl RuntimeModule {
         }
     }
 
-    /// Serialize a RuntimeModule, prepending its byte id
+    /// Serialize a RuntimeModule, prepending its byte id and the length
+    /// prefixed serialization tag, see `serialization_tag`
     pub fn serialize(&self) -> Result<Vec<u8>> {
-        let (mut ser, id) = match self {
+        let (ser, id) = match self {
             RuntimeModule::ASModule(module) => (module.serialize()?, RuntimeModuleId::ASModuleId),
             RuntimeModule::WasmV1Module(module) => {
                 (module.serialize(), RuntimeModuleId::WasmV1ModuleId)
             }
         };
+        let tag = serialization_tag();
 
-        // CHECK: as we know the size before hand, maybe we can put the id at
-        // the end of the vector, this would prevent a memmove
-        ser.insert(0, id as u8);
-
-        Ok(ser)
+        let mut res = Vec::with_capacity(2 + tag.len() + ser.len());
+        res.push(id as u8);
+        res.push(u8::try_from(tag.len())?);
+        res.extend_from_slice(tag.as_bytes());
+        res.extend_from_slice(&ser);
+        Ok(res)
     }
 
-    /// Deserialize a RuntimeModule
+    /// Deserialize a RuntimeModule, rejecting the modules serialized by an
+    /// incompatible runtime or wasmer version
     pub fn deserialize(ser_module: &[u8], limit: u64, gas_costs: GasCosts) -> Result<Self> {
-        let module_id = ser_module
-            .first()
-            .map(|&id| RuntimeModuleId::try_from(id))
-            .transpose()?;
+        let Some((&id, ser_module)) = ser_module.split_first() else {
+            return Err(anyhow!("Empty bytecode"));
+        };
+        let module_id = RuntimeModuleId::try_from(id)?;
+
+        let tag = serialization_tag();
+        let found_tag = ser_module
+            .split_first()
+            .and_then(|(&len, rest)| rest.get(..len as usize))
+            .ok_or_else(|| anyhow!("Serialized module is missing its serialization tag"))?;
+        if found_tag != tag.as_bytes() {
+            return Err(anyhow!(
+                "Incompatible serialized module: expected {}, found {}",
+                tag,
+                String::from_utf8_lossy(found_tag)
+            ));
+        }
+        let ser_module = &ser_module[1 + found_tag.len()..];
 
         match module_id {
-            Some(RuntimeModuleId::ASModuleId) => Ok(RuntimeModule::ASModule(
-                ASModule::deserialize(&ser_module[1..], limit, gas_costs)?,
-            )),
-            Some(RuntimeModuleId::WasmV1ModuleId) => Ok(RuntimeModule::WasmV1Module(
-                WasmV1Module::deserialize(&ser_module[1..], limit, gas_costs)?,
+            RuntimeModuleId::ASModuleId => Ok(RuntimeModule::ASModule(ASModule::deserialize(
+                ser_module, limit, gas_costs,
+            )?)),
+            RuntimeModuleId::WasmV1ModuleId => Ok(RuntimeModule::WasmV1Module(
+                WasmV1Module::deserialize(ser_module, limit, gas_costs)?,
             )),
-            None => Err(anyhow!("Empty bytecode")),
         }
     }
 
     /// Check the exports of a compiled module to see if it contains the given
-    /// function
-    pub(crate) fn function_exists(&self, function: &str) -> bool {
+    /// function, without instantiating it
+    pub fn function_exists(&self, function: &str) -> bool {
         match self {
             RuntimeModule::ASModule(module) => module.function_exists(function),
             RuntimeModule::WasmV1Module(module) => module.function_exists(function),
         }
     }
+
+    /// Names of the functions exported by a compiled module, without
+    /// instantiating it
+    pub fn exported_functions(&self) -> Vec<String> {
+        match self {
+            RuntimeModule::ASModule(module) => module.exported_functions(),
+            RuntimeModule::WasmV1Module(module) => module.exported_functions(),
+        }
+    }
+}
+
+/// Version of the compilation pipeline (engine, middlewares...) of the
+/// serialized modules. Must be bumped on every change making the previously
+/// serialized modules incompatible.
+const SERIALIZATION_VERSION: u32 = 1;
+
+/// Tag embedded in the serialized modules: deserializing an artifact
+/// produced by another version is unsafe, so it is rejected beforehand.
+fn serialization_tag() -> String {
+    format!(
+        "massa-sc-runtime/{}/wasmer/{}",
+        SERIALIZATION_VERSION,
+        wasmer::VERSION
+    )
+}
+
+/// Compile a module and run all the structural checks done by
+/// `RuntimeModule::new`, then drop it.
+///
+/// Meant for the admission of new bytecode, where the compiled module is not
+/// needed: it is compiled with Singlepass and never retained. `limit` is the
+/// gas available to instantiate it, used instead of
+/// `GasCosts::max_instance_cost`.
+pub fn validate_module(bytecode: &[u8], limit: u64, gas_costs: GasCosts) -> VMResult<()> {
+    let gas_costs = GasCosts {
+        max_instance_cost: limit,
+        ..gas_costs
+    };
+    RuntimeModule::new(bytecode, gas_costs, Compiler::SP)
+        .map(drop)
+        .map_err(|err| VMError::CompilationError(err.to_string()))
+}
+
+/// Position of an execution in its call tree, passed down by the caller to its
+/// sub-calls. The default is the context of a top-level execution.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct CallContext {
+    /// Gas remaining when the top-level execution started, see
+    /// `assembly_script_get_logical_clock`. `None` for a top-level execution.
+    pub(crate) clock_origin: Option<u64>,
+    /// End of `RunOptions::max_execution_time`, computed when the top-level
+    /// execution starts. `None` for a top-level execution.
+    pub(crate) execution_deadline: Option<Instant>,
+    /// Number of nested calls above the execution
+    pub(crate) call_depth: u32,
 }
 
 /// Select and launch the adequate execution function
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn exec(
     interface: &dyn Interface,
     rt_module: RuntimeModule,
@@ -130,15 +260,39 @@ pub(crate) fn exec(
     param: &[u8],
     limit: u64,
     gas_costs: GasCosts,
+    options: RunOptions,
+    call_context: CallContext,
 ) -> VMResult<(Response, Option<GasCalibrationResult>)> {
+    let _span = info_span!(
+        "execution",
+        function,
+        limit,
+        label = options.label.as_deref()
+    )
+    .entered();
     let response = match rt_module {
-        RuntimeModule::ASModule(module) => {
-            exec_as_module(interface, module, function, param, limit, gas_costs)?
-        }
-        RuntimeModule::WasmV1Module(module) => {
-            exec_wasmv1_module(interface, module, function, param, limit, gas_costs)
-                .map_err(|err| anyhow!("Failed to execute WasmV1 module: {}", err.to_string()))?
-        }
+        RuntimeModule::ASModule(module) => exec_as_module(
+            interface,
+            module,
+            function,
+            param,
+            limit,
+            gas_costs,
+            options,
+            call_context,
+            None,
+        )?,
+        RuntimeModule::WasmV1Module(module) => exec_wasmv1_module(
+            interface,
+            module,
+            function,
+            param,
+            limit,
+            gas_costs,
+            options,
+            call_context,
+        )
+        .map_err(|err| anyhow!("Failed to execute WasmV1 module: {}", err.to_string()))?,
     };
     Ok(response)
 }
@@ -162,7 +316,17 @@ pub fn run_main(
     limit: u64,
     gas_costs: GasCosts,
 ) -> VMResult<Response> {
-    Ok(exec(interface, rt_module, settings::MAIN, b"", limit, gas_costs)?.0)
+    Ok(exec(
+        interface,
+        rt_module,
+        settings::MAIN,
+        b"",
+        limit,
+        gas_costs,
+        RunOptions::default(),
+        CallContext::default(),
+    )?
+    .0)
 }
 
 /// Library Input, take a `module` wasm built with the massa environment,
@@ -176,6 +340,10 @@ pub fn run_main(
 ///     return 0;
 /// }
 /// ```
+///
+/// A non-empty `param` given to a function that takes none is silently
+/// ignored, unless `GasCosts::reject_unexpected_param` is set: the rejection
+/// is opt-in.
 pub fn run_function(
     interface: &dyn Interface,
     rt_module: RuntimeModule,
@@ -184,7 +352,271 @@ pub fn run_function(
     limit: u64,
     gas_costs: GasCosts,
 ) -> VMResult<Response> {
-    Ok(exec(interface, rt_module, function, param, limit, gas_costs)?.0)
+    run_function_with_options(
+        interface,
+        rt_module,
+        function,
+        param,
+        limit,
+        gas_costs,
+        RunOptions::default(),
+    )
+}
+
+/// Same as `run_function` with the given options, see `RunOptions`. The
+/// other run functions use the default options.
+pub fn run_function_with_options(
+    interface: &dyn Interface,
+    rt_module: RuntimeModule,
+    function: &str,
+    param: &[u8],
+    limit: u64,
+    gas_costs: GasCosts,
+    options: RunOptions,
+) -> VMResult<Response> {
+    Ok(exec(
+        interface,
+        rt_module,
+        function,
+        param,
+        limit,
+        gas_costs,
+        options,
+        CallContext::default(),
+    )?
+    .0)
+}
+
+/// Same as `run_function_with_options` for a sub-call, in the call context
+/// given by its caller
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn run_subcall(
+    interface: &dyn Interface,
+    rt_module: RuntimeModule,
+    function: &str,
+    param: &[u8],
+    limit: u64,
+    gas_costs: GasCosts,
+    options: RunOptions,
+    call_context: CallContext,
+) -> VMResult<Response> {
+    Ok(exec(
+        interface,
+        rt_module,
+        function,
+        param,
+        limit,
+        gas_costs,
+        options,
+        call_context,
+    )?
+    .0)
+}
+
+/// Library Input, take a `module` wasm built with the massa environment and
+/// run its `constructor` function with the given parameter, as done once when
+/// deploying a contract.
+///
+/// Return:
+/// `None` if the module does not export a `constructor` function, otherwise
+/// the response of the constructor, or its error if it failed.
+pub fn run_constructor(
+    interface: &dyn Interface,
+    rt_module: RuntimeModule,
+    param: &[u8],
+    limit: u64,
+    gas_costs: GasCosts,
+) -> VMResult<Option<Response>> {
+    if !rt_module.function_exists(settings::CONSTRUCTOR) {
+        return Ok(None);
+    }
+    let response = exec(
+        interface,
+        rt_module,
+        settings::CONSTRUCTOR,
+        param,
+        limit,
+        gas_costs,
+        RunOptions::default(),
+        CallContext::default(),
+    )?;
+    Ok(Some(response.0))
+}
+
+/// Library Input, take a `module` wasm built with the massa environment,
+/// run a function of that module `runs` times with the same parameter.
+///
+/// The module is instantiated once and the parameter is written once in its
+/// memory, every run then reuses them. Each run gets `limit` gas.
+///
+/// Runs are NOT isolated from each other: the instance memory and globals
+/// persist between runs. Only supported for AssemblyScript modules.
+pub fn run_function_with_shared_param(
+    interface: &dyn Interface,
+    rt_module: RuntimeModule,
+    function: &str,
+    param: &[u8],
+    runs: usize,
+    limit: u64,
+    gas_costs: GasCosts,
+) -> VMResult<Vec<Response>> {
+    run_shared_instance(
+        interface, rt_module, function, param, runs, limit, gas_costs, false,
+    )
+}
+
+/// Same as `run_function_with_shared_param`, but the linear memory and the
+/// mutable globals of the instance are snapshotted once the instance is
+/// created and the parameter written, and restored with a plain copy before
+/// every run after the first one, instead of creating a new instance with its
+/// data segments.
+///
+/// Every run starts with the memory content and the globals the instance had
+/// after its creation and the parameter allocation: the data written by the
+/// previous run is overwritten and the globals, the AssemblyScript allocator
+/// state included, are set back. The memory is not shrunk back: the pages
+/// grown by a previous run stay allocated, zeroed. The memory of a new
+/// instance is zeroed beyond its data segments, as required by the wasm
+/// specification.
+///
+/// The globals a module does not export are reached through exports added at
+/// compilation: the module must be compiled with
+/// `RuntimeModule::new_for_snapshot`, any other module is rejected.
+///
+/// Restoring copies the whole memory, so it only pays off for modules with
+/// large data segments and a small memory. `test_memory_snapshot_timing`
+/// compares it with a regular instantiation; only use it for trusted modules
+/// for which it measurably helps.
+pub fn run_function_with_memory_snapshot(
+    interface: &dyn Interface,
+    rt_module: RuntimeModule,
+    function: &str,
+    param: &[u8],
+    runs: usize,
+    limit: u64,
+    gas_costs: GasCosts,
+) -> VMResult<Vec<Response>> {
+    run_shared_instance(
+        interface, rt_module, function, param, runs, limit, gas_costs, true,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_shared_instance(
+    interface: &dyn Interface,
+    rt_module: RuntimeModule,
+    function: &str,
+    param: &[u8],
+    runs: usize,
+    limit: u64,
+    gas_costs: GasCosts,
+    restore_memory: bool,
+) -> VMResult<Vec<Response>> {
+    let _span = info_span!("execution", function, limit, runs).entered();
+    match rt_module {
+        RuntimeModule::ASModule(module) => exec_as_module_with_shared_param(
+            interface,
+            module,
+            function,
+            param,
+            runs,
+            limit,
+            gas_costs,
+            RunOptions::default(),
+            restore_memory,
+        ),
+        RuntimeModule::WasmV1Module(_) => {
+            Err(anyhow!("Shared parameter runs are not supported for WasmV1 modules").into())
+        }
+    }
+}
+
+/// Same as `run_function` with experimental host functions added to the
+/// imports of the module, see `ExtraImports`.
+///
+/// Extra imports are NOT consensus-safe and must only be used for ABI
+/// research. Only supported for AssemblyScript modules.
+pub fn run_function_with_extra_imports(
+    interface: &dyn Interface,
+    rt_module: RuntimeModule,
+    function: &str,
+    param: &[u8],
+    limit: u64,
+    gas_costs: GasCosts,
+    extra_imports: impl Fn(&mut Store, &FunctionEnv<ASEnv>) -> Vec<(String, String, Function)>
+        + Send
+        + Sync
+        + 'static,
+) -> VMResult<Response> {
+    let _span = info_span!("execution", function, limit).entered();
+    match rt_module {
+        RuntimeModule::ASModule(module) => Ok(exec_as_module(
+            interface,
+            module,
+            function,
+            param,
+            limit,
+            gas_costs,
+            RunOptions::default(),
+            CallContext::default(),
+            Some(Arc::new(extra_imports)),
+        )?
+        .0),
+        RuntimeModule::WasmV1Module(_) => {
+            Err(anyhow!("Extra imports are not supported for WasmV1 modules").into())
+        }
+    }
+}
+
+/// A single call of a batch, see `run_batch`
+pub struct RunRequest {
+    pub rt_module: RuntimeModule,
+    pub function: String,
+    pub param: Vec<u8>,
+    /// Maximum gas the call can use, capped by what remains of the batch
+    /// budget when it starts
+    pub limit: u64,
+}
+
+/// Execute independent calls sequentially, in the given order, drawing their
+/// gas from a shared `total_gas` budget.
+///
+/// Every call is instantiated and executed in isolation, exactly like with
+/// `run_function`, so a call failing does not abort the batch. A successful
+/// call consumes the gas it used, a failed one the whole limit it was given.
+///
+/// Once the budget is exhausted the remaining calls are not run, their entry
+/// in the returned vector is `None`.
+pub fn run_batch(
+    interface: &dyn Interface,
+    calls: Vec<RunRequest>,
+    total_gas: u64,
+    gas_costs: GasCosts,
+) -> Vec<Option<VMResult<Response>>> {
+    let mut remaining_gas = total_gas;
+    calls
+        .into_iter()
+        .map(|call| {
+            if remaining_gas == 0 {
+                return None;
+            }
+            let limit = call.limit.min(remaining_gas);
+            let res = run_function(
+                interface,
+                call.rt_module,
+                &call.function,
+                &call.param,
+                limit,
+                gas_costs.clone(),
+            );
+            let used_gas = match &res {
+                Ok(response) => limit.saturating_sub(response.remaining_gas),
+                Err(_) => limit,
+            };
+            remaining_gas = remaining_gas.saturating_sub(used_gas);
+            Some(res)
+        })
+        .collect()
 }
 
 /// Same as run_main but return a GasCalibrationResult
@@ -203,6 +635,8 @@ pub fn run_main_gc(
         param,
         limit,
         gas_costs,
+        RunOptions::default(),
+        CallContext::default(),
     )?
     .1
     .unwrap())
@@ -225,14 +659,35 @@ fn test_serialize_deserialize() {
             RuntimeModuleId::ASModuleId as u8
         );
 
-        let serialized2 = RuntimeModule::deserialize(&serialized, 0, GasCosts::default())
-            .unwrap()
-            .serialize()
-            .unwrap();
+        let deserialized = RuntimeModule::deserialize(&serialized, 0, GasCosts::default()).unwrap();
+        match (&module, &deserialized) {
+            (RuntimeModule::ASModule(module), RuntimeModule::ASModule(deserialized)) => {
+                assert_eq!(module.code_hash(), deserialized.code_hash())
+            }
+            _ => panic!("expected ASModules"),
+        }
+        let serialized2 = deserialized.serialize().unwrap();
 
         assert_eq!(serialized, serialized2);
     }
 
+    // Incompatible serialization tag
+    {
+        let module = RuntimeModule::ASModule(
+            ASModule::new(bytecode, 0, GasCosts::default(), Compiler::CL).unwrap(),
+        );
+
+        let mut serialized = module.serialize().unwrap();
+        serialized[2] ^= 0xff;
+        assert!(
+            RuntimeModule::deserialize(&serialized, 0, GasCosts::default())
+                .err()
+                .unwrap()
+                .to_string()
+                .starts_with("Incompatible serialized module")
+        );
+    }
+
     // WasmV1Module
     {
         let module = RuntimeModule::WasmV1Module(
@@ -253,3 +708,46 @@ fn test_serialize_deserialize() {
         assert_eq!(serialized, serialized2);
     }
 }
+
+#[test]
+fn test_new_runtime_module_errors() {
+    let new = |bytecode: &[u8]| {
+        RuntimeModule::new(bytecode, GasCosts::default(), Compiler::SP)
+            .err()
+            .unwrap()
+            .to_string()
+    };
+
+    assert_eq!(new(&[]), "Empty bytecode");
+    assert_eq!(
+        new(&[1]),
+        "bytecode contains only a target tag, no module body"
+    );
+    assert!(new(&[1, 0xff, 0xff, 0xff]).starts_with("Failed to compile WasmV1 module"));
+    assert!(new(&[2, 0xff, 0xff, 0xff]).starts_with("Unknown bytecode tag 0x02"));
+}
+
+#[test]
+fn test_validate_module() {
+    let bytecode = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/wasm/basic_func.wasm"));
+    validate_module(bytecode, 0, GasCosts::default()).unwrap();
+    assert!(matches!(
+        validate_module(&[0, 1, 2, 3], 0, GasCosts::default()),
+        Err(VMError::CompilationError(_))
+    ));
+}
+
+#[test]
+fn test_exported_functions() {
+    let bytecode = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1)
+            (func (export "main"))
+            (func (export "constructor")))"#,
+    )
+    .unwrap();
+    let module = RuntimeModule::new(&bytecode, GasCosts::default(), Compiler::SP).unwrap();
+    assert!(module.function_exists("constructor"));
+    assert!(!module.function_exists("memory"));
+    assert_eq!(module.exported_functions(), vec!["main", "constructor"]);
+}
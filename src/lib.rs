@@ -1,17 +1,35 @@
 mod as_execution;
+mod delegate;
 mod error;
+mod estimate;
 mod execution;
 mod middlewares;
+mod replay;
 mod settings;
+#[cfg(any(test, feature = "testing"))]
+mod testing;
 mod tunable_memory;
 mod types;
 mod wasmv1_execution;
 
-pub use error::VMError;
-pub use execution::{run_function, run_main};
-pub use execution::{Compiler, RuntimeModule};
+pub use as_execution::{ASEnv, ExtraImports};
+pub use error::{AbortInfo, TrapDump, TrapFrame, TrapKind, VMError};
+#[cfg(feature = "trap-metrics")]
+pub use error::{TrapStats, TrapStatsSnapshot, TRAP_STATS};
+pub use estimate::{run_estimate, BufferingInterface, Estimate, PlannedEffect};
+pub use execution::{
+    run_batch, run_constructor, run_function, run_function_with_extra_imports,
+    run_function_with_memory_snapshot, run_function_with_options, run_function_with_shared_param,
+    run_main, validate_module,
+};
+pub use execution::{Compiler, RunRequest, RuntimeModule};
+pub use replay::{record_execution, replay, ExecutionTrace, RecordedCall, EXECUTION_TRACE_VERSION};
+pub use settings::RUNTIME_VERSION;
 pub use types::*;
 
+#[cfg(any(test, feature = "testing"))]
+pub use testing::MockInterface;
+
 #[cfg(feature = "gas_calibration")]
 pub use execution::run_main_gc;
 #[cfg(feature = "gas_calibration")]
@@ -0,0 +1,69 @@
+use std::collections::HashSet;
+use wasmer::{FunctionMiddleware, LocalFunctionIndex, ModuleMiddleware};
+use wasmer_types::{
+    ExportIndex, GlobalIndex, GlobalInit, GlobalType, ModuleInfo, Mutability, Type,
+};
+
+/// Prefix of the exports added for the mutable globals a module does not
+/// export itself, followed by the global index
+pub(crate) const EXPORTED_GLOBAL_PREFIX: &str = "massa_global_";
+
+/// Export marking a module compiled with the `GlobalExporter` middleware
+pub(crate) const GLOBALS_EXPORTED_MARKER: &str = "massa_globals_exported";
+
+/// Export every mutable global of a module, so that the state an instance
+/// keeps in its globals (e.g. the AssemblyScript allocator offset) can be
+/// snapshotted and restored along with its memory.
+///
+/// The exports only give the runtime access to the globals: the module code
+/// and its gas usage are unchanged.
+#[derive(Debug, Default)]
+pub struct GlobalExporter;
+
+#[derive(Debug)]
+struct FunctionGlobalExporter;
+
+impl ModuleMiddleware for GlobalExporter {
+    fn generate_function_middleware(
+        &self,
+        _local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionGlobalExporter)
+    }
+
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let exported: HashSet<GlobalIndex> = module_info
+            .exports
+            .values()
+            .filter_map(|export| match export {
+                ExportIndex::Global(index) => Some(*index),
+                _ => None,
+            })
+            .collect();
+        let hidden: Vec<GlobalIndex> = module_info
+            .globals
+            .iter()
+            .filter(|(index, ty)| ty.mutability == Mutability::Var && !exported.contains(index))
+            .map(|(index, _)| index)
+            .collect();
+        for index in hidden {
+            module_info.exports.insert(
+                format!("{}{}", EXPORTED_GLOBAL_PREFIX, index.as_u32()),
+                ExportIndex::Global(index),
+            );
+        }
+
+        let marker = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Const));
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(1));
+        module_info.exports.insert(
+            GLOBALS_EXPORTED_MARKER.to_string(),
+            ExportIndex::Global(marker),
+        );
+    }
+}
+
+impl FunctionMiddleware for FunctionGlobalExporter {}
@@ -1,3 +1,4 @@
 pub mod dumper;
 pub mod gas_calibration;
+pub mod global_exporter;
 pub mod operator;
@@ -0,0 +1,211 @@
+//! Capture of the complete input context of an execution, and its offline
+//! replay.
+//!
+//! An [`ExecutionTrace`] contains everything needed to re-run an execution
+//! deterministically:
+//! * the executed bytecode and its sha256 hash
+//! * the called function, its parameter and the gas limit
+//! * the gas costs and the run options (bootstrap mode...)
+//! * every response returned by the [`Interface`] during the execution, in
+//!   call order. The random seed and the current time are captured this way as
+//!   they are provided by `unsafe_random*` and `get_time`.
+//!
+//! Traces are versioned with [`EXECUTION_TRACE_VERSION`] and can be stored
+//! with any serde format (JSON for instance). Each recorded call is made of the
+//! interface method name and of its result serialized as a serde `Result`,
+//! i.e. `{"Ok": value}` or `{"Err": "message"}`.
+//!
+//! Every interface method is recorded, for AssemblyScript and WasmV1
+//! executions alike. The compiled modules returned by `get_module` and
+//! `get_tmp_module` are not: they are compiled again from their bytecode on
+//! replay.
+
+use crate::delegate::{InterfaceDelegate, Recordable};
+use crate::error::VMResult;
+use crate::execution::{run_function_with_options, Compiler, RuntimeModule};
+use crate::types::{Interface, Response, RunOptions};
+use crate::GasCosts;
+use anyhow::{anyhow, bail, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Current version of the [`ExecutionTrace`] format
+pub const EXECUTION_TRACE_VERSION: u32 = 3;
+
+/// Response of an interface method observed during a recorded execution
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedCall {
+    /// Name of the interface method
+    pub method: String,
+    /// Serialized `Result<T, String>` returned by the method
+    pub response: serde_json::Value,
+}
+
+/// Complete input context of an execution
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExecutionTrace {
+    /// Format version, see [`EXECUTION_TRACE_VERSION`]
+    pub version: u32,
+    /// Sha256 hash of `bytecode`
+    pub code_hash: [u8; 32],
+    /// Executed bytecode
+    pub bytecode: Vec<u8>,
+    /// Called function
+    pub function: String,
+    /// Parameter given to the called function
+    pub param: Vec<u8>,
+    /// Gas limit of the execution
+    pub gas_limit: u64,
+    /// Gas costs of the execution
+    pub gas_costs: GasCosts,
+    /// Options of the execution
+    pub options: RunOptions,
+    /// Interface responses, in call order
+    pub calls: Vec<RecordedCall>,
+}
+
+/// Run a function of the given bytecode and record its complete input context
+///
+/// Returns the execution result along with the trace allowing to replay it.
+pub fn record_execution(
+    interface: &dyn Interface,
+    bytecode: &[u8],
+    function: &str,
+    param: &[u8],
+    limit: u64,
+    gas_costs: GasCosts,
+    options: RunOptions,
+) -> (VMResult<Response>, ExecutionTrace) {
+    let recorder = RecordingInterface {
+        inner: interface.clone_box(),
+        calls: Default::default(),
+    };
+    let res = RuntimeModule::new(bytecode, gas_costs.clone(), Compiler::SP)
+        .map_err(Into::into)
+        .and_then(|module| {
+            run_function_with_options(
+                &recorder,
+                module,
+                function,
+                param,
+                limit,
+                gas_costs.clone(),
+                options.clone(),
+            )
+        });
+    let trace = ExecutionTrace {
+        version: EXECUTION_TRACE_VERSION,
+        code_hash: Sha256::digest(bytecode).into(),
+        bytecode: bytecode.to_vec(),
+        function: function.to_string(),
+        param: param.to_vec(),
+        gas_limit: limit,
+        gas_costs,
+        options,
+        calls: recorder.calls.lock().clone(),
+    };
+    (res, trace)
+}
+
+/// Re-run a recorded execution against the interface responses it observed
+pub fn replay(trace: &ExecutionTrace) -> VMResult<Response> {
+    if trace.version != EXECUTION_TRACE_VERSION {
+        return Err(anyhow!(
+            "Unsupported execution trace version: {} (expected {})",
+            trace.version,
+            EXECUTION_TRACE_VERSION
+        )
+        .into());
+    }
+    if <[u8; 32]>::from(Sha256::digest(&trace.bytecode)) != trace.code_hash {
+        return Err(anyhow!("Execution trace bytecode does not match its hash").into());
+    }
+    let interface = ReplayInterface {
+        calls: Arc::new(Mutex::new(trace.calls.iter().cloned().collect())),
+        gas_costs: trace.gas_costs.clone(),
+    };
+    let module = RuntimeModule::new(&trace.bytecode, trace.gas_costs.clone(), Compiler::SP)?;
+    run_function_with_options(
+        &interface,
+        module,
+        &trace.function,
+        &trace.param,
+        trace.gas_limit,
+        trace.gas_costs.clone(),
+        trace.options.clone(),
+    )
+}
+
+/// Interface wrapper recording every response of the wrapped interface
+#[derive(Clone)]
+struct RecordingInterface {
+    inner: Box<dyn Interface>,
+    calls: Arc<Mutex<Vec<RecordedCall>>>,
+}
+
+impl InterfaceDelegate for RecordingInterface {
+    fn delegate<T: Recordable>(
+        &self,
+        method: &'static str,
+        call: impl FnOnce(&dyn Interface) -> Result<T>,
+    ) -> Result<T> {
+        let res = call(&*self.inner);
+        let response = res
+            .as_ref()
+            .map(Recordable::to_record)
+            .map_err(|err| err.to_string());
+        self.calls.lock().push(RecordedCall {
+            method: method.to_string(),
+            response: serde_json::to_value(response).unwrap_or_default(),
+        });
+        res
+    }
+}
+
+/// Interface returning the responses recorded in an [`ExecutionTrace`]
+#[derive(Clone)]
+struct ReplayInterface {
+    calls: Arc<Mutex<VecDeque<RecordedCall>>>,
+    gas_costs: GasCosts,
+}
+
+impl ReplayInterface {
+    /// Pop the next recorded response, failing if the execution diverged
+    fn next<T: Recordable>(&self, method: &str) -> Result<T> {
+        let Some(call) = self.calls.lock().pop_front() else {
+            bail!("Replay diverged: no recorded response left for {}", method);
+        };
+        if call.method != method {
+            bail!(
+                "Replay diverged: expected a call to {} but {} was recorded",
+                method,
+                call.method
+            );
+        }
+        let response = serde_json::from_value::<Result<serde_json::Value, String>>(call.response)?;
+        T::from_record(response.map_err(|err| anyhow!(err))?)
+    }
+}
+
+impl InterfaceDelegate for ReplayInterface {
+    fn delegate<T: Recordable>(
+        &self,
+        method: &'static str,
+        _call: impl FnOnce(&dyn Interface) -> Result<T>,
+    ) -> Result<T> {
+        self.next(method)
+    }
+
+    fn get_module(&self, bytecode: &[u8], _gas_limit: u64) -> Result<RuntimeModule> {
+        self.next::<()>("get_module")?;
+        RuntimeModule::new(bytecode, self.gas_costs.clone(), Compiler::SP)
+    }
+
+    fn get_tmp_module(&self, bytecode: &[u8], _gas_limit: u64) -> Result<RuntimeModule> {
+        self.next::<()>("get_tmp_module")?;
+        RuntimeModule::new(bytecode, self.gas_costs.clone(), Compiler::SP)
+    }
+}
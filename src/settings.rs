@@ -1,4 +1,13 @@
 pub(crate) const MAIN: &str = "main";
+pub(crate) const CONSTRUCTOR: &str = "constructor";
+/// Prefix of the entries of the ABI cost file giving the cost of an operator,
+/// see `GasCosts::operator_costs`
+pub(crate) const OPERATOR_COST_PREFIX: &str = "operator:";
+
+/// Version of the runtime exposed to the contracts by
+/// `assembly_script_get_runtime_version`. Must be bumped on every change
+/// affecting the ABIs semantics.
+pub const RUNTIME_VERSION: u32 = 1;
 
 pub(crate) fn max_number_of_pages() -> u32 {
     64
@@ -11,3 +20,45 @@ pub(crate) fn max_datastore_entry_count() -> usize {
 pub(crate) fn max_op_datastore_entry_count() -> usize {
     128
 }
+
+pub(crate) fn max_locals_per_function() -> u32 {
+    50_000
+}
+
+pub(crate) fn max_functions() -> u32 {
+    50_000
+}
+
+pub(crate) fn max_globals() -> u32 {
+    10_000
+}
+
+pub(crate) fn max_function_body_size() -> u32 {
+    1024 * 1024
+}
+
+pub(crate) fn max_imports() -> u32 {
+    1_000
+}
+
+pub(crate) fn max_subcall_return_size() -> u64 {
+    // the whole memory of the caller
+    max_number_of_pages() as u64 * 64 * 1024
+}
+
+pub(crate) fn max_param_size() -> u64 {
+    // the whole memory of the callee
+    max_number_of_pages() as u64 * 64 * 1024
+}
+
+pub(crate) fn max_call_depth() -> u32 {
+    64
+}
+
+pub(crate) fn max_op_data_read() -> u64 {
+    64 * 1024 * 1024
+}
+
+pub(crate) fn max_total_event_bytes() -> u64 {
+    10 * 1024 * 1024
+}
@@ -0,0 +1,1066 @@
+//! In-memory implementation of the [`Interface`], to test smart contracts
+//! against the runtime without a node.
+//!
+//! [`MockInterface`] keeps a datastore and a bytecode per address, the
+//! balances, the call stack and the emitted events. Every other method
+//! returns a deterministic value: no randomness, a time set to zero, a
+//! configurable slot, and always valid signatures and addresses.
+//!
+//! Clones share the same state, so the interface given to the runtime can be
+//! inspected after the execution:
+//!
+//! ```ignore
+//! let interface = MockInterface::new()
+//!     .with_balance(MockInterface::DEFAULT_ADDRESS, 1_000)
+//!     .with_datastore_entry(b"key", b"value");
+//! run_main(&interface, module, limit, gas_costs)?;
+//! assert_eq!(interface.emitted_events(), vec!["hello".to_string()]);
+//! ```
+
+use crate::execution::{Compiler, RuntimeModule};
+use crate::types::{CounterOverflow, Interface, InterfaceClone};
+use crate::GasCosts;
+use anyhow::{anyhow, bail, Result};
+use massa_proto_rs::massa::model::v1::{
+    AddressCategory, ComparisonResult, NativeAmount, NativeTime, Slot,
+};
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+/// Number of decimals of the raw amounts, used to convert the WasmV1
+/// `NativeAmount`s
+const AMOUNT_DECIMALS: u32 = 9;
+
+#[derive(Default)]
+struct MockState {
+    call_stack: Vec<String>,
+    datastores: HashMap<String, HashMap<Vec<u8>, Vec<u8>>>,
+    bytecodes: HashMap<String, Vec<u8>>,
+    balances: HashMap<String, u64>,
+    op_datastore: HashMap<Vec<u8>, Vec<u8>>,
+    call_coins: Vec<u64>,
+    events: Vec<String>,
+    calls: Vec<String>,
+    created: u64,
+    slot: (u64, u8),
+}
+
+impl MockState {
+    fn current_address(&self) -> Result<String> {
+        self.call_stack
+            .last()
+            .cloned()
+            .ok_or_else(|| anyhow!("empty call stack"))
+    }
+
+    fn address_or_current(&self, address: Option<String>) -> Result<String> {
+        match address {
+            Some(address) => Ok(address),
+            None => self.current_address(),
+        }
+    }
+
+    fn datastore(&mut self, address: &str) -> &mut HashMap<Vec<u8>, Vec<u8>> {
+        self.datastores.entry(address.to_string()).or_default()
+    }
+
+    fn keys(&mut self, address: &str, prefix: &[u8]) -> BTreeSet<Vec<u8>> {
+        self.datastore(address)
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+
+    fn get_data(&mut self, address: &str, key: &[u8]) -> Result<Vec<u8>> {
+        self.datastore(address)
+            .get(key)
+            .cloned()
+            .ok_or_else(|| anyhow!("data entry not found"))
+    }
+
+    fn set_data(&mut self, address: &str, key: &[u8], value: &[u8]) {
+        self.datastore(address).insert(key.to_vec(), value.to_vec());
+    }
+
+    fn append_data(&mut self, address: &str, key: &[u8], value: &[u8]) -> Result<()> {
+        match self.datastore(address).get_mut(key) {
+            Some(entry) => {
+                entry.extend_from_slice(value);
+                Ok(())
+            }
+            None => bail!("data entry not found"),
+        }
+    }
+
+    fn delete_data(&mut self, address: &str, key: &[u8]) -> Result<()> {
+        match self.datastore(address).remove(key) {
+            Some(_) => Ok(()),
+            None => bail!("data entry not found"),
+        }
+    }
+
+    fn bytecode(&self, address: &str) -> Vec<u8> {
+        self.bytecodes.get(address).cloned().unwrap_or_default()
+    }
+
+    fn transfer(&mut self, from: &str, to: &str, amount: u64) -> Result<()> {
+        let from_balance = self.balances.get(from).copied().unwrap_or_default();
+        let Some(from_balance) = from_balance.checked_sub(amount) else {
+            bail!(
+                "insufficient balance of {} to transfer {} coins",
+                from,
+                amount
+            );
+        };
+        self.balances.insert(from.to_string(), from_balance);
+        let to_balance = self.balances.entry(to.to_string()).or_default();
+        *to_balance = to_balance
+            .checked_add(amount)
+            .ok_or_else(|| anyhow!("balance overflow of {}", to))?;
+        Ok(())
+    }
+}
+
+/// Mock of the [`Interface`] backed by an in-memory state, see the module
+/// documentation
+#[derive(Clone)]
+pub struct MockInterface {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl Default for MockInterface {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockInterface {
+    /// Address of the called contract, on top of the initial call stack
+    pub const DEFAULT_ADDRESS: &'static str = "AS1mockcontract";
+    /// Address of the caller, at the bottom of the initial call stack
+    pub const DEFAULT_CALLER: &'static str = "AU1mockcaller";
+
+    /// Create an empty mock, executing as [`Self::DEFAULT_ADDRESS`] on
+    /// behalf of [`Self::DEFAULT_CALLER`]
+    pub fn new() -> Self {
+        let state = MockState {
+            call_stack: vec![
+                Self::DEFAULT_CALLER.to_string(),
+                Self::DEFAULT_ADDRESS.to_string(),
+            ],
+            ..Default::default()
+        };
+        Self {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Set the initial call stack, the current address being the last one
+    pub fn with_call_stack(self, call_stack: &[&str]) -> Self {
+        self.state.lock().call_stack = call_stack.iter().map(|a| a.to_string()).collect();
+        self
+    }
+
+    /// Set the balance of `address`
+    pub fn with_balance(self, address: &str, balance: u64) -> Self {
+        self.state
+            .lock()
+            .balances
+            .insert(address.to_string(), balance);
+        self
+    }
+
+    /// Add an entry to the datastore of the current address
+    pub fn with_datastore_entry(self, key: &[u8], value: &[u8]) -> Self {
+        {
+            let mut state = self.state.lock();
+            let address = state.current_address().unwrap_or_default();
+            state.set_data(&address, key, value);
+        }
+        self
+    }
+
+    /// Add an entry to the datastore of `address`
+    pub fn with_datastore_entry_for(self, address: &str, key: &[u8], value: &[u8]) -> Self {
+        self.state.lock().set_data(address, key, value);
+        self
+    }
+
+    /// Set the bytecode of `address`, loaded by the calls to this address
+    pub fn with_bytecode(self, address: &str, bytecode: &[u8]) -> Self {
+        self.state
+            .lock()
+            .bytecodes
+            .insert(address.to_string(), bytecode.to_vec());
+        self
+    }
+
+    /// Set the current execution slot, (0, 0) by default
+    pub fn with_slot(self, period: u64, thread: u8) -> Self {
+        self.state.lock().slot = (period, thread);
+        self
+    }
+
+    /// Add an entry to the datastore of the operation
+    pub fn with_op_datastore_entry(self, key: &[u8], value: &[u8]) -> Self {
+        self.state
+            .lock()
+            .op_datastore
+            .insert(key.to_vec(), value.to_vec());
+        self
+    }
+
+    /// Events emitted so far, in emission order
+    pub fn emitted_events(&self) -> Vec<String> {
+        self.state.lock().events.clone()
+    }
+
+    /// Names of the interface methods called so far, in call order
+    pub fn calls(&self) -> Vec<String> {
+        self.state.lock().calls.clone()
+    }
+
+    /// Current balance of `address`
+    pub fn balance(&self, address: &str) -> u64 {
+        self.state
+            .lock()
+            .balances
+            .get(address)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Value stored at `key` in the datastore of `address`
+    pub fn datastore_entry(&self, address: &str, key: &[u8]) -> Option<Vec<u8>> {
+        self.state
+            .lock()
+            .datastores
+            .get(address)
+            .and_then(|datastore| datastore.get(key).cloned())
+    }
+
+    /// Lock the state and record the call to `method`
+    fn call(&self, method: &str) -> parking_lot::MutexGuard<'_, MockState> {
+        let mut state = self.state.lock();
+        state.calls.push(method.to_string());
+        state
+    }
+}
+
+fn to_raw_amount(amount: &NativeAmount) -> Result<u64> {
+    if amount.scale > AMOUNT_DECIMALS {
+        bail!("amount scale {} is too large", amount.scale);
+    }
+    amount
+        .mantissa
+        .checked_mul(10u64.pow(AMOUNT_DECIMALS - amount.scale))
+        .ok_or_else(|| anyhow!("amount overflow"))
+}
+
+fn from_raw_amount(raw_amount: u64) -> NativeAmount {
+    NativeAmount {
+        mantissa: raw_amount,
+        scale: AMOUNT_DECIMALS,
+    }
+}
+
+fn compare<T: Ord>(left: T, right: T) -> ComparisonResult {
+    match left.cmp(&right) {
+        std::cmp::Ordering::Less => ComparisonResult::Lower,
+        std::cmp::Ordering::Equal => ComparisonResult::Equal,
+        std::cmp::Ordering::Greater => ComparisonResult::Greater,
+    }
+}
+
+impl InterfaceClone for MockInterface {
+    fn clone_box(&self) -> Box<dyn Interface> {
+        Box::new(self.clone())
+    }
+}
+
+impl Interface for MockInterface {
+    fn init_call(&self, address: &str, raw_coins: u64) -> Result<Vec<u8>> {
+        let mut state = self.call("init_call");
+        let caller = state.current_address()?;
+        state.transfer(&caller, address, raw_coins)?;
+        state.call_stack.push(address.to_string());
+        state.call_coins.push(raw_coins);
+        Ok(state.bytecode(address))
+    }
+
+    fn init_call_wasmv1(&self, address: &str, raw_coins: NativeAmount) -> Result<Vec<u8>> {
+        let raw_coins = to_raw_amount(&raw_coins)?;
+        let mut state = self.call("init_call_wasmv1");
+        let caller = state.current_address()?;
+        state.transfer(&caller, address, raw_coins)?;
+        state.call_stack.push(address.to_string());
+        state.call_coins.push(raw_coins);
+        Ok(state.bytecode(address))
+    }
+
+    fn finish_call(&self) -> Result<()> {
+        let mut state = self.call("finish_call");
+        state.call_stack.pop();
+        state.call_coins.pop();
+        Ok(())
+    }
+
+    fn get_balance(&self) -> Result<u64> {
+        let state = self.call("get_balance");
+        let address = state.current_address()?;
+        Ok(state.balances.get(&address).copied().unwrap_or_default())
+    }
+
+    fn get_balance_for(&self, address: &str) -> Result<u64> {
+        let state = self.call("get_balance_for");
+        Ok(state.balances.get(address).copied().unwrap_or_default())
+    }
+
+    fn get_balance_wasmv1(&self, address: Option<String>) -> Result<NativeAmount> {
+        let state = self.call("get_balance_wasmv1");
+        let address = state.address_or_current(address)?;
+        let balance = state.balances.get(&address).copied().unwrap_or_default();
+        Ok(from_raw_amount(balance))
+    }
+
+    fn transfer_coins(&self, to_address: &str, raw_amount: u64) -> Result<()> {
+        let mut state = self.call("transfer_coins");
+        let from_address = state.current_address()?;
+        state.transfer(&from_address, to_address, raw_amount)
+    }
+
+    fn transfer_coins_for(
+        &self,
+        from_address: &str,
+        to_address: &str,
+        raw_amount: u64,
+    ) -> Result<()> {
+        let mut state = self.call("transfer_coins_for");
+        state.transfer(from_address, to_address, raw_amount)
+    }
+
+    fn transfer_coins_wasmv1(
+        &self,
+        to_address: String,
+        raw_amount: NativeAmount,
+        from_address: Option<String>,
+    ) -> Result<()> {
+        let mut state = self.call("transfer_coins_wasmv1");
+        let from_address = state.address_or_current(from_address)?;
+        state.transfer(&from_address, &to_address, to_raw_amount(&raw_amount)?)
+    }
+
+    fn get_call_coins(&self) -> Result<u64> {
+        let state = self.call("get_call_coins");
+        Ok(state.call_coins.last().copied().unwrap_or_default())
+    }
+
+    fn get_call_coins_wasmv1(&self) -> Result<NativeAmount> {
+        let state = self.call("get_call_coins_wasmv1");
+        Ok(from_raw_amount(
+            state.call_coins.last().copied().unwrap_or_default(),
+        ))
+    }
+
+    fn raw_set_bytecode(&self, bytecode: &[u8]) -> Result<()> {
+        let mut state = self.call("raw_set_bytecode");
+        let address = state.current_address()?;
+        state.bytecodes.insert(address, bytecode.to_vec());
+        Ok(())
+    }
+
+    fn raw_set_bytecode_for(&self, address: &str, bytecode: &[u8]) -> Result<()> {
+        let mut state = self.call("raw_set_bytecode_for");
+        state
+            .bytecodes
+            .insert(address.to_string(), bytecode.to_vec());
+        Ok(())
+    }
+
+    fn set_bytecode_wasmv1(&self, bytecode: &[u8], address: Option<String>) -> Result<()> {
+        let mut state = self.call("set_bytecode_wasmv1");
+        let address = state.address_or_current(address)?;
+        state.bytecodes.insert(address, bytecode.to_vec());
+        Ok(())
+    }
+
+    fn create_module(&self, module: &[u8]) -> Result<String> {
+        let mut state = self.call("create_module");
+        state.created += 1;
+        let address = format!("AS1mockcreated{}", state.created);
+        state.bytecodes.insert(address.clone(), module.to_vec());
+        Ok(address)
+    }
+
+    fn print(&self, message: &str) -> Result<()> {
+        self.call("print");
+        println!("{}", message);
+        Ok(())
+    }
+
+    fn get_keys(&self, prefix: Option<&[u8]>) -> Result<BTreeSet<Vec<u8>>> {
+        let mut state = self.call("get_keys");
+        let address = state.current_address()?;
+        Ok(state.keys(&address, prefix.unwrap_or_default()))
+    }
+
+    fn get_keys_for(&self, address: &str, prefix: Option<&[u8]>) -> Result<BTreeSet<Vec<u8>>> {
+        let mut state = self.call("get_keys_for");
+        Ok(state.keys(address, prefix.unwrap_or_default()))
+    }
+
+    fn get_ds_keys_wasmv1(
+        &self,
+        prefix: &[u8],
+        address: Option<String>,
+    ) -> Result<BTreeSet<Vec<u8>>> {
+        let mut state = self.call("get_ds_keys_wasmv1");
+        let address = state.address_or_current(address)?;
+        Ok(state.keys(&address, prefix))
+    }
+
+    fn raw_get_data(&self, key: &[u8]) -> Result<Vec<u8>> {
+        let mut state = self.call("raw_get_data");
+        let address = state.current_address()?;
+        state.get_data(&address, key)
+    }
+
+    fn raw_get_data_for(&self, address: &str, key: &[u8]) -> Result<Vec<u8>> {
+        let mut state = self.call("raw_get_data_for");
+        state.get_data(address, key)
+    }
+
+    fn get_ds_value_wasmv1(&self, key: &[u8], address: Option<String>) -> Result<Vec<u8>> {
+        let mut state = self.call("get_ds_value_wasmv1");
+        let address = state.address_or_current(address)?;
+        state.get_data(&address, key)
+    }
+
+    fn raw_set_data(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        let mut state = self.call("raw_set_data");
+        let address = state.current_address()?;
+        state.set_data(&address, key, value);
+        Ok(())
+    }
+
+    fn raw_set_data_for(&self, address: &str, key: &[u8], value: &[u8]) -> Result<()> {
+        let mut state = self.call("raw_set_data_for");
+        state.set_data(address, key, value);
+        Ok(())
+    }
+
+    fn set_ds_value_wasmv1(&self, key: &[u8], value: &[u8], address: Option<String>) -> Result<()> {
+        let mut state = self.call("set_ds_value_wasmv1");
+        let address = state.address_or_current(address)?;
+        state.set_data(&address, key, value);
+        Ok(())
+    }
+
+    fn raw_append_data(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        let mut state = self.call("raw_append_data");
+        let address = state.current_address()?;
+        state.append_data(&address, key, value)
+    }
+
+    fn raw_append_data_for(&self, address: &str, key: &[u8], value: &[u8]) -> Result<()> {
+        let mut state = self.call("raw_append_data_for");
+        state.append_data(address, key, value)
+    }
+
+    fn append_ds_value_wasmv1(
+        &self,
+        key: &[u8],
+        value: &[u8],
+        address: Option<String>,
+    ) -> Result<()> {
+        let mut state = self.call("append_ds_value_wasmv1");
+        let address = state.address_or_current(address)?;
+        state.append_data(&address, key, value)
+    }
+
+    fn raw_delete_data(&self, key: &[u8]) -> Result<()> {
+        let mut state = self.call("raw_delete_data");
+        let address = state.current_address()?;
+        state.delete_data(&address, key)
+    }
+
+    fn raw_delete_data_for(&self, address: &str, key: &[u8]) -> Result<()> {
+        let mut state = self.call("raw_delete_data_for");
+        state.delete_data(address, key)
+    }
+
+    fn delete_ds_entry_wasmv1(&self, key: &[u8], address: Option<String>) -> Result<()> {
+        let mut state = self.call("delete_ds_entry_wasmv1");
+        let address = state.address_or_current(address)?;
+        state.delete_data(&address, key)
+    }
+
+    fn set_data_if_absent(&self, key: &[u8], value: &[u8]) -> Result<bool> {
+        let mut state = self.call("set_data_if_absent");
+        let address = state.current_address()?;
+        if state.datastore(&address).contains_key(key) {
+            return Ok(false);
+        }
+        state.set_data(&address, key, value);
+        Ok(true)
+    }
+
+    fn swap_data(&self, key_a: &[u8], key_b: &[u8]) -> Result<()> {
+        let mut state = self.call("swap_data");
+        let address = state.current_address()?;
+        let datastore = state.datastore(&address);
+        let value_a = datastore.remove(key_a);
+        let value_b = datastore.remove(key_b);
+        if let Some(value) = value_b {
+            datastore.insert(key_a.to_vec(), value);
+        }
+        if let Some(value) = value_a {
+            datastore.insert(key_b.to_vec(), value);
+        }
+        Ok(())
+    }
+
+    fn rate_limit(&self, key: &[u8], max_per_period: u64, period: u64) -> Result<bool> {
+        let mut state = self.call("rate_limit");
+        let address = state.current_address()?;
+        let window = state.slot.0 / period;
+        let count = match state.datastore(&address).get(key) {
+            Some(value) => {
+                let Ok(value) = <[u8; 16]>::try_from(value.as_slice()) else {
+                    bail!("Rate limit counter is not 16 bytes long");
+                };
+                let (stored_window, count) = value.split_at(8);
+                if u64::from_le_bytes(stored_window.try_into()?) == window {
+                    u64::from_le_bytes(count.try_into()?)
+                } else {
+                    0
+                }
+            }
+            None => 0,
+        };
+        if count >= max_per_period {
+            return Ok(false);
+        }
+        state.set_data(
+            &address,
+            key,
+            &[window.to_le_bytes(), (count + 1).to_le_bytes()].concat(),
+        );
+        Ok(true)
+    }
+
+    fn add_to_data(&self, key: &[u8], delta: i64, overflow: CounterOverflow) -> Result<u64> {
+        let mut state = self.call("add_to_data");
+        let address = state.current_address()?;
+        let current = match state.datastore(&address).get(key) {
+            Some(value) => {
+                let Ok(bytes) = <[u8; 8]>::try_from(value.as_slice()) else {
+                    bail!("Counter value is not 8 bytes long");
+                };
+                u64::from_le_bytes(bytes)
+            }
+            None => 0,
+        };
+        let res = overflow.add(current, delta)?;
+        state.set_data(&address, key, &res.to_le_bytes());
+        Ok(res)
+    }
+
+    fn has_data(&self, key: &[u8]) -> Result<bool> {
+        let mut state = self.call("has_data");
+        let address = state.current_address()?;
+        Ok(state.datastore(&address).contains_key(key))
+    }
+
+    fn has_data_for(&self, address: &str, key: &[u8]) -> Result<bool> {
+        let mut state = self.call("has_data_for");
+        Ok(state.datastore(address).contains_key(key))
+    }
+
+    fn ds_entry_exists_wasmv1(&self, key: &[u8], address: Option<String>) -> Result<bool> {
+        let mut state = self.call("ds_entry_exists_wasmv1");
+        let address = state.address_or_current(address)?;
+        Ok(state.datastore(&address).contains_key(key))
+    }
+
+    fn raw_get_bytecode(&self) -> Result<Vec<u8>> {
+        let state = self.call("raw_get_bytecode");
+        Ok(state.bytecode(&state.current_address()?))
+    }
+
+    fn raw_get_bytecode_for(&self, address: &str) -> Result<Vec<u8>> {
+        let state = self.call("raw_get_bytecode_for");
+        Ok(state.bytecode(address))
+    }
+
+    fn get_bytecode_wasmv1(&self, address: Option<String>) -> Result<Vec<u8>> {
+        let state = self.call("get_bytecode_wasmv1");
+        Ok(state.bytecode(&state.address_or_current(address)?))
+    }
+
+    fn get_op_keys(&self, prefix: Option<&[u8]>) -> Result<Vec<Vec<u8>>> {
+        let state = self.call("get_op_keys");
+        let prefix = prefix.unwrap_or_default();
+        let keys: BTreeSet<Vec<u8>> = state
+            .op_datastore
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect();
+        Ok(keys.into_iter().collect())
+    }
+
+    fn get_op_keys_wasmv1(&self, prefix: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let state = self.call("get_op_keys_wasmv1");
+        let keys: BTreeSet<Vec<u8>> = state
+            .op_datastore
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect();
+        Ok(keys.into_iter().collect())
+    }
+
+    fn op_entry_exists(&self, key: &[u8]) -> Result<bool> {
+        let state = self.call("op_entry_exists");
+        Ok(state.op_datastore.contains_key(key))
+    }
+
+    fn get_op_data(&self, key: &[u8]) -> Result<Vec<u8>> {
+        let state = self.call("get_op_data");
+        state
+            .op_datastore
+            .get(key)
+            .cloned()
+            .ok_or_else(|| anyhow!("op data entry not found"))
+    }
+
+    fn caller_has_write_access(&self) -> Result<bool> {
+        self.call("caller_has_write_access");
+        Ok(true)
+    }
+
+    fn hash(&self, data: &[u8]) -> Result<[u8; 32]> {
+        self.call("hash");
+        Ok(Sha256::digest(data).into())
+    }
+
+    fn hash_blake3(&self, _bytes: &[u8]) -> Result<[u8; 32]> {
+        self.call("hash_blake3");
+        Ok([0; 32])
+    }
+
+    fn signature_verify(&self, _data: &[u8], _signature: &str, _public_key: &str) -> Result<bool> {
+        self.call("signature_verify");
+        Ok(true)
+    }
+
+    fn evm_signature_verify(
+        &self,
+        _message: &[u8],
+        _signature: &[u8],
+        _public_key: &[u8],
+    ) -> Result<bool> {
+        self.call("evm_signature_verify");
+        Ok(true)
+    }
+
+    fn evm_get_address_from_pubkey(&self, _public_key: &[u8]) -> Result<Vec<u8>> {
+        self.call("evm_get_address_from_pubkey");
+        Ok(vec![0; 20])
+    }
+
+    fn evm_get_pubkey_from_signature(&self, _hash: &[u8], _signature: &[u8]) -> Result<Vec<u8>> {
+        self.call("evm_get_pubkey_from_signature");
+        Ok(vec![0; 65])
+    }
+
+    fn is_address_eoa(&self, address: &str) -> Result<bool> {
+        self.call("is_address_eoa");
+        Ok(address.starts_with("AU"))
+    }
+
+    fn address_from_public_key(&self, public_key: &str) -> Result<String> {
+        self.call("address_from_public_key");
+        Ok(format!("AU{}", public_key))
+    }
+
+    fn validate_address(&self, _address: &str) -> Result<bool> {
+        self.call("validate_address");
+        Ok(true)
+    }
+
+    fn get_time(&self) -> Result<u64> {
+        self.call("get_time");
+        Ok(0)
+    }
+
+    fn unsafe_random(&self) -> Result<i64> {
+        self.call("unsafe_random");
+        Ok(0)
+    }
+
+    fn unsafe_random_f64(&self) -> Result<f64> {
+        self.call("unsafe_random_f64");
+        Ok(0.0)
+    }
+
+    fn unsafe_random_wasmv1(&self, num_bytes: u64) -> Result<Vec<u8>> {
+        self.call("unsafe_random_wasmv1");
+        Ok(vec![0; num_bytes as usize])
+    }
+
+    fn get_current_period(&self) -> Result<u64> {
+        let state = self.call("get_current_period");
+        Ok(state.slot.0)
+    }
+
+    fn get_current_thread(&self) -> Result<u8> {
+        let state = self.call("get_current_thread");
+        Ok(state.slot.1)
+    }
+
+    fn get_current_slot(&self) -> Result<Slot> {
+        let state = self.call("get_current_slot");
+        Ok(Slot {
+            period: state.slot.0,
+            thread: state.slot.1 as u32,
+        })
+    }
+
+    fn get_owned_addresses(&self) -> Result<Vec<String>> {
+        let state = self.call("get_owned_addresses");
+        Ok(vec![state.current_address()?])
+    }
+
+    fn get_call_stack(&self) -> Result<Vec<String>> {
+        let state = self.call("get_call_stack");
+        Ok(state.call_stack.clone())
+    }
+
+    fn generate_event(&self, event: String) -> Result<()> {
+        let mut state = self.call("generate_event");
+        state.events.push(event);
+        Ok(())
+    }
+
+    fn generate_event_wasmv1(&self, event: Vec<u8>) -> Result<()> {
+        let mut state = self.call("generate_event_wasmv1");
+        state
+            .events
+            .push(String::from_utf8_lossy(&event).into_owned());
+        Ok(())
+    }
+
+    fn get_module(&self, bytecode: &[u8], _gas_limit: u64) -> Result<RuntimeModule> {
+        self.call("get_module");
+        RuntimeModule::new(bytecode, GasCosts::default(), Compiler::CL)
+    }
+
+    fn get_tmp_module(&self, bytecode: &[u8], _gas_limit: u64) -> Result<RuntimeModule> {
+        self.call("get_tmp_module");
+        RuntimeModule::new(bytecode, GasCosts::default(), Compiler::SP)
+    }
+
+    fn send_message(
+        &self,
+        _target_address: &str,
+        _target_handler: &str,
+        _validity_start: (u64, u8),
+        _validity_end: (u64, u8),
+        _max_gas: u64,
+        _raw_fee: u64,
+        raw_coins: u64,
+        _data: &[u8],
+        _filter: Option<(&str, Option<&[u8]>)>,
+    ) -> Result<()> {
+        let mut state = self.call("send_message");
+        // the coins are locked in the message, which is never executed
+        let address = state.current_address()?;
+        let balance = state.balances.get(&address).copied().unwrap_or_default();
+        let Some(balance) = balance.checked_sub(raw_coins) else {
+            bail!(
+                "insufficient balance of {} to send {} coins",
+                address,
+                raw_coins
+            );
+        };
+        state.balances.insert(address, balance);
+        Ok(())
+    }
+
+    fn get_origin_operation_id(&self) -> Result<Option<String>> {
+        self.call("get_origin_operation_id");
+        Ok(None)
+    }
+
+    fn hash_sha256(&self, bytes: &[u8]) -> Result<[u8; 32]> {
+        self.call("hash_sha256");
+        Ok(Sha256::digest(bytes).into())
+    }
+
+    fn hash_keccak256(&self, bytes: &[u8]) -> Result<[u8; 32]> {
+        self.call("hash_keccak256");
+        Ok(Keccak256::digest(bytes).into())
+    }
+
+    fn chain_id(&self) -> Result<u64> {
+        self.call("chain_id");
+        Ok(0)
+    }
+
+    fn native_amount_from_str_wasmv1(&self, amount: &str) -> Result<NativeAmount> {
+        self.call("native_amount_from_str_wasmv1");
+        let (integer, decimals) = amount.split_once('.').unwrap_or((amount, ""));
+        if decimals.len() > AMOUNT_DECIMALS as usize {
+            bail!("too many decimals in amount {}", amount);
+        }
+        let integer: u64 = integer.parse()?;
+        let decimals: u64 =
+            format!("{:0<width$}", decimals, width = AMOUNT_DECIMALS as usize).parse()?;
+        integer
+            .checked_mul(10u64.pow(AMOUNT_DECIMALS))
+            .and_then(|raw| raw.checked_add(decimals))
+            .map(from_raw_amount)
+            .ok_or_else(|| anyhow!("amount overflow"))
+    }
+
+    fn native_amount_to_string_wasmv1(&self, amount: &NativeAmount) -> Result<String> {
+        self.call("native_amount_to_string_wasmv1");
+        let raw = to_raw_amount(amount)?;
+        let unit = 10u64.pow(AMOUNT_DECIMALS);
+        let decimals = format!("{:0>width$}", raw % unit, width = AMOUNT_DECIMALS as usize);
+        let decimals = decimals.trim_end_matches('0');
+        if decimals.is_empty() {
+            Ok((raw / unit).to_string())
+        } else {
+            Ok(format!("{}.{}", raw / unit, decimals))
+        }
+    }
+
+    fn check_native_amount_wasmv1(&self, amount: &NativeAmount) -> Result<bool> {
+        self.call("check_native_amount_wasmv1");
+        Ok(to_raw_amount(amount).is_ok())
+    }
+
+    fn add_native_amount_wasmv1(
+        &self,
+        amount1: &NativeAmount,
+        amount2: &NativeAmount,
+    ) -> Result<NativeAmount> {
+        self.call("add_native_amount_wasmv1");
+        to_raw_amount(amount1)?
+            .checked_add(to_raw_amount(amount2)?)
+            .map(from_raw_amount)
+            .ok_or_else(|| anyhow!("amount overflow"))
+    }
+
+    fn sub_native_amount_wasmv1(
+        &self,
+        amount1: &NativeAmount,
+        amount2: &NativeAmount,
+    ) -> Result<NativeAmount> {
+        self.call("sub_native_amount_wasmv1");
+        to_raw_amount(amount1)?
+            .checked_sub(to_raw_amount(amount2)?)
+            .map(from_raw_amount)
+            .ok_or_else(|| anyhow!("amount underflow"))
+    }
+
+    fn scalar_mul_native_amount_wasmv1(
+        &self,
+        amount: &NativeAmount,
+        factor: u64,
+    ) -> Result<NativeAmount> {
+        self.call("scalar_mul_native_amount_wasmv1");
+        to_raw_amount(amount)?
+            .checked_mul(factor)
+            .map(from_raw_amount)
+            .ok_or_else(|| anyhow!("amount overflow"))
+    }
+
+    fn scalar_div_rem_native_amount_wasmv1(
+        &self,
+        dividend: &NativeAmount,
+        divisor: u64,
+    ) -> Result<(NativeAmount, NativeAmount)> {
+        self.call("scalar_div_rem_native_amount_wasmv1");
+        if divisor == 0 {
+            bail!("division by zero");
+        }
+        let dividend = to_raw_amount(dividend)?;
+        Ok((
+            from_raw_amount(dividend / divisor),
+            from_raw_amount(dividend % divisor),
+        ))
+    }
+
+    fn div_rem_native_amount_wasmv1(
+        &self,
+        dividend: &NativeAmount,
+        divisor: &NativeAmount,
+    ) -> Result<(u64, NativeAmount)> {
+        self.call("div_rem_native_amount_wasmv1");
+        let (dividend, divisor) = (to_raw_amount(dividend)?, to_raw_amount(divisor)?);
+        if divisor == 0 {
+            bail!("division by zero");
+        }
+        Ok((dividend / divisor, from_raw_amount(dividend % divisor)))
+    }
+
+    fn check_address_wasmv1(&self, _to_check: &str) -> Result<bool> {
+        self.call("check_address_wasmv1");
+        Ok(true)
+    }
+
+    fn check_pubkey_wasmv1(&self, _to_check: &str) -> Result<bool> {
+        self.call("check_pubkey_wasmv1");
+        Ok(true)
+    }
+
+    fn check_signature_wasmv1(&self, _to_check: &str) -> Result<bool> {
+        self.call("check_signature_wasmv1");
+        Ok(true)
+    }
+
+    fn get_address_category_wasmv1(&self, to_check: &str) -> Result<AddressCategory> {
+        self.call("get_address_category_wasmv1");
+        if to_check.starts_with("AU") {
+            Ok(AddressCategory::UserAddress)
+        } else {
+            Ok(AddressCategory::ScAddress)
+        }
+    }
+
+    fn get_address_version_wasmv1(&self, _address: &str) -> Result<u64> {
+        self.call("get_address_version_wasmv1");
+        Ok(0)
+    }
+
+    fn get_pubkey_version_wasmv1(&self, _pubkey: &str) -> Result<u64> {
+        self.call("get_pubkey_version_wasmv1");
+        Ok(0)
+    }
+
+    fn get_signature_version_wasmv1(&self, _signature: &str) -> Result<u64> {
+        self.call("get_signature_version_wasmv1");
+        Ok(0)
+    }
+
+    fn checked_add_native_time_wasmv1(
+        &self,
+        time1: &NativeTime,
+        time2: &NativeTime,
+    ) -> Result<NativeTime> {
+        self.call("checked_add_native_time_wasmv1");
+        let milliseconds = time1
+            .milliseconds
+            .checked_add(time2.milliseconds)
+            .ok_or_else(|| anyhow!("time overflow"))?;
+        Ok(NativeTime { milliseconds })
+    }
+
+    fn checked_sub_native_time_wasmv1(
+        &self,
+        time1: &NativeTime,
+        time2: &NativeTime,
+    ) -> Result<NativeTime> {
+        self.call("checked_sub_native_time_wasmv1");
+        let milliseconds = time1
+            .milliseconds
+            .checked_sub(time2.milliseconds)
+            .ok_or_else(|| anyhow!("time underflow"))?;
+        Ok(NativeTime { milliseconds })
+    }
+
+    fn checked_mul_native_time_wasmv1(&self, time: &NativeTime, factor: u64) -> Result<NativeTime> {
+        self.call("checked_mul_native_time_wasmv1");
+        let milliseconds = time
+            .milliseconds
+            .checked_mul(factor)
+            .ok_or_else(|| anyhow!("time overflow"))?;
+        Ok(NativeTime { milliseconds })
+    }
+
+    fn checked_scalar_div_native_time_wasmv1(
+        &self,
+        dividend: &NativeTime,
+        divisor: u64,
+    ) -> Result<(NativeTime, NativeTime)> {
+        self.call("checked_scalar_div_native_time_wasmv1");
+        if divisor == 0 {
+            bail!("division by zero");
+        }
+        Ok((
+            NativeTime {
+                milliseconds: dividend.milliseconds / divisor,
+            },
+            NativeTime {
+                milliseconds: dividend.milliseconds % divisor,
+            },
+        ))
+    }
+
+    fn checked_div_native_time_wasmv1(
+        &self,
+        dividend: &NativeTime,
+        divisor: &NativeTime,
+    ) -> Result<(u64, NativeTime)> {
+        self.call("checked_div_native_time_wasmv1");
+        if divisor.milliseconds == 0 {
+            bail!("division by zero");
+        }
+        Ok((
+            dividend.milliseconds / divisor.milliseconds,
+            NativeTime {
+                milliseconds: dividend.milliseconds % divisor.milliseconds,
+            },
+        ))
+    }
+
+    fn base58_check_to_bytes_wasmv1(&self, s: &str) -> Result<Vec<u8>> {
+        self.call("base58_check_to_bytes_wasmv1");
+        Ok(s.as_bytes().to_vec())
+    }
+
+    fn bytes_to_base58_check_wasmv1(&self, bytes: &[u8]) -> String {
+        self.call("bytes_to_base58_check_wasmv1");
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+
+    fn compare_address_wasmv1(&self, left: &str, right: &str) -> Result<ComparisonResult> {
+        self.call("compare_address_wasmv1");
+        Ok(compare(left, right))
+    }
+
+    fn compare_native_amount_wasmv1(
+        &self,
+        left: &NativeAmount,
+        right: &NativeAmount,
+    ) -> Result<ComparisonResult> {
+        self.call("compare_native_amount_wasmv1");
+        Ok(compare(to_raw_amount(left)?, to_raw_amount(right)?))
+    }
+
+    fn compare_native_time_wasmv1(
+        &self,
+        left: &NativeTime,
+        right: &NativeTime,
+    ) -> Result<ComparisonResult> {
+        self.call("compare_native_time_wasmv1");
+        Ok(compare(left.milliseconds, right.milliseconds))
+    }
+
+    fn compare_pub_key_wasmv1(&self, left: &str, right: &str) -> Result<ComparisonResult> {
+        self.call("compare_pub_key_wasmv1");
+        Ok(compare(left, right))
+    }
+
+    fn save_gas_remaining_before_subexecution(&self, _gas_used_until: u64) {
+        self.call("save_gas_remaining_before_subexecution");
+    }
+}
@@ -6,8 +6,14 @@ use anyhow::Result;
 use massa_proto_rs::massa::model::v1::*;
 use sha2::{Digest, Sha256};
 use sha3::Keccak256;
+use std::cell::RefCell;
 use std::collections::{BTreeMap, BTreeSet};
 
+thread_local! {
+    /// Datastore of the current address, each test running in its own thread
+    static DATASTORE: RefCell<BTreeMap<Vec<u8>, Vec<u8>>> = Default::default();
+}
+
 #[derive(Clone)]
 struct TestInterface;
 
@@ -131,7 +137,7 @@ impl Interface for TestInterface {
 
     fn has_data(&self, key: &[u8]) -> Result<bool> {
         println!("Has data at {:?}", key);
-        Ok(false)
+        Ok(DATASTORE.with(|datastore| datastore.borrow().contains_key(key)))
     }
 
     fn has_data_for(&self, address: &str, key: &[u8]) -> Result<bool> {
@@ -221,6 +227,7 @@ impl Interface for TestInterface {
 
     fn raw_delete_data(&self, key: &[u8]) -> Result<()> {
         println!("Raw delete data at {:?}", key);
+        DATASTORE.with(|datastore| datastore.borrow_mut().remove(key));
         Ok(())
     }
 
@@ -243,7 +250,7 @@ impl Interface for TestInterface {
 
     fn raw_get_data(&self, key: &[u8]) -> Result<Vec<u8>> {
         println!("Raw get data at {:?}", key);
-        Ok(vec![])
+        Ok(DATASTORE.with(|datastore| datastore.borrow().get(key).cloned().unwrap_or_default()))
     }
 
     fn raw_get_data_for(&self, address: &str, key: &[u8]) -> Result<Vec<u8>> {
@@ -265,6 +272,7 @@ impl Interface for TestInterface {
 
     fn raw_set_data(&self, key: &[u8], value: &[u8]) -> Result<()> {
         println!("Raw set data at {:?} with value {:?}", key, value);
+        DATASTORE.with(|datastore| datastore.borrow_mut().insert(key.to_vec(), value.to_vec()));
         Ok(())
     }
 
@@ -1,16 +1,25 @@
-use crate::as_execution::{ASContext, ASModule};
+use crate::as_execution::{ASContext, ASEnv, ASModule, InstanceSnapshot};
+use crate::delegate::Recordable;
 use crate::tests::TestInterface;
 use crate::Compiler;
 use crate::{
-    run_function, run_main,
-    types::{GasCosts, Interface},
-    RuntimeModule,
+    record_execution, replay, run_batch, run_constructor, run_estimate, run_function,
+    run_function_with_extra_imports, run_function_with_memory_snapshot, run_function_with_options,
+    run_function_with_shared_param, run_main,
+    types::{CompilationLimits, GasCosts, Interface, RunOptions},
+    ExecutionTrace, MockInterface, PlannedEffect, RunRequest, RuntimeModule, VMError,
+};
+use massa_proto_rs::massa::model::v1::{
+    AddressCategory, ComparisonResult, NativeAmount, NativeTime,
 };
 use rand::Rng;
 use serial_test::serial;
+use std::time::Duration;
 use wasmer::Store;
 use wasmer::WasmPtr;
 
+#[cfg(feature = "trap-metrics")]
+use crate::TRAP_STATS;
 #[cfg(feature = "execution-trace")]
 use crate::{AbiTrace, AbiTraceType, AbiTraceValue};
 
@@ -497,233 +506,2171 @@ fn test_run_function() {
     run_function(&*interface, runtime_module, "ping", b"", 100_000, gas_costs).unwrap();
 }
 
-// NOTE: this test is outdated as module are now pre-compiled with max_instance_cost
-// leaving this for documentation purposes
-// #[test]
-// #[serial]
-// /// Test both cases of the not enough gas error
-// fn test_not_enough_gas_error() {
-//     let gas_costs = GasCosts::default();
-//     let interface: Box<dyn Interface> = Box::new(TestInterface);
-//     let module = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/wasm/basic_main.wasm"));
-
-//     // Test giving not enough gas to create the instance
-//     let runtime_module = RuntimeModule::new(module, 100, gas_costs.clone(), Compiler::SP).unwrap();
-//     let error = run_main(&*interface, runtime_module, 100_000, gas_costs.clone())
-//         .unwrap_err()
-//         .to_string();
-//     assert!(
-//         error.contains("Not enough gas, limit reached at initialization")
-//             || error.contains("RuntimeError: unreachable")
-//     );
-
-//     // Test giving enough gas to create the instance but not enough for the VM
-//     let runtime_module =
-//         RuntimeModule::new(module, 100_000, gas_costs.clone(), Compiler::SP).unwrap();
-//     let error = run_main(&*interface, runtime_module, 100, gas_costs)
-//         .unwrap_err()
-//         .to_string();
-//     assert!(error.contains("Not enough gas to launch the virtual machine"));
-// }
-
 #[test]
 #[serial]
-/// Test that a no-main SC executed through `run_main` fails as expected
-fn test_run_main_without_main() {
+/// Test running a function several times on a single instance
+fn test_run_function_with_shared_param() {
     let gas_costs = GasCosts::default();
     let interface: Box<dyn Interface> = Box::new(TestInterface);
-    let module = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/wasm/no_main.wasm"));
+    let module = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/wasm/basic_func.wasm"));
+
     let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
-    run_main(&*interface, runtime_module, 100_000, gas_costs)
-        .expect_err("An error should spawn here");
+    let responses = run_function_with_shared_param(
+        &*interface,
+        runtime_module,
+        "ping",
+        b"",
+        3,
+        100_000,
+        gas_costs,
+    )
+    .unwrap();
+    assert_eq!(responses.len(), 3);
+    assert!(responses.iter().all(|resp| resp.ret == responses[0].ret));
 }
 
 #[test]
 #[serial]
-/// Even if our SC is empty there is still an initial and minimum metering cost,
-/// because we have a memory allocator to init.
-///
-/// This test ensure that this initial cost is correctly debited.
-fn test_run_empty_main() {
-    let mut gas_costs = GasCosts::default();
+/// Test that the per-execution budgets do not leak from one run of a shared
+/// instance to the next
+fn test_shared_param_runs_are_reset() {
+    let gas_costs = GasCosts {
+        max_total_event_bytes: 5,
+        ..Default::default()
+    };
     let interface: Box<dyn Interface> = Box::new(TestInterface);
-    let module = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/wasm/empty_main.wasm"));
-    gas_costs.launch_cost = 0;
-    let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
-    let a = run_main(
-        &*interface,
-        runtime_module.clone(),
-        10_000_000,
-        gas_costs.clone(),
+    // generates a 3 bytes event, twice over the limit in a single execution
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_generate_event" (func $generate_event (param i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 12) "\06\00\00\00a\00b\00c\00")
+            (func (export "__new") (param i32 i32) (result i32) (i32.const 1024))
+            (func (export "run")
+                (call $generate_event (i32.const 16))))"#,
     )
-    .expect("Failed to run empty_main.wasm");
-    // Here we avoid hard-coding a value (that can change in future wasmer
-    // release)
-    assert!(a.remaining_gas > 0);
+    .unwrap();
 
-    let mut rng = rand::thread_rng();
-    let cost = rng.gen_range(1..1_000_000);
-    gas_costs.launch_cost = cost;
-    let b = run_main(&*interface, runtime_module, 10_000_000, gas_costs)
-        .expect("Failed to run empty_main.wasm");
-    // Between 2 calls, the metering cost should be the difference
-    assert_eq!(a.remaining_gas - b.remaining_gas, cost);
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    let responses = run_function_with_shared_param(
+        &*interface,
+        runtime_module,
+        "run",
+        b"",
+        3,
+        100_000,
+        gas_costs,
+    )
+    .unwrap();
+    assert!(responses
+        .iter()
+        .all(|response| response.events == vec!["abc".to_string()]));
 }
 
 #[test]
 #[serial]
-/// Even if our SC is empty there is still an initial and minimum metering cost,
-/// because we have a memory allocator to init.
-///
-/// This test ensure that this initial cost is correctly debited.
-fn test_run_main_rust_wasmv1() {
-    // let mut gas_costs = GasCosts::default();
-    // let interface: Box<dyn Interface> =
-    //     Box::new(TestInterface);
-    // let module = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"),
-    //     "/../massa-rust-sc-examples/target/wasm32-unknown-unknown/debug/
-    // massa_rust_sc_deploy_sc.wasm_add")); gas_costs.launch_cost = 0;
-    // let runtime_module =
-    //     RuntimeModule::new(module, gas_costs.clone(), Compiler::SP)
-    //         .unwrap();
-
-    // let a = match run_main(
-    //     &*interface,
-    //     runtime_module.clone(),
-    //     10_000_000,
-    //     gas_costs.clone(),
-    // ) { Ok(a) => a, Err(e) => { println!("e: {}", e); panic!("Failed to run
-    //   main") }
-    // };
-
-    // // Here we avoid hard-coding a value (that can change in future wasmer
-    // // release)
-    // assert!(a.remaining_gas > 0);
+/// Test that a failing shared-param run reports its error like a single run
+fn test_shared_param_abort_info() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "env" "abort" (func $abort (param i32 i32 i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 12) "\08\00\00\00b\00o\00o\00m\00")
+            (data (i32.const 36) "\08\00\00\00a\00.\00t\00s\00")
+            (func (export "__new") (param i32 i32) (result i32) (i32.const 1024))
+            (func (export "run")
+                (call $abort (i32.const 16) (i32.const 40) (i32.const 3) (i32.const 7))))"#,
+    )
+    .unwrap();
 
-    // let mut rng = rand::thread_rng();
-    // let cost = rng.gen_range(1..1_000_000);
-    // gas_costs.launch_cost = cost;
-    // let b = run_main(&*interface, runtime_module, 10_000_000, gas_costs)
-    //     .expect("Failed to run empty_main.wasm");
-    // dbg!(b.ret);
-    // // Between 2 calls, the metering cost should be the difference
-    // // assert_eq!(a.remaining_gas - b.remaining_gas, cost);
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    match run_function_with_shared_param(
+        &*interface,
+        runtime_module,
+        "run",
+        b"",
+        2,
+        100_000,
+        gas_costs,
+    ) {
+        Err(VMError::ExecutionError { abort, .. }) => {
+            let abort = abort.expect("abort info not captured");
+            assert_eq!(abort.message, "boom");
+            assert_eq!((abort.line, abort.column), (3, 7));
+        }
+        _ => panic!("expected an execution error"),
+    }
 }
 
 #[test]
 #[serial]
-/// Test the operation datastore
-///
-/// * getOpKeys
-/// * hasOpKey
-/// * getOpData
-fn test_op_fn() {
+/// Record an execution, round-trip its trace through JSON and replay it
+fn test_record_and_replay_execution() {
     let gas_costs = GasCosts::default();
     let interface: Box<dyn Interface> = Box::new(TestInterface);
-    let module = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/wasm/op_fn.wasm"));
-    let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
-    run_main(&*interface, runtime_module, 10_000_000, gas_costs.clone())
-        .expect("Failed to run op_fn.wasm");
+    let module = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/wasm/basic_func.wasm"));
+
+    let (recorded, trace) = record_execution(
+        &*interface,
+        module,
+        "ping",
+        b"",
+        100_000,
+        gas_costs,
+        RunOptions::default(),
+    );
+    let recorded = recorded.unwrap();
+    let trace: ExecutionTrace =
+        serde_json::from_str(&serde_json::to_string(&trace).unwrap()).unwrap();
+    let replayed = replay(&trace).unwrap();
+    assert_eq!(recorded.ret, replayed.ret);
+    assert_eq!(recorded.remaining_gas, replayed.remaining_gas);
+
+    let mut tampered = trace;
+    tampered.bytecode.push(0);
+    assert!(replay(&tampered).is_err());
 }
 
-/// Test `seed`, `Date.now`, `console.log` and `abort`
-///
-/// These are AS functions that we choose to handle in the VM
 #[test]
-#[serial]
-fn test_builtins() {
-    let gas_costs = GasCosts::default();
-    let interface: Box<dyn Interface> = Box::new(TestInterface);
-    let module = include_bytes!(concat!(
-        env!("CARGO_MANIFEST_DIR"),
-        "/wasm/use_builtins.wasm"
-    ));
-    let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
-    // let before = chrono::offset::Utc::now().timestamp_millis();
-    match run_main(&*interface, runtime_module, 10_000_000, gas_costs.clone()) {
-        Err(e) => {
-            let msg = e.to_string();
-            // make sure the error was caused by a manual abort
-            assert!(msg.contains("Manual abort"), "Error was: {:?}", e);
-            assert!(msg.contains("at use_builtins.ts"), "Error was: {:?}", e);
-            // check the given timestamp validity
-            // let after = chrono::offset::Utc::now().timestamp_millis();
-            // let ident = "UTC timestamp (ms) = ";
-            // let start =
-            // msg.find(ident).unwrap_or(0).saturating_add(ident.len());
-            // let end = msg.find(" at use_builtins.ts").unwrap_or(0);
-            // let sc_timestamp: i64 = msg[start..end].parse().unwrap();
-            // assert!(before <= sc_timestamp && sc_timestamp <= after);
-        }
-        _ => panic!("Failed to run use_builtins.wasm"),
+/// Test that the WasmV1 responses round-trip through their recorded form
+fn test_recordable_wasmv1_responses() {
+    let amount = NativeAmount {
+        mantissa: 1_500,
+        scale: 9,
+    };
+    let time = NativeTime { milliseconds: 42 };
+    let (rem_amount, rem_time): (NativeAmount, NativeTime) =
+        Recordable::from_record((amount.clone(), time.clone()).to_record()).unwrap();
+    assert_eq!((rem_amount, rem_time), (amount, time));
+    let comparison: ComparisonResult =
+        Recordable::from_record(ComparisonResult::Greater.to_record()).unwrap();
+    assert_eq!(comparison, ComparisonResult::Greater);
+    let category: AddressCategory =
+        Recordable::from_record(AddressCategory::ScAddress.to_record()).unwrap();
+    assert_eq!(category, AddressCategory::ScAddress);
+    assert!(<RuntimeModule as Recordable>::from_record(serde_json::Value::Null).is_err());
+}
+
+#[test]
+/// Test that modules declaring too many locals in a function are rejected
+fn test_max_locals_per_function() {
+    let gas_costs = GasCosts {
+        max_locals_per_function: 2,
+        ..Default::default()
+    };
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (func (local i32 i32))
+            (func (local i32) (local i64 i64)))"#,
+    )
+    .unwrap();
+
+    match RuntimeModule::new(&module, gas_costs, Compiler::SP) {
+        Err(err) => assert_eq!(
+            err.to_string(),
+            "Function 1 declares 3 locals, the limit is 2"
+        ),
+        Ok(_) => panic!("module exceeding the locals limit was compiled"),
     }
 }
 
 #[test]
 #[serial]
-/// Test `assert` & `process.exit
-///
-/// These are AS functions that we choose to handle in the VM
-fn test_builtin_assert_and_exit() {
+/// Test that the frames of a trap are captured with verbose traps enabled
+fn test_verbose_traps() {
     let gas_costs = GasCosts::default();
     let interface: Box<dyn Interface> = Box::new(TestInterface);
-    let module = include_bytes!(concat!(
-        env!("CARGO_MANIFEST_DIR"),
-        "/wasm/use_builtin_assert.wasm"
-    ));
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1)
+            (func $fail unreachable)
+            (func (export "main") call $fail))"#,
+    )
+    .unwrap();
 
-    let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
-    match run_function(
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    let options = RunOptions {
+        verbose_traps: true,
+        ..Default::default()
+    };
+    match run_function_with_options(
         &*interface,
         runtime_module,
-        "assert_with_msg",
+        "main",
         b"",
         100_000,
-        gas_costs.clone(),
+        gas_costs,
+        options,
     ) {
-        Err(e) => {
-            assert!(e.to_string().contains("Result is not true!"))
+        Err(VMError::ExecutionError { trap_dump, .. }) => {
+            let frames = trap_dump.expect("trap dump not captured").frames;
+            assert_eq!(frames.first().map(|frame| frame.func_index), Some(0));
+            assert_eq!(frames.get(1).map(|frame| frame.func_index), Some(1));
         }
-        _ => panic!("test should return an error!"),
+        _ => panic!("expected an execution error"),
+    }
+}
+
+#[test]
+/// Test that storing a bytecode costs proportionally to its size
+fn test_bytecode_storage_cost() {
+    let gas_costs = GasCosts {
+        bytecode_storage_byte_cost: 3,
+        ..Default::default()
+    };
+    assert_eq!(gas_costs.bytecode_storage_cost(0), 0);
+    assert_eq!(gas_costs.bytecode_storage_cost(1_000), 3_000);
+    assert_eq!(
+        gas_costs.bytecode_storage_cost(2_000),
+        2 * gas_costs.bytecode_storage_cost(1_000)
+    );
+    assert_eq!(GasCosts::default().bytecode_storage_cost(1_000), 0);
+}
+
+#[test]
+#[serial]
+/// Test that the time spent in host functions is only reported when profiling
+fn test_profile_abis() {
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_get_balance" (func $balance (result i64)))
+            (memory (export "memory") 1)
+            (func (export "main") call $balance drop call $balance drop))"#,
+    )
+    .unwrap();
+
+    let gas_costs = GasCosts::default();
+    for profile_abis in [false, true] {
+        let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+        let options = RunOptions {
+            profile_abis,
+            ..Default::default()
+        };
+        let response = run_function_with_options(
+            &*interface,
+            runtime_module,
+            "main",
+            b"",
+            100_000,
+            gas_costs.clone(),
+            options,
+        )
+        .unwrap();
+        assert_eq!(
+            response
+                .abi_timings
+                .contains_key("assembly_script_get_balance"),
+            profile_abis
+        );
     }
+}
 
-    let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
-    if let Ok(_) = run_function(
-        &*interface,
-        runtime_module,
-        "assert_no_msg",
-        b"",
-        100_000,
-        gas_costs.clone(),
-    ) {
-        panic!("test should return an error!");
+#[test]
+/// Test that many small events cannot exceed the cumulative event size limit
+fn test_max_total_event_bytes() {
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let gas_costs = GasCosts {
+        max_total_event_bytes: 1_000,
+        ..Default::default()
+    };
+    let mut env = ASEnv::new(&*interface, gas_costs);
+    for _ in 0..100 {
+        env.consume_event_budget(10).unwrap();
     }
+    env.consume_event_budget(1).unwrap_err();
+}
 
-    let module = include_bytes!(concat!(
-        env!("CARGO_MANIFEST_DIR"),
-        "/wasm/use_builtin_exit.wasm"
-    ));
+#[test]
+/// Test that modules exceeding the compilation limits are rejected
+fn test_compilation_limits() {
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "f" (func))
+            (global i32 (i32.const 0))
+            (func)
+            (func (drop (i32.const 1))))"#,
+    )
+    .unwrap();
+    let error = |compilation_limits: CompilationLimits| {
+        let gas_costs = GasCosts {
+            compilation_limits,
+            ..Default::default()
+        };
+        RuntimeModule::new(&module, gas_costs, Compiler::SP)
+            .err()
+            .map(|err| err.to_string())
+    };
 
-    let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
-    match run_function(
+    assert_eq!(error(Default::default()), None);
+    let limits = CompilationLimits::default;
+    assert_eq!(
+        error(CompilationLimits {
+            max_functions: 1,
+            ..limits()
+        }),
+        Some("Module declares 2 functions, the limit is 1".to_string())
+    );
+    assert_eq!(
+        error(CompilationLimits {
+            max_imports: 0,
+            ..limits()
+        }),
+        Some("Module declares 1 imports, the limit is 0".to_string())
+    );
+    assert!(error(CompilationLimits {
+        max_function_body_size: 3,
+        ..limits()
+    })
+    .unwrap()
+    .starts_with("Function 2 body is"));
+}
+
+#[test]
+#[serial]
+/// Test the injection of experimental host functions
+fn test_run_function_with_extra_imports() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "experimental_answer" (func $answer (result i32)))
+            (memory (export "memory") 1)
+            (func (export "main") call $answer drop))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    run_function_with_extra_imports(
         &*interface,
         runtime_module,
-        "exit_no_code",
+        "main",
         b"",
         100_000,
         gas_costs.clone(),
-    ) {
-        Err(e) => {
-            assert!(e.to_string().contains("exit with code: 0"))
-        }
-        _ => panic!("test should return an error!"),
-    }
+        |store, _| {
+            vec![(
+                "massa".to_string(),
+                "experimental_answer".to_string(),
+                wasmer::Function::new_typed(store, || 42_i32),
+            )]
+        },
+    )
+    .unwrap();
 
-    let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
-    match run_function(
+    // core imports cannot be shadowed
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    let res = run_function_with_extra_imports(
         &*interface,
         runtime_module,
-        "exit_with_code",
+        "main",
+        b"",
+        100_000,
+        gas_costs,
+        |store, _| {
+            vec![(
+                "massa".to_string(),
+                "assembly_script_print".to_string(),
+                wasmer::Function::new_typed(store, || 42_i32),
+            )]
+        },
+    );
+    assert!(res.is_err());
+}
+
+#[test]
+#[serial]
+/// Test that a launch cost above the remaining gas returns a typed error
+fn test_insufficient_launch_gas() {
+    let mut gas_costs = GasCosts::default();
+    gas_costs.launch_cost = 1_000_000_000;
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/wasm/basic_func.wasm"));
+
+    let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
+    match run_function(&*interface, runtime_module, "ping", b"", 100_000, gas_costs) {
+        Err(VMError::InsufficientLaunchGas {
+            required,
+            available,
+            init_gas_cost,
+        }) => {
+            assert_eq!(required, 1_000_000_000);
+            assert_eq!(available, 100_000 - init_gas_cost);
+        }
+        _ => panic!("expected an insufficient launch gas error"),
+    }
+}
+
+#[test]
+#[serial]
+fn test_run_function_with_memory_snapshot() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/wasm/basic_func.wasm"));
+
+    let runtime_module =
+        RuntimeModule::new_for_snapshot(module, gas_costs.clone(), Compiler::SP).unwrap();
+    let responses = run_function_with_memory_snapshot(
+        &*interface,
+        runtime_module,
+        "ping",
+        b"",
+        3,
+        100_000,
+        gas_costs,
+    )
+    .unwrap();
+    assert_eq!(responses.len(), 3);
+    assert!(responses.iter().all(|resp| resp.ret == responses[0].ret));
+}
+
+#[test]
+#[serial]
+/// Test that only the modules compiled for snapshots can be snapshotted
+fn test_memory_snapshot_opt_in() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1)
+            (global $g (mut i32) (i32.const 0))
+            (func (export "run")))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    match run_function_with_memory_snapshot(
+        &*interface,
+        runtime_module,
+        "run",
+        b"",
+        2,
+        100_000,
+        gas_costs.clone(),
+    ) {
+        Err(err) => assert!(err
+            .to_string()
+            .contains("module compiled without its globals exported")),
+        Ok(_) => panic!("expected a module compiled without its globals exported to fail"),
+    }
+
+    let wasmv1_module = [&[1u8][..], &module].concat();
+    assert!(RuntimeModule::new_for_snapshot(&wasmv1_module, gas_costs, Compiler::SP).is_err());
+}
+
+#[test]
+#[serial]
+/// Test that batched calls share their gas budget and fail independently
+fn test_run_batch() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/wasm/basic_func.wasm"));
+    let request = |function: &str| RunRequest {
+        rt_module: RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap(),
+        function: function.to_string(),
+        param: Vec::new(),
+        limit: 100_000,
+    };
+
+    let responses = run_batch(
+        &*interface,
+        vec![request("ping"), request("missing"), request("ping")],
+        250_000,
+        gas_costs.clone(),
+    );
+    assert_eq!(responses.len(), 3);
+    assert!(responses[0].as_ref().unwrap().is_ok());
+    assert!(responses[1].as_ref().unwrap().is_err());
+    assert!(responses[2].as_ref().unwrap().is_ok());
+
+    // the failed call consumes its whole limit
+    let responses = run_batch(
+        &*interface,
+        vec![request("missing"), request("missing"), request("ping")],
+        200_000,
+        gas_costs,
+    );
+    assert!(responses[0].is_some());
+    assert!(responses[1].is_some());
+    assert!(responses[2].is_none());
+}
+
+#[test]
+/// Test that reads reflect the conditional writes made before them
+fn test_read_after_conditional_write() {
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+
+    assert!(interface.set_data_if_absent(b"a", b"1").unwrap());
+    assert_eq!(interface.raw_get_data(b"a").unwrap(), b"1");
+    assert!(!interface.set_data_if_absent(b"a", b"2").unwrap());
+    assert_eq!(interface.raw_get_data(b"a").unwrap(), b"1");
+
+    interface.swap_data(b"a", b"b").unwrap();
+    assert!(!interface.has_data(b"a").unwrap());
+    assert_eq!(interface.raw_get_data(b"b").unwrap(), b"1");
+    assert!(interface.set_data_if_absent(b"a", b"2").unwrap());
+
+    interface.swap_data(b"a", b"b").unwrap();
+    assert_eq!(interface.raw_get_data(b"a").unwrap(), b"1");
+    assert_eq!(interface.raw_get_data(b"b").unwrap(), b"2");
+}
+
+#[test]
+#[serial]
+/// Test that reading a balance aborts a pure execution
+fn test_pure_execution() {
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_get_balance" (func $balance (result i64)))
+            (memory (export "memory") 1)
+            (func (export "main") call $balance drop))"#,
+    )
+    .unwrap();
+
+    let gas_costs = GasCosts::default();
+    for pure in [false, true] {
+        let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+        let options = RunOptions {
+            pure,
+            ..Default::default()
+        };
+        let res = run_function_with_options(
+            &*interface,
+            runtime_module,
+            "main",
+            b"",
+            100_000,
+            gas_costs.clone(),
+            options,
+        );
+        assert_eq!(res.is_err(), pure);
+    }
+}
+
+#[test]
+#[serial]
+/// Test that out-of-gas can be forced after a given number of operators
+fn test_force_oog_after() {
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1)
+            (func (export "main") i32.const 1 drop i32.const 2 drop))"#,
+    )
+    .unwrap();
+
+    for (force_oog_after, oog) in [(100, false), (2, true)] {
+        let gas_costs = GasCosts {
+            force_oog_after: Some(force_oog_after),
+            ..Default::default()
+        };
+        let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+        match run_main(&*interface, runtime_module, 100_000, gas_costs) {
+            Ok(_) => assert!(!oog),
+            Err(err) => assert!(oog && err.to_string().contains("Not enough gas")),
+        }
+    }
+}
+
+#[test]
+#[serial]
+/// Test that a loop can stop once the remaining gas is below a threshold
+fn test_has_gas_at_least() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_has_gas_at_least" (func $has_gas (param i64) (result i32)))
+            (memory (export "memory") 1)
+            (func (export "main")
+                (loop $work
+                    (br_if $work (call $has_gas (i64.const 50000))))))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    let response = run_main(&*interface, runtime_module, 100_000, gas_costs).unwrap();
+    assert!(response.remaining_gas < 50_000);
+    assert!(response.remaining_gas > 0);
+}
+
+#[test]
+#[serial]
+/// Test that operators listed in the cost table are charged their own cost
+fn test_operator_costs() {
+    let gas_costs = GasCosts {
+        operator_costs: [("MemoryGrow".to_string(), 1_000)].into(),
+        ..Default::default()
+    };
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let gas_used = |body: &str| {
+        let module = wasmer::wat2wasm(
+            format!(r#"(module (memory (export "memory") 1) (func (export "main") {body}))"#)
+                .as_bytes(),
+        )
+        .unwrap();
+        let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+        let response = run_main(&*interface, runtime_module, 100_000, gas_costs.clone()).unwrap();
+        100_000 - response.remaining_gas
+    };
+
+    let grow = "(drop (memory.grow (i32.const 0)))";
+    let add = "(drop (i32.add (i32.const 1) (i32.const 2)))";
+    assert!(gas_used(&grow.repeat(10)) > gas_used(&add.repeat(10)));
+}
+
+#[test]
+#[serial]
+/// Test that a parameter given to a function taking none is rejected when
+/// configured to
+fn test_run_function_unexpected_param() {
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1)
+            (func (export "noarg")))"#,
+    )
+    .unwrap();
+
+    let run = |gas_costs: GasCosts, param: &[u8]| {
+        let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+        run_function(
+            &*interface,
+            runtime_module,
+            "noarg",
+            param,
+            100_000,
+            gas_costs,
+        )
+    };
+    // ignored by default
+    run(GasCosts::default(), b"param").unwrap();
+
+    let gas_costs = GasCosts {
+        reject_unexpected_param: true,
+        ..Default::default()
+    };
+    run(gas_costs.clone(), b"").unwrap();
+    assert!(run(gas_costs, b"param")
+        .unwrap_err()
+        .to_string()
+        .contains("Function noarg takes no parameter but one was given"));
+}
+
+#[test]
+#[serial]
+/// Test that a parameter over `max_param_size` is rejected before allocation
+fn test_max_param_size() {
+    let mut gas_costs = GasCosts::default();
+    gas_costs.max_param_size = 8;
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1)
+            (func (export "__new") (param i32 i32) (result i32)
+                (i32.store (i32.const 32764) (local.get 0))
+                (i32.const 32768))
+            (func (export "arg") (param i32)))"#,
+    )
+    .unwrap();
+
+    let run = |param: &[u8]| {
+        let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+        run_function(
+            &*interface,
+            runtime_module,
+            "arg",
+            param,
+            100_000,
+            gas_costs.clone(),
+        )
+    };
+    run(&[0; 8]).unwrap();
+    assert!(run(&[0; 16])
+        .unwrap_err()
+        .to_string()
+        .contains("Parameter of 16 bytes exceeds the maximum size of 8 bytes"));
+}
+
+/// Build a module whose `main` function runs the `lib` function of the given
+/// library with `assembly_script_local_execution`
+fn local_execution_caller(library: &[u8]) -> Vec<u8> {
+    let library_data: String = library.iter().map(|b| format!("\\{:02x}", b)).collect();
+    let library_len: String = (library.len() as u32)
+        .to_le_bytes()
+        .iter()
+        .map(|b| format!("\\{:02x}", b))
+        .collect();
+    // caller: runs the `lib` function of the library in its own context
+    wasmer::wat2wasm(
+        format!(
+            r#"(module
+            (import "massa" "assembly_script_local_execution" (func $local_execution (param i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 12) "\06\00\00\00l\00i\00b\00")
+            (data (i32.const 28) "\00\00\00\00")
+            (data (i32.const 60) "{library_len}{library_data}")
+            (func (export "__new") (param i32 i32) (result i32) (i32.const 32768))
+            (func (export "main")
+                (drop (call $local_execution (i32.const 64) (i32.const 16) (i32.const 32)))))"#
+        )
+        .as_bytes(),
+    )
+    .unwrap()
+}
+
+#[test]
+#[serial]
+/// Test that a library executed with `local_execution` writes to the
+/// datastore of the caller
+fn test_local_execution_writes_caller_datastore() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    // library: writes `key` = `key` in the datastore of the current address
+    let library = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_set_data" (func $set_data (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 12) "\03\00\00\00key")
+            (func (export "lib") (call $set_data (i32.const 16) (i32.const 16))))"#,
+    )
+    .unwrap();
+    let caller = local_execution_caller(&library);
+
+    interface.raw_delete_data(b"key").unwrap();
+    let runtime_module = RuntimeModule::new(&caller, gas_costs.clone(), Compiler::SP).unwrap();
+    run_main(&*interface, runtime_module, 10_000_000, gas_costs).unwrap();
+    assert_eq!(interface.raw_get_data(b"key").unwrap(), b"key");
+}
+
+#[test]
+#[serial]
+/// Test that nested calls are limited by `GasCosts::max_call_depth`
+fn test_max_call_depth() {
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let library = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1)
+            (func (export "lib")))"#,
+    )
+    .unwrap();
+    let caller = local_execution_caller(&library);
+
+    let run = |max_call_depth: u32| {
+        let gas_costs = GasCosts {
+            max_call_depth,
+            ..GasCosts::default()
+        };
+        let runtime_module = RuntimeModule::new(&caller, gas_costs.clone(), Compiler::SP).unwrap();
+        run_main(&*interface, runtime_module, 10_000_000, gas_costs)
+    };
+    run(1).unwrap();
+    let err = run(0).unwrap_err();
+    assert!(err.to_string().contains("maximum call depth exceeded"));
+}
+
+#[test]
+#[serial]
+/// Test the gas consumption breakdown of the response
+fn test_response_gas_used() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_has_gas_at_least" (func $has_gas (param i64) (result i32)))
+            (memory (export "memory") 1)
+            (func (export "main")
+                (drop (call $has_gas (i64.const 0)))
+                (drop (call $has_gas (i64.const 0)))))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    let response = run_main(&*interface, runtime_module, 100_000, gas_costs.clone()).unwrap();
+    assert_eq!(response.gas_used, 100_000 - response.remaining_gas);
+    assert_eq!(
+        response.abi_gas,
+        Some(2 * gas_costs.abi_costs["assembly_script_has_gas_at_least"])
+    );
+    assert!(response.gas_used > response.abi_gas.unwrap());
+}
+
+#[test]
+#[serial]
+/// Test the per-execution memory pages limit
+fn test_memory_pages_limit() {
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1)
+            (func (export "main")
+                (if (i32.eq (memory.grow (i32.const 2)) (i32.const -1))
+                    (then unreachable))))"#,
+    )
+    .unwrap();
+
+    let run = |memory_pages_limit: u32| {
+        let gas_costs = GasCosts {
+            memory_pages_limit: Some(memory_pages_limit),
+            ..Default::default()
+        };
+        let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+        run_main(&*interface, runtime_module, 100_000, gas_costs)
+    };
+    assert!(run(2).is_err());
+    run(3).unwrap();
+}
+
+#[test]
+#[serial]
+/// Test that a module declaring an initial memory over the memory pages limit
+/// is rejected at instantiation
+fn test_memory_pages_limit_minimum() {
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let gas_costs = GasCosts {
+        memory_pages_limit: Some(10),
+        ..Default::default()
+    };
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1000)
+            (func (export "main")))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    match run_main(&*interface, runtime_module, 100_000, gas_costs) {
+        Err(VMError::InstanceError(error)) => assert!(error
+            .contains("Minimum memory of 1000 pages exceeds the allowed memory limit of 10 pages")),
+        _ => panic!("expected an instance error"),
+    }
+}
+
+#[test]
+#[serial]
+/// Test that resetting an env clears the per-execution state only
+fn test_env_reset() {
+    let options = RunOptions {
+        pure: true,
+        ..Default::default()
+    };
+    let mut env = ASEnv::with_options(&TestInterface, GasCosts::default(), options);
+    env.consume_event_budget(10).unwrap();
+    env.savepoints.push(42);
+    env.start_points = Some(1_000);
+
+    env.reset();
+    assert_eq!(env.event_bytes, 0);
+    assert!(env.savepoints.is_empty());
+    assert_eq!(env.start_points, None);
+    assert!(env.options.pure);
+}
+
+#[test]
+/// Test loading and validating gas costs from JSON
+fn test_gas_costs_from_json() {
+    let json = serde_json::to_vec(&GasCosts::default()).unwrap();
+    GasCosts::from_json_slice(&json).unwrap();
+
+    let mut gas_costs = GasCosts {
+        operator_cost: 0,
+        ..Default::default()
+    };
+    let json = serde_json::to_vec(&gas_costs).unwrap();
+    assert!(GasCosts::from_json_slice(&json).is_err());
+
+    gas_costs.operator_cost = 1;
+    gas_costs.abi_costs.remove("assembly_script_set_data");
+    let json = serde_json::to_vec(&gas_costs).unwrap();
+    let err = GasCosts::from_json_slice(&json).unwrap_err();
+    assert!(err.to_string().contains("assembly_script_set_data"));
+}
+
+#[test]
+/// Test loading the operator costs from the `operator:` entries of the ABI
+/// cost file
+fn test_gas_costs_operator_costs_from_file() {
+    let dir = std::env::temp_dir();
+    let abi_cost_file = dir.join("test_operator_costs_abi_gas_costs.json");
+    let wasm_abi_file = dir.join("test_operator_costs_wasm_gas_costs.json");
+    std::fs::write(&wasm_abi_file, r#"{"Wasm:I32Add": 2, "Wasm:Drop": 4}"#).unwrap();
+    let write_abi_costs = |operator_entry: &str| {
+        std::fs::write(
+            &abi_cost_file,
+            format!(
+                r#"{{"launch": 10, "cl_compilation": 10, "sp_compilation": 10,
+                    "max_instance": 10, "assembly_script_set_data": 10 {}}}"#,
+                operator_entry
+            ),
+        )
+        .unwrap();
+    };
+
+    write_abi_costs("");
+    let gas_costs = GasCosts::new(abi_cost_file.clone(), wasm_abi_file.clone()).unwrap();
+    assert!(gas_costs.operator_costs.is_empty());
+
+    write_abi_costs(r#", "operator:MemoryFill": 33"#);
+    let gas_costs = GasCosts::new(abi_cost_file.clone(), wasm_abi_file.clone()).unwrap();
+    assert_eq!(gas_costs.operator_costs["MemoryFill"], 33);
+    assert!(!gas_costs.abi_costs.contains_key("operator:MemoryFill"));
+
+    write_abi_costs(r#", "operator:Unknown": 33"#);
+    assert!(GasCosts::new(abi_cost_file.clone(), wasm_abi_file.clone()).is_err());
+
+    std::fs::remove_file(abi_cost_file).unwrap();
+    std::fs::remove_file(wasm_abi_file).unwrap();
+}
+
+#[cfg(feature = "trap-metrics")]
+#[test]
+#[serial]
+/// Test that failed executions are counted by trap kind
+fn test_trap_stats() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1)
+            (func (export "main") unreachable))"#,
+    )
+    .unwrap();
+
+    let before = TRAP_STATS.snapshot();
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    run_main(&*interface, runtime_module, 100_000, gas_costs).unwrap_err();
+    assert!(TRAP_STATS.snapshot().unreachable > before.unreachable);
+}
+
+#[test]
+#[serial]
+/// Test that no memory written by a run leaks into the next one when the
+/// instance memory is restored
+fn test_memory_snapshot_clean_state() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    // traps if the bytes or the (non-exported) global written by a previous
+    // run are still there, the byte of the grown page included
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1)
+            (global $g (mut i32) (i32.const 0))
+            (func (export "__new") (param i32 i32) (result i32) (i32.const 1024))
+            (func (export "run")
+                (if (global.get $g) (then unreachable))
+                (if (i32.gt_u (memory.size) (i32.const 1))
+                    (then (if (i32.load8_u (i32.const 70000)) (then unreachable))))
+                (global.set $g (i32.const 1))
+                (if (i32.load8_u (i32.const 100)) (then unreachable))
+                (i32.store8 (i32.const 100) (i32.const 1))
+                (drop (memory.grow (i32.const 1)))
+                (i32.store8 (i32.const 70000) (i32.const 1))))"#,
+    )
+    .unwrap();
+
+    let runtime_module =
+        RuntimeModule::new_for_snapshot(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    let responses = run_function_with_memory_snapshot(
+        &*interface,
+        runtime_module,
+        "run",
+        b"",
+        3,
+        100_000,
+        gas_costs.clone(),
+    )
+    .unwrap();
+    assert_eq!(responses.len(), 3);
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    assert!(run_function_with_shared_param(
+        &*interface,
+        runtime_module,
+        "run",
+        b"",
+        2,
+        100_000,
+        gas_costs
+    )
+    .is_err());
+}
+
+#[test]
+#[serial]
+/// Test that `InstanceSnapshot::verify_clean_state` detects the state a run
+/// leaves on a reused instance, and accepts it once restored
+fn test_verify_clean_state() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let bytecode = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1)
+            (global $g (mut i32) (i32.const 0))
+            (func (export "__new") (param i32 i32) (result i32) (i32.const 1024))
+            (func (export "run")
+                (global.set $g (i32.const 1))
+                (i32.store8 (i32.const 100) (i32.const 1))
+                (drop (memory.grow (i32.const 1)))
+                (i32.store8 (i32.const 70000) (i32.const 1))))"#,
+    )
+    .unwrap();
+    let module =
+        ASModule::new_with_exported_globals(&bytecode, 100_000, gas_costs.clone(), Compiler::SP)
+            .unwrap();
+    let mut store = Store::new(module._engine);
+    let mut context = ASContext::new(
+        &*interface,
+        module.binary_module,
+        gas_costs,
+        RunOptions::default(),
+    );
+    let (instance, _, _) = context.create_vm_instance_and_init_env(&mut store).unwrap();
+
+    let snapshot = InstanceSnapshot::take(&mut store, &instance).unwrap();
+    snapshot.verify_clean_state(&mut store, &instance).unwrap();
+
+    let run = instance.exports.get_function("run").unwrap();
+    run.call(&mut store, &[]).unwrap();
+    assert!(snapshot.verify_clean_state(&mut store, &instance).is_err());
+
+    snapshot.restore(&mut store, &instance).unwrap();
+    snapshot.verify_clean_state(&mut store, &instance).unwrap();
+}
+
+#[test]
+#[serial]
+#[ignore]
+/// Compare the time of the runs restoring a snapshot with the time of the
+/// runs creating a new instance, for a module with a 256 KiB data segment.
+///
+/// Run with `cargo test --release -- --ignored test_memory_snapshot_timing
+/// --nocapture`.
+fn test_memory_snapshot_timing() {
+    const RUNS: usize = 1_000;
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        format!(
+            r#"(module
+                (memory (export "memory") 5)
+                (data (i32.const 0) "{}")
+                (func (export "__new") (param i32 i32) (result i32) (i32.const 262144))
+                (func (export "run")))"#,
+            "\\2a".repeat(256 * 1024)
+        )
+        .as_bytes(),
+    )
+    .unwrap();
+
+    let start = std::time::Instant::now();
+    let runtime_module =
+        RuntimeModule::new_for_snapshot(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    run_function_with_memory_snapshot(
+        &*interface,
+        runtime_module,
+        "run",
+        b"",
+        RUNS,
+        1_000_000,
+        gas_costs.clone(),
+    )
+    .unwrap();
+    let snapshot = start.elapsed();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    let start = std::time::Instant::now();
+    for _ in 0..RUNS {
+        run_function(
+            &*interface,
+            runtime_module.clone(),
+            "run",
+            b"",
+            1_000_000,
+            gas_costs.clone(),
+        )
+        .unwrap();
+    }
+    let instantiation = start.elapsed();
+
+    println!(
+        "{} runs: {:?} with a snapshot, {:?} with new instances",
+        RUNS, snapshot, instantiation
+    );
+}
+
+#[test]
+#[serial]
+/// Test that the arguments of an `abort` call are attached to the error
+fn test_abort_info() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "env" "abort" (func $abort (param i32 i32 i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 12) "\08\00\00\00b\00o\00o\00m\00")
+            (data (i32.const 36) "\08\00\00\00a\00.\00t\00s\00")
+            (func (export "main")
+                (call $abort (i32.const 16) (i32.const 40) (i32.const 3) (i32.const 7))))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    match run_main(&*interface, runtime_module, 100_000, gas_costs) {
+        Err(VMError::ExecutionError { error, abort, .. }) => {
+            assert!(error.contains("Contract aborted: boom at a.ts:3:7"));
+            let abort = abort.expect("abort info not captured");
+            assert_eq!(abort.message, "boom");
+            assert_eq!(abort.filename, "a.ts");
+            assert_eq!((abort.line, abort.column), (3, 7));
+        }
+        _ => panic!("expected an execution error"),
+    }
+}
+
+#[test]
+/// Test the cumulative operation datastore read budget
+fn test_max_op_data_read() {
+    let gas_costs = GasCosts {
+        max_op_data_read: 10,
+        ..Default::default()
+    };
+    let mut env = ASEnv::new(&TestInterface, gas_costs);
+    env.consume_op_data_budget(6).unwrap();
+    env.consume_op_data_budget(4).unwrap();
+    assert!(env.consume_op_data_budget(1).is_err());
+}
+
+#[test]
+#[serial]
+/// Test the default rate limit counter of the interface
+fn test_rate_limit() {
+    let interface = TestInterface;
+    interface.raw_delete_data(b"rate").unwrap();
+    assert!(interface.rate_limit(b"rate", 2, 10).unwrap());
+    assert!(interface.rate_limit(b"rate", 2, 10).unwrap());
+    assert!(!interface.rate_limit(b"rate", 2, 10).unwrap());
+    assert_eq!(
+        interface.raw_get_data(b"rate").unwrap()[8..],
+        2u64.to_le_bytes()
+    );
+}
+
+#[test]
+/// Test that the remaining gas cannot be read from an env bound to no instance
+fn test_env_remaining_gas_unbound() {
+    let env = ASEnv::new(&TestInterface, GasCosts::default());
+    let mut store = Store::default();
+    assert!(env.remaining_gas(&mut store).is_err());
+}
+
+#[test]
+#[serial]
+/// Test the recording of the host functions called by an execution
+fn test_record_abi_usage() {
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_get_balance" (func $balance (result i64)))
+            (import "massa" "assembly_script_get_op_count" (func $op_count (result i64)))
+            (memory (export "memory") 1)
+            (func (export "main") call $balance drop call $balance drop))"#,
+    )
+    .unwrap();
+
+    let gas_costs = GasCosts::default();
+    for record_abi_usage in [false, true] {
+        let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+        let options = RunOptions {
+            record_abi_usage,
+            ..Default::default()
+        };
+        let response = run_function_with_options(
+            &*interface,
+            runtime_module,
+            "main",
+            b"",
+            100_000,
+            gas_costs.clone(),
+            options,
+        )
+        .unwrap();
+        let expected = record_abi_usage.then(|| "assembly_script_get_balance".to_string());
+        assert_eq!(
+            response.used_abis.into_iter().collect::<Vec<_>>(),
+            Vec::from_iter(expected)
+        );
+    }
+}
+
+#[test]
+#[serial]
+/// Test running the constructor of modules with and without one
+fn test_run_constructor() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let run = |functions: &str| {
+        let module = wasmer::wat2wasm(
+            format!(r#"(module (memory (export "memory") 1) {functions})"#).as_bytes(),
+        )
+        .unwrap();
+        let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+        run_constructor(&*interface, runtime_module, b"", 100_000, gas_costs.clone())
+    };
+
+    assert!(run(r#"(func (export "main"))"#).unwrap().is_none());
+    assert!(run(r#"(func (export "constructor"))"#).unwrap().is_some());
+    assert!(run(r#"(func (export "constructor") unreachable)"#).is_err());
+}
+
+#[test]
+#[serial]
+/// Test that the generated events are returned in the response
+fn test_response_events() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_generate_event" (func $event (param i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 12) "\04\00\00\00h\00i\00")
+            (data (i32.const 28) "\02\00\00\00!\00")
+            (func (export "main")
+                (call $event (i32.const 16))
+                (call $event (i32.const 32))))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    let response = run_main(&*interface, runtime_module, 100_000, gas_costs).unwrap();
+    assert_eq!(response.events, vec!["hi", "!"]);
+}
+
+#[test]
+/// Test that modules with a start function are rejected unless configured not
+/// to
+fn test_reject_start_section() {
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1)
+            (func $f)
+            (start $f))"#,
+    )
+    .unwrap();
+
+    RuntimeModule::new(&module, GasCosts::default(), Compiler::SP).unwrap();
+    let gas_costs = GasCosts {
+        reject_start_section: true,
+        ..Default::default()
+    };
+    match RuntimeModule::new(&module, gas_costs, Compiler::SP) {
+        Err(err) => assert_eq!(
+            err.to_string(),
+            "modules with a wasm start section are not allowed"
+        ),
+        Ok(_) => panic!("module with a start section was compiled"),
+    }
+}
+
+#[test]
+/// Test the per-address accounting of the coins sent by an execution
+fn test_paid_to() {
+    let mut env = ASEnv::new(&TestInterface, GasCosts::default());
+    env.record_payment("AU1", 10);
+    env.record_payment("AU2", 5);
+    env.record_payment("AU1", 7);
+    assert_eq!(env.paid_to.get("AU1"), Some(&17));
+    env.reset();
+    assert!(env.paid_to.is_empty());
+}
+
+#[test]
+#[serial]
+/// Test that `assembly_script_get_balance_delta` counts from the start of the
+/// execution
+fn test_get_balance_delta() {
+    let gas_costs = GasCosts::default();
+    let interface = MockInterface::new().with_balance(MockInterface::DEFAULT_ADDRESS, 100);
+    // traps unless the deltas are 0 then -30
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_get_balance_delta" (func $balance_delta (result i64)))
+            (import "massa" "assembly_script_transfer_coins" (func $transfer_coins (param i32 i64)))
+            (memory (export "memory") 1)
+            (data (i32.const 44) "\06\00\00\00A\00U\002\00")
+            (func (export "main")
+                (if (i64.ne (call $balance_delta) (i64.const 0))
+                    (then unreachable))
+                (call $transfer_coins (i32.const 48) (i64.const 30))
+                (if (i64.ne (call $balance_delta) (i64.const -30))
+                    (then unreachable))))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    run_main(&interface, runtime_module, 10_000_000, gas_costs.clone()).unwrap();
+
+    // traps unless the delta is -30 on the first call, after a transfer
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_get_balance_delta" (func $balance_delta (result i64)))
+            (import "massa" "assembly_script_transfer_coins" (func $transfer_coins (param i32 i64)))
+            (memory (export "memory") 1)
+            (data (i32.const 44) "\06\00\00\00A\00U\002\00")
+            (func (export "main")
+                (call $transfer_coins (i32.const 48) (i64.const 30))
+                (if (i64.ne (call $balance_delta) (i64.const -30))
+                    (then unreachable))))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    run_main(&interface, runtime_module, 10_000_000, gas_costs).unwrap();
+}
+
+#[test]
+#[serial]
+/// Test that the transfers of a bootstrap execution are routed to the
+/// interface bootstrap policy
+fn test_bootstrap_transfer() {
+    let gas_costs = GasCosts::default();
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_transfer_coins" (func $transfer_coins (param i32 i64)))
+            (memory (export "memory") 1)
+            (data (i32.const 44) "\06\00\00\00A\00U\002\00")
+            (func (export "main")
+                (call $transfer_coins (i32.const 48) (i64.const 30))))"#,
+    )
+    .unwrap();
+
+    let run = |bootstrap: bool| {
+        let interface = MockInterface::new().with_balance(MockInterface::DEFAULT_ADDRESS, 100);
+        let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+        let options = RunOptions {
+            bootstrap,
+            ..Default::default()
+        };
+        run_function_with_options(
+            &interface,
+            runtime_module,
+            "main",
+            b"",
+            10_000_000,
+            gas_costs.clone(),
+            options,
+        )
+        .map(|_| interface.calls())
+    };
+    assert!(run(false).unwrap().contains(&"transfer_coins".to_string()));
+    // the mock has no bootstrap policy
+    let err = run(true).unwrap_err();
+    assert!(err.to_string().contains("transfer_coins_bootstrap"));
+}
+
+#[test]
+#[serial]
+/// Test that `assembly_script_add_to_data` is a single interface call
+fn test_add_to_data() {
+    let gas_costs = GasCosts::default();
+    let interface = MockInterface::new().with_datastore_entry(b"key", &5u64.to_le_bytes());
+    // traps unless the counter goes 5 -> 8 -> 0 and the checked underflow fails
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_add_to_data" (func $add_to_data (param i32 i64 i32) (result i64)))
+            (memory (export "memory") 1)
+            (data (i32.const 12) "\03\00\00\00key")
+            (func (export "main")
+                (if (i64.ne (call $add_to_data (i32.const 16) (i64.const 3) (i32.const 0)) (i64.const 8))
+                    (then unreachable))
+                (if (i64.ne (call $add_to_data (i32.const 16) (i64.const -10) (i32.const 1)) (i64.const 0))
+                    (then unreachable)))
+            (func (export "underflow")
+                (drop (call $add_to_data (i32.const 16) (i64.const -1) (i32.const 0)))))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    run_main(
+        &interface,
+        runtime_module.clone(),
+        10_000_000,
+        gas_costs.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        interface.datastore_entry(MockInterface::DEFAULT_ADDRESS, b"key"),
+        Some(0u64.to_le_bytes().to_vec())
+    );
+    let calls = interface.calls();
+    assert_eq!(calls[calls.len() - 2..], ["add_to_data", "add_to_data"]);
+    assert!(!calls
+        .iter()
+        .any(|call| call == "has_data" || call == "raw_set_data"));
+
+    run_function(
+        &interface,
+        runtime_module,
+        "underflow",
+        b"",
+        10_000_000,
+        gas_costs,
+    )
+    .expect_err("checked underflow should fail");
+    assert_eq!(
+        interface.datastore_entry(MockInterface::DEFAULT_ADDRESS, b"key"),
+        Some(0u64.to_le_bytes().to_vec())
+    );
+}
+
+#[test]
+#[serial]
+/// Test an execution against the in-memory `MockInterface`
+fn test_mock_interface() {
+    let gas_costs = GasCosts::default();
+    let interface = MockInterface::new()
+        .with_balance(MockInterface::DEFAULT_ADDRESS, 100)
+        .with_datastore_entry(b"old", b"value");
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_set_data" (func $set_data (param i32 i32)))
+            (import "massa" "assembly_script_generate_event" (func $generate_event (param i32)))
+            (import "massa" "assembly_script_transfer_coins" (func $transfer_coins (param i32 i64)))
+            (memory (export "memory") 1)
+            (data (i32.const 12) "\03\00\00\00key")
+            (data (i32.const 28) "\04\00\00\00h\00i\00")
+            (data (i32.const 44) "\06\00\00\00A\00U\002\00")
+            (func (export "main")
+                (call $set_data (i32.const 16) (i32.const 16))
+                (call $generate_event (i32.const 32))
+                (call $transfer_coins (i32.const 48) (i64.const 30))))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    run_main(&interface, runtime_module, 10_000_000, gas_costs).unwrap();
+    assert_eq!(
+        interface.datastore_entry(MockInterface::DEFAULT_ADDRESS, b"key"),
+        Some(b"key".to_vec())
+    );
+    assert_eq!(
+        interface.datastore_entry(MockInterface::DEFAULT_ADDRESS, b"old"),
+        Some(b"value".to_vec())
+    );
+    assert_eq!(interface.emitted_events(), vec!["hi".to_string()]);
+    assert_eq!(interface.balance(MockInterface::DEFAULT_ADDRESS), 70);
+    assert_eq!(interface.balance("AU2"), 30);
+    let calls = interface.calls();
+    assert_eq!(
+        calls[calls.len() - 3..],
+        ["raw_set_data", "generate_event", "transfer_coins"]
+    );
+}
+
+#[test]
+#[serial]
+/// Test that `get_keys_prefix` returns at most `max_count` keys
+fn test_get_keys_prefix() {
+    let gas_costs = GasCosts::default();
+    let interface = MockInterface::new()
+        .with_datastore_entry(b"k3", b"")
+        .with_datastore_entry(b"k1", b"")
+        .with_datastore_entry(b"x", b"")
+        .with_datastore_entry(b"k2", b"");
+    // stores the keys returned for the prefix `k` under the key `out`
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_get_keys_prefix" (func $get_keys_prefix (param i32 i32) (result i32)))
+            (import "massa" "assembly_script_set_data" (func $set_data (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 12) "\01\00\00\00k")
+            (data (i32.const 28) "\03\00\00\00out")
+            (func (export "__new") (param i32 i32) (result i32)
+                (i32.store (i32.const 32764) (local.get 0))
+                (i32.const 32768))
+            (func (export "main")
+                (call $set_data (i32.const 32) (call $get_keys_prefix (i32.const 16) (i32.const 2)))))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    run_main(&interface, runtime_module, 10_000_000, gas_costs).unwrap();
+    assert_eq!(
+        interface.datastore_entry(MockInterface::DEFAULT_ADDRESS, b"out"),
+        Some(vec![2, 0, 0, 0, 2, b'k', b'1', 2, b'k', b'2'])
+    );
+}
+
+#[test]
+#[serial]
+/// Test that the bulk memory operators are charged their `operator_costs`
+/// entry, and the flat operator cost without one
+fn test_bulk_memory_cost() {
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1)
+            (func (export "main")
+                (local $i i32)
+                (loop $fill
+                    (memory.fill (i32.const 0) (i32.const 255) (i32.const 65536))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br_if $fill (i32.lt_u (local.get $i) (i32.const 10))))))"#,
+    )
+    .unwrap();
+
+    let operator_cost = GasCosts::default().operator_cost;
+    let run = |memory_fill_cost: Option<u64>| {
+        let mut gas_costs = GasCosts::default();
+        if let Some(cost) = memory_fill_cost {
+            gas_costs
+                .operator_costs
+                .insert("MemoryFill".to_string(), cost);
+        }
+        let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+        run_main(&*interface, runtime_module, 10_000_000, gas_costs)
+            .unwrap()
+            .remaining_gas
+    };
+    // flat cost by default
+    assert_eq!(run(None), run(Some(operator_cost)));
+    assert_eq!(
+        run(None) - run(Some(100 * operator_cost)),
+        10 * 99 * operator_cost
+    );
+}
+
+#[test]
+#[serial]
+/// Test that the packed current slot matches the period and thread getters
+fn test_get_current_slot() {
+    let gas_costs = GasCosts::default();
+    let interface = MockInterface::new().with_slot(123_456_789_012, 17);
+    // checks the packed slot against the getters and stores it under `slot`
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_get_current_slot" (func $get_current_slot (result i32)))
+            (import "massa" "assembly_script_get_current_period" (func $get_current_period (result i64)))
+            (import "massa" "assembly_script_get_current_thread" (func $get_current_thread (result i32)))
+            (import "massa" "assembly_script_set_data" (func $set_data (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 12) "\04\00\00\00slot")
+            (func (export "__new") (param i32 i32) (result i32)
+                (i32.store (i32.const 32764) (local.get 0))
+                (i32.const 32768))
+            (func (export "main")
+                (local $slot i32)
+                (local.set $slot (call $get_current_slot))
+                (if (i64.ne (i64.load (local.get $slot)) (call $get_current_period))
+                    (then unreachable))
+                (if (i32.ne (i32.load8_u offset=8 (local.get $slot)) (call $get_current_thread))
+                    (then unreachable))
+                (call $set_data (i32.const 16) (local.get $slot))))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    run_main(&interface, runtime_module, 10_000_000, gas_costs).unwrap();
+    let mut expected = 123_456_789_012u64.to_le_bytes().to_vec();
+    expected.push(17);
+    assert_eq!(
+        interface.datastore_entry(MockInterface::DEFAULT_ADDRESS, b"slot"),
+        Some(expected)
+    );
+}
+
+#[test]
+#[serial]
+/// Test that an unbounded recursion fails with a stack overflow error
+fn test_stack_overflow() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1)
+            (func $recurse (param i32) (result i32)
+                (i32.add (call $recurse (local.get 0)) (i32.const 1)))
+            (func (export "main")
+                (drop (call $recurse (i32.const 0)))))"#,
+    )
+    .unwrap();
+
+    #[cfg(feature = "trap-metrics")]
+    let before = TRAP_STATS.snapshot();
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    let err = run_main(&*interface, runtime_module, 100_000_000, gas_costs).unwrap_err();
+    assert!(matches!(err, VMError::StackOverflow { .. }));
+    #[cfg(feature = "trap-metrics")]
+    assert!(TRAP_STATS.snapshot().stack_overflow > before.stack_overflow);
+}
+
+#[test]
+#[serial]
+/// Test that running out of gas fails with a typed error
+fn test_out_of_gas_error() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1)
+            (func (export "main") (loop $spin (br $spin))))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    match run_main(&*interface, runtime_module, 100_000, gas_costs) {
+        Err(VMError::OutOfGas {
+            function, limit, ..
+        }) => {
+            assert_eq!(function, "main");
+            assert_eq!(limit, 100_000);
+        }
+        _ => panic!("expected an out of gas error"),
+    }
+}
+
+#[test]
+#[serial]
+/// Test the batched datastore read
+fn test_get_data_multi() {
+    let gas_costs = GasCosts::default();
+    let interface = MockInterface::new()
+        .with_datastore_entry(b"a", b"1")
+        .with_datastore_entry(b"c", b"333");
+    // reads the keys `a`, `b` and `c` and stores the values under `out`
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_get_data_multi" (func $get_data_multi (param i32) (result i32)))
+            (import "massa" "assembly_script_set_data" (func $set_data (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 12) "\0a\00\00\00\03\00\00\00\01a\01b\01c")
+            (data (i32.const 44) "\03\00\00\00out")
+            (func (export "__new") (param i32 i32) (result i32)
+                (i32.store (i32.const 32764) (local.get 0))
+                (i32.const 32768))
+            (func (export "main")
+                (call $set_data (i32.const 48) (call $get_data_multi (i32.const 16)))))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    run_main(&interface, runtime_module, 10_000_000, gas_costs).unwrap();
+    assert_eq!(
+        interface.datastore_entry(MockInterface::DEFAULT_ADDRESS, b"out"),
+        Some(vec![
+            3, 0, 0, 0, 1, 1, 0, 0, 0, b'1', 0, 1, 3, 0, 0, 0, b'3', b'3', b'3'
+        ])
+    );
+}
+
+#[test]
+#[serial]
+/// Test that an ABI called past the execution time limit fails the execution
+fn test_max_execution_time() {
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_has_gas_at_least" (func $has_gas (param i64) (result i32)))
+            (memory (export "memory") 1)
+            (func (export "main")
+                (drop (call $has_gas (i64.const 0)))))"#,
+    )
+    .unwrap();
+
+    let run = |max_execution_time: Option<Duration>| {
+        let gas_costs = GasCosts::default();
+        let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+        let options = RunOptions {
+            max_execution_time,
+            ..Default::default()
+        };
+        run_function_with_options(
+            &*interface,
+            runtime_module,
+            "main",
+            b"",
+            10_000_000,
+            gas_costs,
+            options,
+        )
+    };
+    run(None).unwrap();
+    run(Some(Duration::from_secs(60))).unwrap();
+    #[cfg(feature = "trap-metrics")]
+    let before = TRAP_STATS.snapshot();
+    assert!(matches!(
+        run(Some(Duration::ZERO)),
+        Err(VMError::Timeout { .. })
+    ));
+    #[cfg(feature = "trap-metrics")]
+    {
+        let after = TRAP_STATS.snapshot();
+        assert!(after.timeout > before.timeout);
+        assert_eq!(after.other, before.other);
+    }
+}
+
+#[test]
+#[serial]
+/// Test that `assembly_script_get_remaining_time_ms` reports the execution
+/// time limit
+fn test_get_remaining_time_ms() {
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    // traps if there is no time limit
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_get_remaining_time_ms" (func $remaining_time (result i64)))
+            (memory (export "memory") 1)
+            (func (export "main")
+                (if (i64.lt_s (call $remaining_time) (i64.const 0))
+                    (then unreachable))))"#,
+    )
+    .unwrap();
+
+    let run = |max_execution_time: Option<Duration>| {
+        let gas_costs = GasCosts::default();
+        let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+        let options = RunOptions {
+            max_execution_time,
+            ..Default::default()
+        };
+        run_function_with_options(
+            &*interface,
+            runtime_module,
+            "main",
+            b"",
+            10_000_000,
+            gas_costs,
+            options,
+        )
+    };
+    run(Some(Duration::from_secs(60))).unwrap();
+    assert!(matches!(run(None), Err(VMError::ExecutionError { .. })));
+}
+
+#[test]
+#[serial]
+/// Test that `run_estimate` reports the gas and the changes of an execution
+/// without applying them
+fn test_run_estimate() {
+    let gas_costs = GasCosts::default();
+    let interface = MockInterface::new().with_balance(MockInterface::DEFAULT_ADDRESS, 100);
+    // the deletion only succeeds if the buffered write is visible
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_set_data" (func $set_data (param i32 i32)))
+            (import "massa" "assembly_script_transfer_coins" (func $transfer_coins (param i32 i64)))
+            (import "massa" "assembly_script_delete_data" (func $delete_data (param i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 12) "\03\00\00\00key")
+            (data (i32.const 44) "\06\00\00\00A\00U\002\00")
+            (func (export "main")
+                (call $set_data (i32.const 16) (i32.const 16))
+                (call $transfer_coins (i32.const 48) (i64.const 30))
+                (call $delete_data (i32.const 16))))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    let estimate = run_estimate(
+        &interface,
+        runtime_module,
+        "main",
+        b"",
+        10_000_000,
+        gas_costs,
+    )
+    .unwrap();
+    assert!(estimate.response.gas_used > 0);
+    assert_eq!(
+        estimate.effects,
+        vec![
+            PlannedEffect::SetData {
+                address: MockInterface::DEFAULT_ADDRESS.to_string(),
+                key: b"key".to_vec(),
+                value: b"key".to_vec(),
+            },
+            PlannedEffect::Transfer {
+                from_address: MockInterface::DEFAULT_ADDRESS.to_string(),
+                to_address: "AU2".to_string(),
+                raw_amount: 30,
+            },
+            PlannedEffect::DeleteData {
+                address: MockInterface::DEFAULT_ADDRESS.to_string(),
+                key: b"key".to_vec(),
+            },
+        ]
+    );
+    assert_eq!(
+        interface.datastore_entry(MockInterface::DEFAULT_ADDRESS, b"key"),
+        None
+    );
+    assert_eq!(interface.balance(MockInterface::DEFAULT_ADDRESS), 100);
+    assert_eq!(interface.balance("AU2"), 0);
+}
+
+#[test]
+#[serial]
+/// Test that `run_estimate` records the coins attached to a call to another
+/// contract without transferring them
+fn test_run_estimate_call_coins() {
+    let gas_costs = GasCosts::default();
+    let callee = wasmer::wat2wasm(
+        br#"(module
+            (memory (export "memory") 1)
+            (func (export "main")))"#,
+    )
+    .unwrap();
+    let interface = MockInterface::new()
+        .with_balance(MockInterface::DEFAULT_ADDRESS, 100)
+        .with_bytecode("AU2", &callee);
+    // calls `main` of AU2 with an empty parameter and 30 coins
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_call" (func $call (param i32 i32 i32 i64) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 44) "\06\00\00\00A\00U\002\00")
+            (data (i32.const 60) "\00\00\00\00")
+            (data (i32.const 76) "\08\00\00\00m\00a\00i\00n\00")
+            (func (export "__new") (param i32 i32) (result i32)
+                (i32.store (i32.const 32764) (local.get 0))
+                (i32.const 32768))
+            (func (export "main")
+                (drop (call $call (i32.const 48) (i32.const 80) (i32.const 64) (i64.const 30)))))"#,
+    )
+    .unwrap();
+
+    let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+    let estimate = run_estimate(
+        &interface,
+        runtime_module,
+        "main",
+        b"",
+        10_000_000,
+        gas_costs,
+    )
+    .unwrap();
+    assert_eq!(
+        estimate.effects,
+        vec![PlannedEffect::Transfer {
+            from_address: MockInterface::DEFAULT_ADDRESS.to_string(),
+            to_address: "AU2".to_string(),
+            raw_amount: 30,
+        }]
+    );
+    assert_eq!(interface.balance(MockInterface::DEFAULT_ADDRESS), 100);
+    assert_eq!(interface.balance("AU2"), 0);
+}
+
+#[test]
+#[serial]
+/// Test that `assembly_script_hash_blake3` returns the interface hash and
+/// charges for each hashed byte
+fn test_hash_blake3() {
+    let gas_costs = GasCosts::default();
+    let byte_cost = gas_costs.abi_costs["assembly_script_hash_blake3_byte"];
+    // stores the hash of the data at 16 under the key `out`
+    let run = |data: &str| {
+        let interface = MockInterface::new();
+        let module = wasmer::wat2wasm(
+            format!(
+                r#"(module
+                    (import "massa" "assembly_script_hash_blake3" (func $hash_blake3 (param i32) (result i32)))
+                    (import "massa" "assembly_script_set_data" (func $set_data (param i32 i32)))
+                    (memory (export "memory") 1)
+                    (data (i32.const 12) "{}")
+                    (data (i32.const 1020) "\03\00\00\00out")
+                    (func (export "__new") (param i32 i32) (result i32)
+                        (i32.store (i32.const 32764) (local.get 0))
+                        (i32.const 32768))
+                    (func (export "main")
+                        (call $set_data (i32.const 1024) (call $hash_blake3 (i32.const 16)))))"#,
+                data
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+        let response = run_main(&interface, runtime_module, 10_000_000, gas_costs.clone()).unwrap();
+        assert_eq!(
+            interface.datastore_entry(MockInterface::DEFAULT_ADDRESS, b"out"),
+            Some(vec![0; 32])
+        );
+        assert!(interface.calls().contains(&"hash_blake3".to_string()));
+        response.gas_used
+    };
+    let empty = run(r"\00\00\00\00");
+    let ten_bytes = run(r"\0a\00\00\00abcdefghij");
+    assert_eq!(ten_bytes - empty, 10 * byte_cost);
+}
+
+#[test]
+#[serial]
+/// Test that `assembly_script_random_from_seed` only depends on the seed and
+/// the current slot
+fn test_random_from_seed() {
+    let gas_costs = GasCosts::default();
+    // stores the number generated from the seed under the key `out`
+    let module = wasmer::wat2wasm(
+        br#"(module
+            (import "massa" "assembly_script_random_from_seed" (func $random_from_seed (param i32) (result i64)))
+            (import "massa" "assembly_script_set_data" (func $set_data (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 12) "\04\00\00\00seed")
+            (data (i32.const 28) "\03\00\00\00out")
+            (data (i32.const 44) "\08\00\00\00")
+            (func (export "main")
+                (i64.store (i32.const 48) (call $random_from_seed (i32.const 16)))
+                (call $set_data (i32.const 32) (i32.const 48))))"#,
+    )
+    .unwrap();
+
+    let run = |interface: &MockInterface| {
+        let runtime_module = RuntimeModule::new(&module, gas_costs.clone(), Compiler::SP).unwrap();
+        run_main(interface, runtime_module, 10_000_000, gas_costs.clone()).unwrap();
+        let value = interface
+            .datastore_entry(MockInterface::DEFAULT_ADDRESS, b"out")
+            .unwrap();
+        i64::from_le_bytes(value.try_into().unwrap())
+    };
+    let interface = MockInterface::new().with_slot(12, 3);
+    let value = run(&interface);
+    assert_eq!(value, interface.random_from_seed(b"seed").unwrap());
+    assert_eq!(run(&MockInterface::new().with_slot(12, 3)), value);
+    assert_ne!(run(&MockInterface::new().with_slot(12, 4)), value);
+    assert_ne!(run(&MockInterface::new().with_slot(13, 3)), value);
+}
+
+// NOTE: this test is outdated as module are now pre-compiled with max_instance_cost
+// leaving this for documentation purposes
+// #[test]
+// #[serial]
+// /// Test both cases of the not enough gas error
+// fn test_not_enough_gas_error() {
+//     let gas_costs = GasCosts::default();
+//     let interface: Box<dyn Interface> = Box::new(TestInterface);
+//     let module = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/wasm/basic_main.wasm"));
+
+//     // Test giving not enough gas to create the instance
+//     let runtime_module = RuntimeModule::new(module, 100, gas_costs.clone(), Compiler::SP).unwrap();
+//     let error = run_main(&*interface, runtime_module, 100_000, gas_costs.clone())
+//         .unwrap_err()
+//         .to_string();
+//     assert!(
+//         error.contains("Not enough gas, limit reached at initialization")
+//             || error.contains("RuntimeError: unreachable")
+//     );
+
+//     // Test giving enough gas to create the instance but not enough for the VM
+//     let runtime_module =
+//         RuntimeModule::new(module, 100_000, gas_costs.clone(), Compiler::SP).unwrap();
+//     let error = run_main(&*interface, runtime_module, 100, gas_costs)
+//         .unwrap_err()
+//         .to_string();
+//     assert!(error.contains("Not enough gas to launch the virtual machine"));
+// }
+
+#[test]
+#[serial]
+/// Test that a no-main SC executed through `run_main` fails as expected
+fn test_run_main_without_main() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/wasm/no_main.wasm"));
+    let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
+    run_main(&*interface, runtime_module, 100_000, gas_costs)
+        .expect_err("An error should spawn here");
+}
+
+#[test]
+#[serial]
+/// Even if our SC is empty there is still an initial and minimum metering cost,
+/// because we have a memory allocator to init.
+///
+/// This test ensure that this initial cost is correctly debited.
+fn test_run_empty_main() {
+    let mut gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/wasm/empty_main.wasm"));
+    gas_costs.launch_cost = 0;
+    let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
+    let a = run_main(
+        &*interface,
+        runtime_module.clone(),
+        10_000_000,
+        gas_costs.clone(),
+    )
+    .expect("Failed to run empty_main.wasm");
+    // Here we avoid hard-coding a value (that can change in future wasmer
+    // release)
+    assert!(a.remaining_gas > 0);
+
+    let mut rng = rand::thread_rng();
+    let cost = rng.gen_range(1..1_000_000);
+    gas_costs.launch_cost = cost;
+    let b = run_main(&*interface, runtime_module, 10_000_000, gas_costs)
+        .expect("Failed to run empty_main.wasm");
+    // Between 2 calls, the metering cost should be the difference
+    assert_eq!(a.remaining_gas - b.remaining_gas, cost);
+}
+
+#[test]
+#[serial]
+/// Even if our SC is empty there is still an initial and minimum metering cost,
+/// because we have a memory allocator to init.
+///
+/// This test ensure that this initial cost is correctly debited.
+fn test_run_main_rust_wasmv1() {
+    // let mut gas_costs = GasCosts::default();
+    // let interface: Box<dyn Interface> =
+    //     Box::new(TestInterface);
+    // let module = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"),
+    //     "/../massa-rust-sc-examples/target/wasm32-unknown-unknown/debug/
+    // massa_rust_sc_deploy_sc.wasm_add")); gas_costs.launch_cost = 0;
+    // let runtime_module =
+    //     RuntimeModule::new(module, gas_costs.clone(), Compiler::SP)
+    //         .unwrap();
+
+    // let a = match run_main(
+    //     &*interface,
+    //     runtime_module.clone(),
+    //     10_000_000,
+    //     gas_costs.clone(),
+    // ) { Ok(a) => a, Err(e) => { println!("e: {}", e); panic!("Failed to run
+    //   main") }
+    // };
+
+    // // Here we avoid hard-coding a value (that can change in future wasmer
+    // // release)
+    // assert!(a.remaining_gas > 0);
+
+    // let mut rng = rand::thread_rng();
+    // let cost = rng.gen_range(1..1_000_000);
+    // gas_costs.launch_cost = cost;
+    // let b = run_main(&*interface, runtime_module, 10_000_000, gas_costs)
+    //     .expect("Failed to run empty_main.wasm");
+    // dbg!(b.ret);
+    // // Between 2 calls, the metering cost should be the difference
+    // // assert_eq!(a.remaining_gas - b.remaining_gas, cost);
+}
+
+#[test]
+#[serial]
+/// Test the operation datastore
+///
+/// * getOpKeys
+/// * hasOpKey
+/// * getOpData
+fn test_op_fn() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/wasm/op_fn.wasm"));
+    let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
+    run_main(&*interface, runtime_module, 10_000_000, gas_costs.clone())
+        .expect("Failed to run op_fn.wasm");
+}
+
+/// Test `seed`, `Date.now`, `console.log` and `abort`
+///
+/// These are AS functions that we choose to handle in the VM
+#[test]
+#[serial]
+fn test_builtins() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/wasm/use_builtins.wasm"
+    ));
+    let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
+    // let before = chrono::offset::Utc::now().timestamp_millis();
+    match run_main(&*interface, runtime_module, 10_000_000, gas_costs.clone()) {
+        Err(e) => {
+            let msg = e.to_string();
+            // make sure the error was caused by a manual abort
+            assert!(msg.contains("Manual abort"), "Error was: {:?}", e);
+            assert!(msg.contains("at use_builtins.ts"), "Error was: {:?}", e);
+            // check the given timestamp validity
+            // let after = chrono::offset::Utc::now().timestamp_millis();
+            // let ident = "UTC timestamp (ms) = ";
+            // let start =
+            // msg.find(ident).unwrap_or(0).saturating_add(ident.len());
+            // let end = msg.find(" at use_builtins.ts").unwrap_or(0);
+            // let sc_timestamp: i64 = msg[start..end].parse().unwrap();
+            // assert!(before <= sc_timestamp && sc_timestamp <= after);
+        }
+        _ => panic!("Failed to run use_builtins.wasm"),
+    }
+}
+
+#[test]
+#[serial]
+/// Test `assert` & `process.exit
+///
+/// These are AS functions that we choose to handle in the VM
+fn test_builtin_assert_and_exit() {
+    let gas_costs = GasCosts::default();
+    let interface: Box<dyn Interface> = Box::new(TestInterface);
+    let module = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/wasm/use_builtin_assert.wasm"
+    ));
+
+    let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
+    match run_function(
+        &*interface,
+        runtime_module,
+        "assert_with_msg",
+        b"",
+        100_000,
+        gas_costs.clone(),
+    ) {
+        Err(e) => {
+            assert!(e.to_string().contains("Result is not true!"))
+        }
+        _ => panic!("test should return an error!"),
+    }
+
+    let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
+    if let Ok(_) = run_function(
+        &*interface,
+        runtime_module,
+        "assert_no_msg",
+        b"",
+        100_000,
+        gas_costs.clone(),
+    ) {
+        panic!("test should return an error!");
+    }
+
+    let module = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/wasm/use_builtin_exit.wasm"
+    ));
+
+    let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
+    match run_function(
+        &*interface,
+        runtime_module,
+        "exit_no_code",
+        b"",
+        100_000,
+        gas_costs.clone(),
+    ) {
+        Err(e) => {
+            assert!(e.to_string().contains("exit with code: 0"))
+        }
+        _ => panic!("test should return an error!"),
+    }
+
+    let runtime_module = RuntimeModule::new(module, gas_costs.clone(), Compiler::SP).unwrap();
+    match run_function(
+        &*interface,
+        runtime_module,
+        "exit_with_code",
         b"",
         100_000,
         gas_costs,
@@ -791,10 +2738,7 @@ fn test_wat() {
         match runtime_module {
             Ok(_) => assert!(false, ".wat are not supported anymore"),
             Err(err) => {
-                assert_eq!(
-                    true,
-                    err.to_string().contains("Unsupported file format for SC")
-                );
+                assert_eq!(true, err.to_string().contains("Unknown bytecode tag"));
             }
         }
     }
@@ -853,7 +2797,12 @@ fn test_class_id() {
     ));
     let module = ASModule::new(bytecode, 100_000, GasCosts::default(), Compiler::SP).unwrap();
     let mut store = Store::new(module._engine);
-    let mut context = ASContext::new(&*interface, module.binary_module, GasCosts::default());
+    let mut context = ASContext::new(
+        &*interface,
+        module.binary_module,
+        GasCosts::default(),
+        RunOptions::default(),
+    );
     let (instance, _function_env, _) = context.create_vm_instance_and_init_env(&mut store).unwrap();
 
     // setup test specific context
@@ -37,18 +37,24 @@ impl<T: Tunables> LimitingTunables<T> {
 
     /// Ensures the a given memory type does not exceed the memory limit.
     /// Call this after adjusting the memory.
+    ///
+    /// This runs when the memory is created, at instantiation: a module
+    /// declaring an initial memory over the limit is rejected before anything
+    /// is allocated.
     fn validate_memory(&self, ty: &MemoryType) -> Result<(), MemoryError> {
         if ty.minimum > self.limit {
-            return Err(MemoryError::Generic(
-                "Minimum exceeds the allowed memory limit".to_string(),
-            ));
+            return Err(MemoryError::Generic(format!(
+                "Minimum memory of {} pages exceeds the allowed memory limit of {} pages",
+                ty.minimum.0, self.limit.0
+            )));
         }
 
         if let Some(max) = ty.maximum {
             if max > self.limit {
-                return Err(MemoryError::Generic(
-                    "Maximum exceeds the allowed memory limit".to_string(),
-                ));
+                return Err(MemoryError::Generic(format!(
+                    "Maximum memory of {} pages exceeds the allowed memory limit of {} pages",
+                    max.0, self.limit.0
+                )));
             }
         } else {
             return Err(MemoryError::Generic("Maximum unset".to_string()));
@@ -2,13 +2,18 @@ use anyhow::{anyhow, bail, Result};
 use massa_proto_rs::massa::model::v1::{
     AddressCategory, ComparisonResult, NativeAmount, NativeTime, Slot,
 };
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
 use std::{
     collections::{BTreeSet, HashMap},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    time::Duration,
 };
+use wasmer::wasmparser::Operator;
 
 use crate::execution::RuntimeModule;
+use crate::middlewares::operator::{operator_field_str, OPERATOR_VARIANTS};
+use crate::settings;
 
 #[cfg(feature = "execution-trace")]
 #[derive(Debug, Clone, PartialEq, Serialize)]
@@ -152,6 +157,22 @@ pub struct Response {
     pub remaining_gas: u64,
     /// number of gas required for the instance creation
     pub init_gas_cost: u64,
+    /// gas consumed by the execution, instance creation included: the limit
+    /// given to the execution minus `remaining_gas`
+    pub gas_used: u64,
+    /// part of `gas_used` charged by the host functions, `None` if the
+    /// runtime does not track it (WasmV1)
+    pub abi_gas: Option<u64>,
+    /// Cumulated wall-clock time spent in each host function, only filled
+    /// when `RunOptions::profile_abis` is set
+    pub abi_timings: HashMap<String, Duration>,
+    /// Names of the host functions called during the execution, only filled
+    /// when `RunOptions::record_abi_usage` is set
+    pub used_abis: BTreeSet<String>,
+    /// Events generated by the execution with `assembly_script_generate_event`,
+    /// in order, sub-calls excluded. Also forwarded to the interface as they
+    /// are generated. Always empty for WasmV1 executions.
+    pub events: Vec<String>,
     #[cfg(feature = "execution-trace")]
     pub trace: Vec<AbiTrace>,
 }
@@ -166,7 +187,33 @@ impl Clone for Box<dyn Interface> {
     }
 }
 
-#[derive(Clone, Debug)]
+/// Limits on the structure of an AssemblyScript module, checked with a cheap
+/// parsing pass before compiling it, so that the compilation time of a
+/// module is bounded before any metering applies.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CompilationLimits {
+    /// Maximum number of functions defined by the module, imports excluded
+    pub max_functions: u32,
+    /// Maximum number of globals defined by the module, imports excluded
+    pub max_globals: u32,
+    /// Maximum size in bytes of the body of a function
+    pub max_function_body_size: u32,
+    /// Maximum number of imports, of any kind
+    pub max_imports: u32,
+}
+
+impl Default for CompilationLimits {
+    fn default() -> Self {
+        Self {
+            max_functions: settings::max_functions(),
+            max_globals: settings::max_globals(),
+            max_function_body_size: settings::max_function_body_size(),
+            max_imports: settings::max_imports(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GasCosts {
     pub(crate) abi_costs: HashMap<String, u64>,
     pub(crate) operator_cost: u64,
@@ -174,12 +221,92 @@ pub struct GasCosts {
     pub cl_compilation_cost: u64,
     pub sp_compilation_cost: u64,
     pub max_instance_cost: u64,
+    /// Maximum cumulative number of bytes the read ABIs can copy into the
+    /// wasm memory during an execution. Unlimited if `None`.
+    pub max_bytes_read: Option<u64>,
+    /// Maximum cumulative number of bytes of the operation datastore (keys
+    /// and values) the op-data ABIs can read during an execution, on top of
+    /// `max_bytes_read`.
+    pub max_op_data_read: u64,
+    /// Cost of specific operators, keyed by their name as in
+    /// `OPERATOR_VARIANTS` (e.g. `MemoryGrow`, `I32Add`). Overrides
+    /// `call_indirect_cost` and `operator_cost` for the listed operators.
+    ///
+    /// Loaded by `GasCosts::new` from the `operator:<name>` entries of the ABI
+    /// cost file (e.g. `operator:MemoryFill`).
+    ///
+    /// The metering middleware only sees the operator and not the number of
+    /// bytes a `memory.copy`, `memory.fill` or `memory.init` moves, which is
+    /// only known at run time: listing them here charges them a higher cost,
+    /// but a fixed one whatever their length.
+    pub operator_costs: HashMap<String, u64>,
+    /// Cost of a `call_indirect` operator, which also pays for the table
+    /// bounds and signature checks. `operator_cost` is used if zero.
+    pub call_indirect_cost: u64,
+    /// Maximum number of locals a single function can declare, checked before
+    /// compiling an AssemblyScript module.
+    pub max_locals_per_function: u32,
+    /// Limits on the structure of a module, checked before compiling it.
+    pub compilation_limits: CompilationLimits,
+    /// Reject modules declaring a wasm start function. Off by default as the
+    /// AssemblyScript compiler puts the top-level statements of a contract in
+    /// one: it runs at instantiation, with the ABIs disabled, and its gas is
+    /// part of the instance creation cost, bounded by `max_instance_cost`.
+    pub reject_start_section: bool,
+    /// Reject a non-empty parameter given to a function, other than `main`,
+    /// that takes none, instead of ignoring it. Off by default as existing
+    /// contracts may rely on the parameter being ignored.
+    pub reject_unexpected_param: bool,
+    /// Gas charged for each byte of bytecode stored by the `set_bytecode`
+    /// ABIs, on top of their flat cost.
+    pub bytecode_storage_byte_cost: u64,
+    /// Maximum size in bytes of the value returned by a sub-call, checked
+    /// before copying it into the caller memory.
+    pub max_subcall_return_size: u64,
+    /// Maximum size in bytes of the parameter of an AssemblyScript function,
+    /// checked before allocating it in the instance memory.
+    pub max_param_size: u64,
+    /// Maximum number of nested calls (`call` and `local_execution` alike)
+    /// above the top-level execution.
+    pub max_call_depth: u32,
+    /// Maximum cumulative size in bytes of the events generated during an
+    /// execution, on top of the per-event limits enforced by the node.
+    pub max_total_event_bytes: u64,
+    /// Maximum number of wasm pages (64 KiB) the memory of an instance can
+    /// grow to, overriding the global default for the executions using these
+    /// costs. A `memory.grow` past it returns -1.
+    pub memory_pages_limit: Option<u32>,
+    /// Testing only: make the metering trip after this number of executed
+    /// operators, whatever the gas limit. Every operator then costs 1 and the
+    /// execution starts with this many points. ABI costs are still charged
+    /// from the same points, set them to 0 for an exact operator count.
+    ///
+    /// Applied when compiling the module, so the same `GasCosts` must be
+    /// given to `RuntimeModule::new` and to the run function. Metering checks
+    /// the points at the start of each block, so the trap happens on the
+    /// block containing the Nth operator. AssemblyScript modules only.
+    #[cfg(any(test, feature = "testing"))]
+    pub force_oog_after: Option<u64>,
 }
 
 impl GasCosts {
     pub fn new(abi_cost_file: PathBuf, wasm_abi_file: PathBuf) -> Result<Self> {
         let abi_cost_file = std::fs::read_to_string(abi_cost_file)?;
-        let mut abi_costs: HashMap<String, u64> = serde_json::from_str(&abi_cost_file)?;
+        let abi_costs: HashMap<String, u64> = serde_json::from_str(&abi_cost_file)?;
+        // operator costs are not rounded like the ABI ones
+        let (operator_costs, mut abi_costs): (HashMap<_, _>, HashMap<_, _>) = abi_costs
+            .into_iter()
+            .partition(|(name, _)| name.starts_with(settings::OPERATOR_COST_PREFIX));
+        let operator_costs = operator_costs
+            .into_iter()
+            .map(|(name, cost)| {
+                let operator = &name[settings::OPERATOR_COST_PREFIX.len()..];
+                if !OPERATOR_VARIANTS.contains(&operator) {
+                    bail!("unknown operator {} in ABI gas cost file.", operator);
+                }
+                Ok((operator.to_string(), cost))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
         abi_costs.iter_mut().for_each(|(_, v)| {
             let unit_digit = *v % 10;
             if unit_digit > 5 {
@@ -204,16 +331,85 @@ impl GasCosts {
             max_instance_cost: *abi_costs
                 .get("max_instance")
                 .ok_or_else(|| anyhow!("max_instance cost not found in ABI gas cost file."))?,
+            max_bytes_read: None,
+            max_op_data_read: settings::max_op_data_read(),
+            operator_costs,
+            call_indirect_cost: 0,
+            max_locals_per_function: settings::max_locals_per_function(),
+            compilation_limits: Default::default(),
+            reject_start_section: false,
+            reject_unexpected_param: false,
+            bytecode_storage_byte_cost: 0,
+            max_subcall_return_size: settings::max_subcall_return_size(),
+            max_param_size: settings::max_param_size(),
+            max_call_depth: settings::max_call_depth(),
+            max_total_event_bytes: settings::max_total_event_bytes(),
+            memory_pages_limit: None,
+            #[cfg(any(test, feature = "testing"))]
+            force_oog_after: None,
             abi_costs,
         })
     }
+
+    /// Load gas costs serialized in JSON, in the format produced by
+    /// serializing a `GasCosts`, from the given file
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        Self::from_json_slice(&std::fs::read(path)?)
+    }
+
+    /// Load gas costs serialized in JSON, see `from_json_file`.
+    ///
+    /// Fails if `operator_cost` is 0, as metering would then be disabled, or
+    /// if an ABI of the runtime has no cost.
+    pub fn from_json_slice(json: &[u8]) -> Result<Self> {
+        let gas_costs: Self = serde_json::from_slice(json)?;
+        if gas_costs.operator_cost == 0 {
+            bail!("invalid gas costs: operator_cost must not be 0");
+        }
+        let mut missing: Vec<_> = GasCosts::default()
+            .abi_costs
+            .into_keys()
+            .filter(|abi| !gas_costs.abi_costs.contains_key(abi))
+            .collect();
+        if !missing.is_empty() {
+            missing.sort();
+            bail!("invalid gas costs: no cost for {}", missing.join(", "));
+        }
+        Ok(gas_costs)
+    }
+
+    /// Storage cost of a bytecode of `len` bytes stored by the
+    /// `set_bytecode` ABIs
+    pub(crate) fn bytecode_storage_cost(&self, len: usize) -> u64 {
+        self.bytecode_storage_byte_cost.saturating_mul(len as u64)
+    }
+
+    /// Cost charged by the metering middleware for the given operator
+    pub(crate) fn metered_operator_cost(&self, operator: &Operator) -> u64 {
+        #[cfg(any(test, feature = "testing"))]
+        if self.force_oog_after.is_some() {
+            return 1;
+        }
+        if let Some(cost) = self.operator_costs.get(operator_field_str(operator)) {
+            return *cost;
+        }
+        match operator {
+            Operator::CallIndirect { .. } if self.call_indirect_cost != 0 => {
+                self.call_indirect_cost
+            }
+            _ => self.operator_cost,
+        }
+    }
 }
 
-#[cfg(any(test, feature = "gas_calibration", feature = "testing"))]
+/// Production costs, also the reference list of the ABIs that must have a cost,
+/// see `GasCosts::from_json_slice`
 impl Default for GasCosts {
     fn default() -> Self {
         let mut abi_costs = HashMap::new();
         abi_costs.insert(String::from("assembly_script_address_from_public_key"), 147);
+        abi_costs.insert(String::from("assembly_script_address_matches_pubkey"), 147);
+        abi_costs.insert(String::from("assembly_script_resolve_name"), 80);
         abi_costs.insert(String::from("assembly_script_validate_address"), 4);
         abi_costs.insert(String::from("assembly_script_append_data"), 162);
         abi_costs.insert(String::from("assembly_script_append_data_for"), 200);
@@ -222,35 +418,94 @@ impl Default for GasCosts {
         abi_costs.insert(String::from("assembly_script_delete_data"), 78);
         abi_costs.insert(String::from("assembly_script_delete_data_for"), 120);
         abi_costs.insert(String::from("assembly_script_generate_event"), 36);
+        abi_costs.insert(String::from("assembly_script_generate_error_event"), 46); // generate_event + 10
         abi_costs.insert(String::from("assembly_script_get_balance"), 4);
+        abi_costs.insert(String::from("assembly_script_get_min_balance"), 4);
+        abi_costs.insert(String::from("assembly_script_get_estimated_refund"), 5);
+        abi_costs.insert(String::from("assembly_script_get_interface_version"), 20);
         abi_costs.insert(String::from("assembly_script_get_balance_for"), 41);
+        abi_costs.insert(String::from("assembly_script_get_balance_delta"), 4);
+        abi_costs.insert(
+            String::from("assembly_script_get_remaining_transferable"),
+            4,
+        );
         abi_costs.insert(String::from("assembly_script_get_call_coins"), 9);
+        abi_costs.insert(String::from("assembly_script_get_op_deposit"), 9);
+        abi_costs.insert(String::from("assembly_script_get_priority_tier"), 9);
         abi_costs.insert(String::from("assembly_script_get_call_stack"), 56);
         abi_costs.insert(String::from("assembly_script_get_current_slot"), 9);
         abi_costs.insert(String::from("assembly_script_get_data"), 85);
+        abi_costs.insert(String::from("assembly_script_get_data_multi"), 85);
+        abi_costs.insert(String::from("assembly_script_get_data_multi_key"), 30);
+        abi_costs.insert(String::from("assembly_script_get_data_multi_byte"), 1);
+        abi_costs.insert(String::from("assembly_script_get_data_at_slot"), 500);
         abi_costs.insert(String::from("assembly_script_get_data_for"), 139);
         abi_costs.insert(String::from("assembly_script_get_keys"), 26);
         abi_costs.insert(String::from("assembly_script_get_keys_for"), 48);
+        abi_costs.insert(String::from("assembly_script_get_keys_prefix"), 26);
+        abi_costs.insert(String::from("assembly_script_get_keys_prefix_key"), 2);
+        abi_costs.insert(String::from("assembly_script_datastore_diff"), 200);
+        abi_costs.insert(String::from("assembly_script_datastore_diff_entry"), 30);
+        abi_costs.insert(String::from("assembly_script_get_writers"), 100);
+        abi_costs.insert(String::from("assembly_script_get_writers_entry"), 10);
         abi_costs.insert(String::from("assembly_script_get_op_data"), 71);
+        abi_costs.insert(String::from("assembly_script_get_op_data_multi"), 71);
+        abi_costs.insert(String::from("assembly_script_get_op_data_multi_key"), 30);
+        abi_costs.insert(String::from("assembly_script_get_op_data_multi_byte"), 1);
+        abi_costs.insert(String::from("assembly_script_get_message_data"), 50);
+        abi_costs.insert(String::from("assembly_script_get_message_data_byte"), 1);
         abi_costs.insert(String::from("assembly_script_get_op_keys"), 138);
         abi_costs.insert(String::from("assembly_script_get_op_keys_prefix"), 138);
         abi_costs.insert(String::from("assembly_script_get_owned_addresses"), 52);
         abi_costs.insert(String::from("assembly_script_get_remaining_gas"), 7);
+        abi_costs.insert(String::from("assembly_script_has_gas_at_least"), 7);
+        abi_costs.insert(String::from("assembly_script_estimate_abi_cost"), 10);
+        abi_costs.insert(String::from("assembly_script_get_op_count"), 7);
+        abi_costs.insert(String::from("assembly_script_get_remaining_time_ms"), 7);
+        abi_costs.insert(String::from("assembly_script_get_logical_clock"), 7);
         abi_costs.insert(String::from("assembly_script_get_time"), 4);
+        abi_costs.insert(String::from("assembly_script_get_runtime_version"), 4);
+        abi_costs.insert(String::from("assembly_script_compare_slot"), 10);
         abi_costs.insert(String::from("assembly_script_has_data"), 69);
         abi_costs.insert(String::from("assembly_script_has_data_for"), 115);
         abi_costs.insert(String::from("assembly_script_has_op_key"), 78);
         abi_costs.insert(String::from("assembly_script_hash"), 83);
         abi_costs.insert(String::from("assembly_script_hash_sha256"), 83);
         abi_costs.insert(String::from("assembly_script_keccak256_hash"), 83);
+        abi_costs.insert(String::from("assembly_script_hash_ex_blake3"), 83);
+        abi_costs.insert(String::from("assembly_script_hash_ex_sha256"), 83);
+        abi_costs.insert(String::from("assembly_script_hash_ex_sha3_256"), 83);
+        abi_costs.insert(String::from("assembly_script_hash_ex_keccak256"), 83);
+        abi_costs.insert(String::from("assembly_script_hash_blake3"), 83);
+        abi_costs.insert(String::from("assembly_script_hash_blake3_byte"), 1);
         abi_costs.insert(String::from("assembly_script_print"), 35);
         abi_costs.insert(String::from("assembly_script_send_message"), 316);
+        abi_costs.insert(String::from("assembly_script_get_my_scheduled_slots"), 100);
+        abi_costs.insert(String::from("assembly_script_get_my_deferred_coins"), 56);
+        abi_costs.insert(String::from("assembly_script_get_my_storage_bytes"), 56);
+        abi_costs.insert(
+            String::from("assembly_script_get_my_scheduled_slots_entry"),
+            11,
+        );
         abi_costs.insert(String::from("assembly_script_get_origin_operation_id"), 200);
+        abi_costs.insert(String::from("assembly_script_get_block_producer"), 20);
         abi_costs.insert(String::from("assembly_script_set_bytecode"), 74);
         abi_costs.insert(String::from("assembly_script_set_bytecode_for"), 129);
         abi_costs.insert(String::from("assembly_script_set_data"), 158);
         abi_costs.insert(String::from("assembly_script_set_data_for"), 165);
+        abi_costs.insert(String::from("assembly_script_set_data_if_absent"), 158);
+        abi_costs.insert(String::from("assembly_script_swap_data"), 486);
+        abi_costs.insert(String::from("assembly_script_add_to_data"), 312);
+        abi_costs.insert(String::from("assembly_script_rate_limit"), 250);
+        abi_costs.insert(String::from("assembly_script_savepoint"), 50);
+        abi_costs.insert(String::from("assembly_script_rollback_to"), 100);
         abi_costs.insert(String::from("assembly_script_signature_verify"), 98);
+        abi_costs.insert(String::from("assembly_script_verify_threshold"), 11);
+        abi_costs.insert(String::from("assembly_script_verify_state_inclusion"), 500);
+        abi_costs.insert(
+            String::from("assembly_script_verify_state_inclusion_byte"),
+            1,
+        );
         abi_costs.insert(String::from("assembly_script_evm_signature_verify"), 264);
         abi_costs.insert(
             String::from("assembly_script_evm_get_address_from_pubkey"),
@@ -262,8 +517,10 @@ impl Default for GasCosts {
         );
         abi_costs.insert(String::from("assembly_script_is_address_eoa"), 11);
         abi_costs.insert(String::from("assembly_script_transfer_coins"), 62);
+        abi_costs.insert(String::from("assembly_script_get_paid_to"), 10);
         abi_costs.insert(String::from("assembly_script_transfer_coins_for"), 102);
         abi_costs.insert(String::from("assembly_script_unsafe_random"), 11);
+        abi_costs.insert(String::from("assembly_script_random_from_seed"), 83);
         abi_costs.insert(String::from("assembly_script_call"), 11);
         abi_costs.insert(String::from("assembly_script_local_call"), 11);
         abi_costs.insert(String::from("assembly_script_local_execution"), 11);
@@ -281,6 +538,7 @@ impl Default for GasCosts {
         abi_costs.insert(String::from("assembly_script_console_error"), 36);
         abi_costs.insert(String::from("assembly_script_trace"), 36);
         abi_costs.insert(String::from("assembly_script_chain_id"), 9);
+        abi_costs.insert(String::from("assembly_script_get_fee_split"), 9);
         Self {
             abi_costs,
             operator_cost: 1,
@@ -288,10 +546,105 @@ impl Default for GasCosts {
             sp_compilation_cost: 314_000_000,
             cl_compilation_cost: 745_000_000,
             max_instance_cost: 2_100_000,
+            max_bytes_read: None,
+            max_op_data_read: settings::max_op_data_read(),
+            operator_costs: HashMap::new(),
+            call_indirect_cost: 0,
+            max_locals_per_function: settings::max_locals_per_function(),
+            compilation_limits: Default::default(),
+            reject_start_section: false,
+            reject_unexpected_param: false,
+            bytecode_storage_byte_cost: 0,
+            max_subcall_return_size: settings::max_subcall_return_size(),
+            max_param_size: settings::max_param_size(),
+            max_call_depth: settings::max_call_depth(),
+            max_total_event_bytes: settings::max_total_event_bytes(),
+            memory_pages_limit: None,
+            #[cfg(any(test, feature = "testing"))]
+            force_oog_after: None,
+        }
+    }
+}
+
+/// Options of a single execution, as opposed to the `GasCosts` shared by all
+/// of them, see `run_function_with_options`.
+///
+/// Inherited by the sub-calls of the execution. Only applied to AssemblyScript
+/// executions: WasmV1 executions ignore them, but pass them down to the calls
+/// they make.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RunOptions {
+    /// Network bootstrap mode, node-internal only.
+    ///
+    /// DANGEROUS: relaxes some invariants (e.g. transfers from system accounts
+    /// that have no balance yet) by routing the affected ABIs to the
+    /// interface bootstrap policy. Must never be enabled for user executions.
+    pub bootstrap: bool,
+    /// Pure mode, stricter than read-only: the execution cannot observe nor
+    /// move coins, so it can only compute over its inputs and the datastore.
+    /// The following ABIs abort in this mode, sub-calls included:
+    /// `get_balance`, `get_balance_for`, `get_balance_delta`,
+    /// `get_remaining_transferable`, `get_call_coins`, `get_op_deposit`,
+    /// `get_my_deferred_coins`, `transfer_coins` and `transfer_coins_for`.
+    pub pure: bool,
+    /// Operational profiling: measure the wall-clock time spent in each host
+    /// function of an AssemblyScript execution, see `Response::abi_timings`.
+    /// Timings are non-deterministic and never affect gas. Off by default.
+    pub profile_abis: bool,
+    /// Record the names of the host functions called by an AssemblyScript
+    /// execution, see `Response::used_abis`. Sub-calls are not included. Off
+    /// by default.
+    pub record_abi_usage: bool,
+    /// Development aid: capture the wasm frames of a trapping AssemblyScript
+    /// execution into the returned error. Off by default as building the
+    /// dump has a cost on every failed execution.
+    pub verbose_traps: bool,
+    /// Optional label attached to the execution tracing spans, used to
+    /// aggregate metrics by contract category. No effect on the execution.
+    pub label: Option<String>,
+    /// Wall-clock time limit of an execution, sub-calls included. Checked by
+    /// every ABI call: an execution past it fails with `VMError::Timeout`.
+    /// Meant for simulations, to stop executions stuck in slow interface
+    /// callbacks where metering cannot trigger, and exposed to the contracts
+    /// by `assembly_script_get_remaining_time_ms` so they can wrap up before.
+    /// Unlimited if `None`.
+    pub max_execution_time: Option<Duration>,
+}
+
+/// Behavior of `Interface::add_to_data` when the counter would leave the u64
+/// bounds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CounterOverflow {
+    /// The call fails and the counter is left unchanged
+    Checked,
+    /// The result is clamped to the bounds
+    Saturating,
+    /// The result wraps around modulo 2^64
+    Wrapping,
+}
+
+impl CounterOverflow {
+    /// Add a signed `delta` to the `current` counter value
+    pub fn add(self, current: u64, delta: i64) -> Result<u64> {
+        match self {
+            CounterOverflow::Checked => match current.checked_add_signed(delta) {
+                Some(res) => Ok(res),
+                None => bail!("Counter overflow"),
+            },
+            CounterOverflow::Saturating => Ok(current.saturating_add_signed(delta)),
+            CounterOverflow::Wrapping => Ok(current.wrapping_add_signed(delta)),
         }
     }
 }
 
+/// Host side of the ABIs, implemented by the node.
+///
+/// The host calls of an execution are serialized in the order the module makes
+/// them. Every datastore operation, the conditional ones included
+/// (`set_data_if_absent`, `swap_data`, `add_to_data`), must observe the
+/// outcome of all the operations made before it in the same execution: a read
+/// following a conditional write reflects that write. Implementations
+/// executing contracts in parallel must preserve this serialized view.
 #[allow(unused_variables)]
 pub trait Interface: Send + Sync + InterfaceClone {
     /// Prepare the execution of a module at the given address and transfer a
@@ -314,6 +667,13 @@ pub trait Interface: Send + Sync + InterfaceClone {
     /// Defaults to zero if the address is not found.
     fn get_balance_for(&self, address: &str) -> Result<u64>;
 
+    /// Get the amount the current address can still transfer, taking into
+    /// account the transfers already made during the current execution.
+    /// Defaults to the live balance of the current address.
+    fn get_remaining_transferable(&self) -> Result<u64> {
+        self.get_balance()
+    }
+
     fn get_balance_wasmv1(&self, address: Option<String>) -> Result<NativeAmount>;
 
     /// Transfer an amount from the address on the current call stack to a
@@ -328,6 +688,20 @@ pub trait Interface: Send + Sync + InterfaceClone {
         raw_amount: u64,
     ) -> Result<()>;
 
+    /// Transfer an amount during the network bootstrap, bypassing the balance
+    /// checks that cannot hold before the ledger is initialized.
+    ///
+    /// Only called when `RunOptions::bootstrap` is set, `from_address` is `None`
+    /// for a transfer from the address on the current call stack.
+    fn transfer_coins_bootstrap(
+        &self,
+        from_address: Option<&str>,
+        to_address: &str,
+        raw_amount: u64,
+    ) -> Result<()> {
+        bail!("unimplemented function transfer_coins_bootstrap in interface")
+    }
+
     fn transfer_coins_wasmv1(
         &self,
         to_address: String,
@@ -345,6 +719,28 @@ pub trait Interface: Send + Sync + InterfaceClone {
     /// the caller of the currently executing code.
     fn get_call_coins_wasmv1(&self) -> Result<NativeAmount>;
 
+    /// Get the data attached to the deferred message that triggered the
+    /// current execution, empty if it was not triggered by a message.
+    fn get_message_data(&self) -> Result<Vec<u8>> {
+        bail!("unimplemented function get_message_data in interface")
+    }
+
+    /// Get the amount of coins deposited by the operation that originated the
+    /// current execution.
+    ///
+    /// Unlike the call coins, this does not change along the call stack.
+    fn get_op_deposit(&self) -> Result<u64> {
+        bail!("unimplemented function get_op_deposit in interface")
+    }
+
+    /// Get the fee market priority tier of the operation that originated the
+    /// current execution, 0 being the standard tier.
+    ///
+    /// Defaults to the standard tier for nodes without priority tiers.
+    fn get_priority_tier(&self) -> Result<u8> {
+        Ok(0)
+    }
+
     /// Sets the executable bytecode at a current address.
     fn raw_set_bytecode(&self, bytecode: &[u8]) -> Result<()>;
 
@@ -369,6 +765,27 @@ pub trait Interface: Send + Sync + InterfaceClone {
     /// Will only return keys with a given prefix if provided in args
     fn get_keys_for(&self, address: &str, prefix: Option<&[u8]>) -> Result<BTreeSet<Vec<u8>>>;
 
+    /// Return at most `max` datastore keys of the current address sharing the
+    /// given prefix, in ascending order.
+    /// Defaults to truncating the result of `get_keys`.
+    fn get_keys_prefix(&self, prefix: &[u8], max: usize) -> Result<BTreeSet<Vec<u8>>> {
+        default_get_keys_prefix(self, prefix, max)
+    }
+
+    /// Return the datastore keys under the given prefix that differ between
+    /// two addresses (present in only one of them, or with different values),
+    /// along with the number of entries compared.
+    ///
+    /// Must fail if the datastore of one of the addresses cannot be read.
+    fn datastore_diff(
+        &self,
+        address_a: &str,
+        address_b: &str,
+        prefix: Option<&[u8]>,
+    ) -> Result<(BTreeSet<Vec<u8>>, u64)> {
+        default_datastore_diff(self, address_a, address_b, prefix)
+    }
+
     fn get_ds_keys_wasmv1(
         &self,
         prefix: &[u8],
@@ -378,6 +795,21 @@ pub trait Interface: Send + Sync + InterfaceClone {
     /// Return the datastore value of the corresponding key
     fn raw_get_data(&self, key: &[u8]) -> Result<Vec<u8>>;
 
+    /// Return the datastore values of the given keys of the current address,
+    /// in the same order, `None` for the missing keys
+    fn get_data_multi(&self, keys: &[Vec<u8>]) -> Result<Vec<Option<Vec<u8>>>> {
+        default_get_data_multi(self, keys)
+    }
+
+    /// Return the value the datastore key of the current address had at the
+    /// given (period, thread) slot, `None` if the key was missing.
+    ///
+    /// Must fail if the slot is not finalized, to keep executions
+    /// deterministic.
+    fn get_data_at_slot(&self, _key: &[u8], _slot: (u64, u8)) -> Result<Option<Vec<u8>>> {
+        bail!("unimplemented function get_data_at_slot in interface")
+    }
+
     /// Requires the data at the address
     fn raw_get_data_for(&self, address: &str, key: &[u8]) -> Result<Vec<u8>>;
 
@@ -386,6 +818,87 @@ pub trait Interface: Send + Sync + InterfaceClone {
     /// Set the datastore value for the corresponding key
     fn raw_set_data(&self, key: &[u8], value: &[u8]) -> Result<()>;
 
+    /// Map a datastore key used by a contract to the key actually read or
+    /// written in the datastore of the given address, the current one if
+    /// `None`. Defaults to the identity.
+    ///
+    /// Consulted by every AssemblyScript ABI accessing a datastore entry by
+    /// key (get, set, has, delete, append and the conditional writes), so
+    /// that round-trips stay coherent. Listed keys are returned as stored.
+    /// A non-identity mapping applies to all the contracts and must be
+    /// deterministic, e.g. to namespace keys or during a storage migration.
+    fn map_key(&self, _address: Option<&str>, key: &[u8]) -> Result<Vec<u8>> {
+        Ok(key.to_vec())
+    }
+
+    /// Set the datastore value for the corresponding key only if the key does
+    /// not exist yet. Returns whether the value was written.
+    ///
+    /// Implementors should override this to make the check and the write a
+    /// single atomic operation: the default makes two separate calls, another
+    /// write to the key can thus happen between them.
+    fn set_data_if_absent(&self, key: &[u8], value: &[u8]) -> Result<bool> {
+        default_set_data_if_absent(self, key, value)
+    }
+
+    /// Swap the datastore values of two keys of the current address. If only
+    /// one of them exists, its value is moved to the other key.
+    ///
+    /// Implementors should override this to make the swap a single atomic
+    /// operation: the default reads then writes both keys in separate calls,
+    /// another write to either key can thus happen in the middle.
+    fn swap_data(&self, key_a: &[u8], key_b: &[u8]) -> Result<()> {
+        default_swap_data(self, key_a, key_b)
+    }
+
+    /// Count an operation against the rate limit counter stored at `key` in
+    /// the datastore of the current address. Returns whether it is allowed,
+    /// in which case it is counted.
+    ///
+    /// Periods are grouped in windows of `period` consecutive periods aligned
+    /// on 0: window `n` covers the periods `[n * period, (n + 1) * period)`,
+    /// whatever the thread. The counter resets on the first call of a new
+    /// window. It is stored on 16 bytes: the window index then the number of
+    /// operations counted in it, both u64 little endian.
+    ///
+    /// Implementors should override this to make the read, the check and the
+    /// write a single atomic operation: the default makes separate calls,
+    /// another write to the counter can thus happen in the middle.
+    fn rate_limit(&self, key: &[u8], max_per_period: u64, period: u64) -> Result<bool> {
+        default_rate_limit(self, key, max_per_period, period)
+    }
+
+    /// Add a signed `delta` to the counter stored at `key` in the datastore of
+    /// the current address and return its new value.
+    ///
+    /// The counter is an u64 stored in little endian on exactly 8 bytes, a
+    /// missing key being read as 0. `overflow` selects the behavior when the
+    /// result is out of the u64 bounds.
+    ///
+    /// Implementors should override this to make the read and the write a
+    /// single atomic operation.
+    fn add_to_data(&self, key: &[u8], delta: i64, overflow: CounterOverflow) -> Result<u64> {
+        default_add_to_data(self, key, delta, overflow)
+    }
+
+    /// Take a savepoint of the state written during the current execution
+    ///
+    /// Returns `None` if savepoints are not supported, in which case
+    /// `assembly_script_savepoint` returns -1 and rolling back to it is a
+    /// no-op.
+    fn savepoint(&self) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Undo every state write made since the given savepoint, which stays
+    /// valid, and release the savepoints taken after it
+    ///
+    /// Only called with ids returned by `savepoint` during the current
+    /// execution.
+    fn rollback_to(&self, _id: u64) -> Result<()> {
+        Ok(())
+    }
+
     /// Set the datastore value for the corresponding key of the given address
     fn raw_set_data_for(&self, address: &str, key: &[u8], value: &[u8]) -> Result<()>;
 
@@ -444,6 +957,18 @@ pub trait Interface: Send + Sync + InterfaceClone {
     /// Return operation datastore data for a given key
     fn get_op_data(&self, key: &[u8]) -> Result<Vec<u8>>;
 
+    /// Return the operation datastore values of the given keys, in the same
+    /// order, `None` for the missing keys
+    fn get_op_data_multi(&self, keys: &[Vec<u8>]) -> Result<Vec<Option<Vec<u8>>>> {
+        keys.iter()
+            .map(|key| {
+                self.op_entry_exists(key)?
+                    .then(|| self.get_op_data(key))
+                    .transpose()
+            })
+            .collect()
+    }
+
     /// Check whether or not the caller has write access in the current context
     fn caller_has_write_access(&self) -> Result<bool>;
 
@@ -456,6 +981,45 @@ pub trait Interface: Send + Sync + InterfaceClone {
     /// Verify signature
     fn signature_verify(&self, data: &[u8], signature: &str, public_key: &str) -> Result<bool>;
 
+    /// Verify a threshold (m-of-n) signature
+    ///
+    /// Returns true if at least `threshold` of the (`signatures[i]`,
+    /// `public_keys[i]`) pairs are valid signatures of `data`.
+    fn verify_threshold(
+        &self,
+        data: &[u8],
+        signatures: &[String],
+        public_keys: &[String],
+        threshold: u32,
+    ) -> Result<bool> {
+        let mut valid_count: u32 = 0;
+        for (signature, public_key) in signatures.iter().zip(public_keys) {
+            if valid_count >= threshold {
+                break;
+            }
+            if self.signature_verify(data, signature, public_key)? {
+                valid_count += 1;
+            }
+        }
+        Ok(valid_count >= threshold)
+    }
+
+    /// Verify that the datastore entry (`address`, `key`, `value`) is included
+    /// in the latest finalized state root, given a Merkle proof serialized in
+    /// the format of the node state trie
+    ///
+    /// Must only verify against finalized roots, to keep executions
+    /// deterministic.
+    fn verify_state_inclusion(
+        &self,
+        _address: &str,
+        _key: &[u8],
+        _value: &[u8],
+        _proof: &[u8],
+    ) -> Result<bool> {
+        bail!("unimplemented function verify_state_inclusion in interface")
+    }
+
     /// Verify signature (EVM)
     fn evm_signature_verify(
         &self,
@@ -477,6 +1041,12 @@ pub trait Interface: Send + Sync + InterfaceClone {
     /// Convert a public key to an address
     fn address_from_public_key(&self, public_key: &str) -> Result<String>;
 
+    /// Resolve a name of the name registry to its address, `None` if the
+    /// name is not registered.
+    fn resolve_name(&self, _name: &str) -> Result<Option<String>> {
+        bail!("unimplemented function resolve_name in interface")
+    }
+
     /// Validate an address
     fn validate_address(&self, address: &str) -> Result<bool>;
 
@@ -492,15 +1062,127 @@ pub trait Interface: Send + Sync + InterfaceClone {
     /// Returns a random number (unsafe: can be predicted and manipulated)
     fn unsafe_random_wasmv1(&self, num_bytes: u64) -> Result<Vec<u8>>;
 
-    /// Returns the period of the current execution slot
-    fn get_current_period(&self) -> Result<u64>;
+    /// Returns the period of the current execution slot. Defaults to the
+    /// period of `get_current_slot`.
+    fn get_current_period(&self) -> Result<u64> {
+        Ok(self.get_current_slot()?.period)
+    }
 
-    /// Returns the thread of the current execution slot
-    fn get_current_thread(&self) -> Result<u8>;
+    /// Returns the thread of the current execution slot. Defaults to the
+    /// thread of `get_current_slot`.
+    fn get_current_thread(&self) -> Result<u8> {
+        Ok(self.get_current_slot()?.thread.try_into()?)
+    }
 
     /// Returns the current execution slot
     fn get_current_slot(&self) -> Result<Slot>;
 
+    /// Returns a pseudo-random number derived from the given seed and the
+    /// current execution slot.
+    ///
+    /// Unlike `unsafe_random`, the result only depends on the seed and the
+    /// slot, so every node executing the slot gets the same value: it is safe
+    /// for consensus, but anyone knowing the seed can predict it.
+    /// Defaults to the first 8 bytes (little-endian) of the `hash` of the seed
+    /// followed by the slot period (u64 LE) and thread (u8).
+    fn random_from_seed(&self, seed: &[u8]) -> Result<i64> {
+        let slot = self.get_current_slot()?;
+        let Ok(thread) = u8::try_from(slot.thread) else {
+            bail!("Invalid thread");
+        };
+        let hash = self.hash(&[seed, &slot.period.to_le_bytes(), &[thread]].concat())?;
+        let mut value = [0; 8];
+        value.copy_from_slice(&hash[..8]);
+        Ok(i64::from_le_bytes(value))
+    }
+
+    /// Returns the (period, thread) slots at which the given address has
+    /// pending scheduled executions
+    ///
+    /// Required on smart-contract execute the imported function
+    /// `assembly_script_get_my_scheduled_slots`
+    fn get_scheduled_slots(&self, _address: &str) -> Result<Vec<(u64, u8)>> {
+        bail!("unimplemented function get_scheduled_slots in interface")
+    }
+
+    /// Returns the sum of the coins attached to the pending scheduled messages
+    /// sent by the given address
+    ///
+    /// Required on smart-contract execute the imported function
+    /// `assembly_script_get_my_deferred_coins`
+    fn get_deferred_coins(&self, _address: &str) -> Result<u64> {
+        bail!("unimplemented function get_deferred_coins in interface")
+    }
+
+    /// Returns the addresses allowed to write to the datastore of the given
+    /// address, which can be any address. The permission model is the one of
+    /// the node: typically the address itself and, for a smart contract, the
+    /// addresses it delegated write access to.
+    ///
+    /// Required on smart-contract execute the imported function
+    /// `assembly_script_get_writers`
+    fn get_writers(&self, _address: &str) -> Result<BTreeSet<String>> {
+        bail!("unimplemented function get_writers in interface")
+    }
+
+    /// Returns the minimum balance of the network, in raw coins: an account
+    /// whose balance falls below it may be reaped by the node, losing its
+    /// remaining coins and storage. 0 if accounts are never reaped.
+    ///
+    /// Required on smart-contract execute the imported function
+    /// `assembly_script_get_min_balance`
+    fn get_min_balance(&self) -> Result<u64> {
+        bail!("unimplemented function get_min_balance in interface")
+    }
+
+    /// Returns the gas price of the current operation, in raw coins per unit
+    /// of gas.
+    ///
+    /// Required on smart-contract execute the imported function
+    /// `assembly_script_get_estimated_refund`
+    fn get_gas_price(&self) -> Result<u64> {
+        bail!("unimplemented function get_gas_price in interface")
+    }
+
+    /// Returns the interface version declared by the contract at the given
+    /// address when it was deployed, `None` if it declares none. Read-only.
+    ///
+    /// Required on smart-contract execute the imported function
+    /// `assembly_script_get_interface_version`
+    fn get_interface_version(&self, _address: &str) -> Result<Option<u32>> {
+        bail!("unimplemented function get_interface_version in interface")
+    }
+
+    /// Generate a smart contract event tagged as an error with the given
+    /// code. The execution is not reverted.
+    ///
+    /// Required on smart-contract execute the imported function
+    /// `assembly_script_generate_error_event`
+    fn generate_error_event(&self, _code: u32, _message: String) -> Result<()> {
+        bail!("unimplemented function generate_error_event in interface")
+    }
+
+    /// Returns the address of the producer of the block being executed.
+    ///
+    /// Deterministic: it is fixed for all the executions of a block, and is
+    /// the same when the block is replayed.
+    ///
+    /// Required on smart-contract execute the imported function
+    /// `assembly_script_get_block_producer`
+    fn get_block_producer(&self) -> Result<String> {
+        bail!("unimplemented function get_block_producer in interface")
+    }
+
+    /// Returns the number of bytes the given address occupies in the
+    /// datastore: the sum of the key and value lengths of all its entries.
+    /// The bytecode and any per-entry storage overhead are not included.
+    ///
+    /// Required on smart-contract execute the imported function
+    /// `assembly_script_get_my_storage_bytes`
+    fn get_storage_bytes(&self, _address: &str) -> Result<u64> {
+        bail!("unimplemented function get_storage_bytes in interface")
+    }
+
     /// Expect to return a list of owned addresses
     ///
     /// Required on smart-contract execute the imported function
@@ -570,9 +1252,20 @@ pub trait Interface: Send + Sync + InterfaceClone {
     // Keccak256 hash bytes
     fn hash_keccak256(&self, bytes: &[u8]) -> Result<[u8; 32]>;
 
+    // Sha3-256 hash bytes
+    fn hash_sha3_256(&self, bytes: &[u8]) -> Result<[u8; 32]> {
+        Ok(Sha3_256::digest(bytes).into())
+    }
+
     // Return the current chain id
     fn chain_id(&self) -> Result<u64>;
 
+    /// Returns the share of the gas fees that is burned, in permille (at most
+    /// 1000), the rest being redistributed to the block producers
+    fn get_fee_split(&self) -> Result<u16> {
+        bail!("unimplemented function get_fee_split in interface")
+    }
+
     fn native_amount_from_str_wasmv1(&self, amount: &str) -> Result<NativeAmount>;
 
     fn native_amount_to_string_wasmv1(&self, amount: &NativeAmount) -> Result<String>;
@@ -672,6 +1365,145 @@ pub trait Interface: Send + Sync + InterfaceClone {
     fn save_gas_remaining_before_subexecution(&self, gas_used_until: u64);
 }
 
+/// Default implementation of `Interface::get_keys_prefix`
+pub(crate) fn default_get_keys_prefix<I: Interface + ?Sized>(
+    interface: &I,
+    prefix: &[u8],
+    max: usize,
+) -> Result<BTreeSet<Vec<u8>>> {
+    let prefix = (!prefix.is_empty()).then_some(prefix);
+    Ok(interface.get_keys(prefix)?.into_iter().take(max).collect())
+}
+
+/// Default implementation of `Interface::datastore_diff`
+pub(crate) fn default_datastore_diff<I: Interface + ?Sized>(
+    interface: &I,
+    address_a: &str,
+    address_b: &str,
+    prefix: Option<&[u8]>,
+) -> Result<(BTreeSet<Vec<u8>>, u64)> {
+    let keys_a = interface.get_keys_for(address_a, prefix)?;
+    let keys_b = interface.get_keys_for(address_b, prefix)?;
+    // keys present in only one of the datastores always differ
+    let mut diff: BTreeSet<Vec<u8>> = keys_a.symmetric_difference(&keys_b).cloned().collect();
+    for key in keys_a.intersection(&keys_b) {
+        if interface.raw_get_data_for(address_a, key)?
+            != interface.raw_get_data_for(address_b, key)?
+        {
+            diff.insert(key.clone());
+        }
+    }
+    let compared_entries = keys_a.union(&keys_b).count() as u64;
+    Ok((diff, compared_entries))
+}
+
+/// Default implementation of `Interface::get_data_multi`
+pub(crate) fn default_get_data_multi<I: Interface + ?Sized>(
+    interface: &I,
+    keys: &[Vec<u8>],
+) -> Result<Vec<Option<Vec<u8>>>> {
+    keys.iter()
+        .map(|key| {
+            interface
+                .has_data(key)?
+                .then(|| interface.raw_get_data(key))
+                .transpose()
+        })
+        .collect()
+}
+
+/// Default implementation of `Interface::set_data_if_absent`
+pub(crate) fn default_set_data_if_absent<I: Interface + ?Sized>(
+    interface: &I,
+    key: &[u8],
+    value: &[u8],
+) -> Result<bool> {
+    if interface.has_data(key)? {
+        return Ok(false);
+    }
+    interface.raw_set_data(key, value)?;
+    Ok(true)
+}
+
+/// Default implementation of `Interface::swap_data`
+pub(crate) fn default_swap_data<I: Interface + ?Sized>(
+    interface: &I,
+    key_a: &[u8],
+    key_b: &[u8],
+) -> Result<()> {
+    let value_a = interface
+        .has_data(key_a)?
+        .then(|| interface.raw_get_data(key_a))
+        .transpose()?;
+    let value_b = interface
+        .has_data(key_b)?
+        .then(|| interface.raw_get_data(key_b))
+        .transpose()?;
+    match value_b {
+        Some(value) => interface.raw_set_data(key_a, &value)?,
+        None if value_a.is_some() => interface.raw_delete_data(key_a)?,
+        None => {}
+    }
+    match value_a {
+        Some(value) => interface.raw_set_data(key_b, &value)?,
+        None if interface.has_data(key_b)? => interface.raw_delete_data(key_b)?,
+        None => {}
+    }
+    Ok(())
+}
+
+/// Default implementation of `Interface::rate_limit`
+pub(crate) fn default_rate_limit<I: Interface + ?Sized>(
+    interface: &I,
+    key: &[u8],
+    max_per_period: u64,
+    period: u64,
+) -> Result<bool> {
+    let window = interface.get_current_period()? / period;
+    let count = if interface.has_data(key)? {
+        let value = interface.raw_get_data(key)?;
+        let Ok(value) = <[u8; 16]>::try_from(value) else {
+            bail!("Rate limit counter is not 16 bytes long");
+        };
+        let (stored_window, count) = value.split_at(8);
+        if u64::from_le_bytes(stored_window.try_into()?) == window {
+            u64::from_le_bytes(count.try_into()?)
+        } else {
+            0
+        }
+    } else {
+        0
+    };
+    if count >= max_per_period {
+        return Ok(false);
+    }
+    interface.raw_set_data(
+        key,
+        &[window.to_le_bytes(), (count + 1).to_le_bytes()].concat(),
+    )?;
+    Ok(true)
+}
+
+/// Default implementation of `Interface::add_to_data`
+pub(crate) fn default_add_to_data<I: Interface + ?Sized>(
+    interface: &I,
+    key: &[u8],
+    delta: i64,
+    overflow: CounterOverflow,
+) -> Result<u64> {
+    let current = if interface.has_data(key)? {
+        let Ok(bytes) = <[u8; 8]>::try_from(interface.raw_get_data(key)?) else {
+            bail!("Counter value is not 8 bytes long");
+        };
+        u64::from_le_bytes(bytes)
+    } else {
+        0
+    };
+    let res = overflow.add(current, delta)?;
+    interface.raw_set_data(key, &res.to_le_bytes())?;
+    Ok(res)
+}
+
 impl dyn Interface {
     pub fn get_data<T: DeserializeOwned>(&self, key: &[u8]) -> Result<T> {
         Ok(serde_json::from_str::<T>(std::str::from_utf8(
@@ -695,3 +1527,22 @@ impl dyn Interface {
         self.raw_set_data_for(address, key, serde_json::to_string::<T>(value)?.as_bytes())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::CounterOverflow;
+
+    #[test]
+    fn test_counter_overflow() {
+        assert_eq!(CounterOverflow::Checked.add(1, -1).unwrap(), 0);
+        assert!(CounterOverflow::Checked.add(0, -1).is_err());
+        assert!(CounterOverflow::Checked.add(u64::MAX, 1).is_err());
+        assert_eq!(CounterOverflow::Saturating.add(0, -1).unwrap(), 0);
+        assert_eq!(
+            CounterOverflow::Saturating.add(u64::MAX, 1).unwrap(),
+            u64::MAX
+        );
+        assert_eq!(CounterOverflow::Wrapping.add(0, -1).unwrap(), u64::MAX);
+        assert_eq!(CounterOverflow::Wrapping.add(u64::MAX, 1).unwrap(), 0);
+    }
+}
@@ -152,13 +152,15 @@ fn abi_call(store_env: FunctionEnvMut<ABIEnv>, arg_offset: i32) -> Result<i32, W
             let remaining_gas = handler.get_remaining_gas();
             let interface = handler.exec_env.get_interface();
             let module = helper_get_module(interface, bytecode, remaining_gas)?;
-            let response = crate::execution::run_function(
+            let response = crate::execution::run_subcall(
                 interface,
                 module,
                 &req.target_function_name,
                 &req.function_arg,
                 remaining_gas,
                 handler.get_gas_costs().clone(),
+                handler.get_options().clone(),
+                handler.get_call_context(),
             )
             .map_err(|err| WasmV1Error::RuntimeError(format!("Could not run function: {}", err)))?;
             handler.set_remaining_gas(response.remaining_gas);
@@ -202,13 +204,15 @@ fn abi_local_call(store_env: FunctionEnvMut<ABIEnv>, arg_offset: i32) -> Result<
             let interface = handler.exec_env.get_interface();
             let module = helper_get_module(interface, bytecode.clone(), remaining_gas)?;
 
-            let response = crate::execution::run_function(
+            let response = crate::execution::run_subcall(
                 interface,
                 module,
                 &req.target_function_name,
                 &req.function_arg,
                 remaining_gas,
                 handler.get_gas_costs().clone(),
+                handler.get_options().clone(),
+                handler.get_call_context(),
             )
             .map_err(|err| WasmV1Error::RuntimeError(format!("Could not run function: {}", err)))?;
             handler.set_remaining_gas(response.remaining_gas);
@@ -1022,13 +1026,15 @@ fn abi_local_execution(
             let module = helper_get_tmp_module(handler, req.bytecode.clone(), remaining_gas)?;
 
             let interface = handler.exec_env.get_interface();
-            match crate::execution::run_function(
+            match crate::execution::run_subcall(
                 interface,
                 module,
                 &req.target_function_name,
                 &req.function_arg,
                 remaining_gas,
                 handler.get_gas_costs().clone(),
+                handler.get_options().clone(),
+                handler.get_call_context(),
             ) {
                 Ok(response) => {
                     handler.set_remaining_gas(response.remaining_gas);
@@ -1,5 +1,6 @@
 use super::super::env::{ABIEnv, ExecutionEnv};
-use crate::{wasmv1_execution::WasmV1Error, GasCosts};
+use crate::execution::CallContext;
+use crate::{wasmv1_execution::WasmV1Error, GasCosts, RunOptions};
 use std::io::Cursor;
 use wasmer::FunctionEnvMut;
 
@@ -170,6 +171,16 @@ impl<'a, 'b> ABIHandler<'a, 'b> {
         self.exec_env.get_gas_costs()
     }
 
+    /// Get the options of the execution
+    pub fn get_options(&self) -> &RunOptions {
+        self.exec_env.get_options()
+    }
+
+    /// Get the call context given to the sub-calls
+    pub(crate) fn get_call_context(&self) -> CallContext {
+        self.exec_env.get_call_context()
+    }
+
     /// Get gas cost
     pub fn get_gas_cost(&self, abi_name: &str) -> u64 {
         *self
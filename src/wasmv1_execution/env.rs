@@ -1,8 +1,9 @@
 use std::sync::Arc;
 
 use super::{ffi::Ffi, WasmV1Error};
+use crate::execution::CallContext;
 use crate::types::Interface;
-use crate::GasCosts;
+use crate::{GasCosts, RunOptions};
 use parking_lot::Mutex;
 use wasmer::{AsStoreMut, AsStoreRef, Imports, Instance, InstantiationError, TypedFunction};
 use wasmer_middlewares::metering::{self, MeteringPoints};
@@ -21,6 +22,10 @@ pub struct ExecutionEnv {
     interface: Box<dyn Interface>,
     /// Gas costs of different execution operations.
     gas_costs: GasCosts,
+    /// Options of the execution, not applied but passed down to the sub-calls
+    options: RunOptions,
+    /// Context given by the caller, passed down as is to the sub-calls
+    call_context: CallContext,
     /// Instance to execute
     pub(crate) instance: Instance,
     /// Memory interface
@@ -39,6 +44,8 @@ impl ExecutionEnv {
         module: &super::WasmV1Module,
         interface: &dyn Interface,
         gas_costs: GasCosts,
+        options: RunOptions,
+        call_context: CallContext,
         import_object: &Imports,
     ) -> Result<Self, WasmV1Error> {
         // Create the instance
@@ -88,6 +95,8 @@ impl ExecutionEnv {
         // Return the environment
         Ok(Self {
             gas_costs,
+            options,
+            call_context,
             interface: interface.clone_box(),
             instance,
             ffi,
@@ -198,6 +207,16 @@ impl ExecutionEnv {
         &self.gas_costs
     }
 
+    /// Get the options of the execution.
+    pub fn get_options(&self) -> &RunOptions {
+        &self.options
+    }
+
+    /// Get the call context given to the sub-calls.
+    pub(crate) fn get_call_context(&self) -> CallContext {
+        self.call_context
+    }
+
     /// Get the memory maximum size in bytes
     pub fn get_max_mem_size(&self, store: &mut impl AsStoreMut) -> u64 {
         self.ffi.get_max_mem_size(&store)
@@ -5,13 +5,13 @@ mod ffi;
 
 use self::env::{ABIEnv, ExecutionEnv};
 use crate::error::VMResult;
-use crate::execution::Compiler;
+use crate::execution::{CallContext, Compiler};
 use crate::middlewares::gas_calibration::{
     get_gas_calibration_result, GasCalibration, GasCalibrationResult,
 };
 use crate::settings::max_number_of_pages;
 use crate::tunable_memory::LimitingTunables;
-use crate::{GasCosts, Interface, Response, VMError};
+use crate::{GasCosts, Interface, Response, RunOptions, VMError};
 use abi::*;
 use anyhow::Result;
 pub(crate) use error::*;
@@ -103,6 +103,15 @@ impl WasmV1Module {
             .functions()
             .any(|export| export.name() == function)
     }
+
+    /// Names of the functions exported by the compiled module
+    pub(crate) fn exported_functions(&self) -> Vec<String> {
+        self.binary_module
+            .exports()
+            .functions()
+            .map(|export| export.name().to_string())
+            .collect()
+    }
 }
 
 // Compiler feature.
@@ -130,6 +139,9 @@ const FEATURES: Features = Features {
 };
 
 pub(crate) fn init_sp_engine(limit: u64, gas_costs: GasCosts) -> Engine {
+    let pages_limit = gas_costs
+        .memory_pages_limit
+        .unwrap_or_else(max_number_of_pages);
     // Singlepass is used to compile arbitrary bytecode.
     //
     // Reference:
@@ -141,7 +153,7 @@ pub(crate) fn init_sp_engine(limit: u64, gas_costs: GasCosts) -> Engine {
     add_middleware(&mut compiler_config, limit, gas_costs);
 
     let base = BaseTunables::for_target(&Target::default());
-    let tunables = LimitingTunables::new(base, Pages(max_number_of_pages()));
+    let tunables = LimitingTunables::new(base, Pages(pages_limit));
 
     let mut engine = Engine::from(
         EngineBuilder::new(compiler_config)
@@ -153,6 +165,9 @@ pub(crate) fn init_sp_engine(limit: u64, gas_costs: GasCosts) -> Engine {
 }
 
 pub(crate) fn init_cl_engine(limit: u64, gas_costs: GasCosts) -> Engine {
+    let pages_limit = gas_costs
+        .memory_pages_limit
+        .unwrap_or_else(max_number_of_pages);
     // Cranelift is used to compile bytecode that will be cached.
     //
     // Reference:
@@ -164,7 +179,7 @@ pub(crate) fn init_cl_engine(limit: u64, gas_costs: GasCosts) -> Engine {
     add_middleware(&mut compiler_config, limit, gas_costs);
 
     let base = BaseTunables::for_target(&Target::default());
-    let tunables = LimitingTunables::new(base, Pages(max_number_of_pages()));
+    let tunables = LimitingTunables::new(base, Pages(pages_limit));
 
     let mut engine = Engine::from(
         EngineBuilder::new(compiler_config)
@@ -185,13 +200,14 @@ where
         compiler_config.push_middleware(gas_calibration);
     } else {
         // Add metering middleware
-        let metering = Arc::new(Metering::new(limit, move |_: &Operator| -> u64 {
-            gas_costs.operator_cost
+        let metering = Arc::new(Metering::new(limit, move |operator: &Operator| -> u64 {
+            gas_costs.metered_operator_cost(operator)
         }));
         compiler_config.push_middleware(metering);
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn exec_wasmv1_module(
     interface: &dyn Interface,
     module: WasmV1Module,
@@ -199,6 +215,8 @@ pub(crate) fn exec_wasmv1_module(
     param: &[u8],
     gas_limit: u64,
     gas_costs: GasCosts,
+    options: RunOptions,
+    call_context: CallContext,
 ) -> VMResult<(Response, Option<GasCalibrationResult>)> {
     // Init store
     let engine = match module.compiler {
@@ -215,14 +233,21 @@ pub(crate) fn exec_wasmv1_module(
     interface.save_gas_remaining_before_subexecution(gas_limit);
 
     // Create an instance of the execution environment.
-    let execution_env =
-        ExecutionEnv::create_instance(&mut store, &module, interface, gas_costs, &import_object)
-            .map_err(|err| {
-                VMError::InstanceError(format!(
-                    "Failed to create instance of execution environment: {}",
-                    err
-                ))
-            })?;
+    let execution_env = ExecutionEnv::create_instance(
+        &mut store,
+        &module,
+        interface,
+        gas_costs,
+        options,
+        call_context,
+        &import_object,
+    )
+    .map_err(|err| {
+        VMError::InstanceError(format!(
+            "Failed to create instance of execution environment: {}",
+            err
+        ))
+    })?;
 
     // Get gas cost of instance creation
     let init_gas_cost = execution_env.get_init_gas_cost();
@@ -235,6 +260,8 @@ pub(crate) fn exec_wasmv1_module(
             return Err(VMError::ExecutionError {
                 error: "Available gas does not cover instance creation".to_string(),
                 init_gas_cost,
+                trap_dump: None,
+                abort: None,
             })
         }
     };
@@ -251,6 +278,8 @@ pub(crate) fn exec_wasmv1_module(
                     function, err
                 ),
                 init_gas_cost,
+                trap_dump: None,
+                abort: None,
             })?;
 
     // Allocate and write function argument to guest memory
@@ -262,6 +291,8 @@ pub(crate) fn exec_wasmv1_module(
                 function, err
             ),
             init_gas_cost,
+            trap_dump: None,
+            abort: None,
         })?;
 
     // Now that we have an instance, we can make the execution environment
@@ -277,6 +308,8 @@ pub(crate) fn exec_wasmv1_module(
             .map_err(|err| VMError::ExecutionError {
                 error: format!("Error while calling guest function {}: {}", function, err),
                 init_gas_cost,
+                trap_dump: None,
+                abort: None,
             })?;
 
     // Take back the execution environment
@@ -294,6 +327,8 @@ pub(crate) fn exec_wasmv1_module(
                 function, err
             ),
             init_gas_cost,
+            trap_dump: None,
+            abort: None,
         })?;
 
     // Get remaining gas
@@ -314,6 +349,11 @@ pub(crate) fn exec_wasmv1_module(
             ret,
             remaining_gas,
             init_gas_cost,
+            gas_used: gas_limit.saturating_sub(remaining_gas),
+            abi_gas: None,
+            abi_timings: Default::default(),
+            used_abis: Default::default(),
+            events: Default::default(),
             #[cfg(feature = "execution-trace")]
             trace: execution_env.trace.clone(),
         },